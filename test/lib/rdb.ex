@@ -19,11 +19,33 @@ defmodule RustlerRocksDB do
   ## Returns
 
   * `{:ok, db_resource, list_of_cf_names}` on success.
-  * `{:error, reason}` on failure.
+  * `{:error, {kind, message}}` on failure. See "Errors" below.
+
+  ## Errors
+
+  RocksDB failures of every function in this module are returned as
+  `{:error, {kind, message}}`, where `message` is RocksDB's description and `kind`
+  one of `:not_found`, `:corruption`, `:io_error`, `:busy`, `:timed_out`,
+  `:incomplete`, `:shutdown_in_progress`, `:invalid_argument`, `:not_supported`,
+  `:try_again`, `:aborted` or `:unknown`. `:busy` and `:try_again` are worth
+  retrying (a conflicting optimistic transaction commits with `:busy`);
+  `:corruption` and `:io_error` are not.
   """
   def open_optimistic_transaction_db(_path, _db_opts, _cf_descriptors),
     do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc """
+  Opens a pessimistic TransactionDB, with the same arguments and returns as
+  `open_optimistic_transaction_db/3`.
+
+  Its transactions lock the keys they write or read for update until they commit
+  or roll back, so a conflicting writer waits for the lock (see
+  `transaction_get_for_update_cf/4`) instead of failing at commit. Savepoints and
+  `transaction_conflicting_keys/1` are for optimistic transactions only.
+  """
+  def open_transaction_db(_path, _db_opts, _cf_descriptors),
+    do: :erlang.nif_error(:nif_not_loaded)
+
   # --- Direct DB Operations ---
 
   @doc "Puts a key-value pair into a specific column family."
@@ -41,7 +63,10 @@ defmodule RustlerRocksDB do
 
   # --- Transaction Management ---
 
-  @doc "Begins a new optimistic transaction. Returns `{:ok, transaction_resource}`."
+  @doc """
+  Begins a new transaction, optimistic or pessimistic depending on the database.
+  Returns `{:ok, transaction_resource}`.
+  """
   def begin_transaction(_db_resource), do: :erlang.nif_error(:nif_not_loaded)
 
   @doc "Commits a transaction. The transaction resource cannot be used after this call."
@@ -52,7 +77,11 @@ defmodule RustlerRocksDB do
 
   # --- Transactional Operations ---
 
-  @doc "Puts a key-value pair into a column family within a transaction."
+  @doc """
+  Puts a key-value pair into a column family within a transaction. A pessimistic
+  transaction waits for the key's lock as long as the `:lock_timeout_ms` default of
+  `transaction_get_for_update_cf/4`.
+  """
   def transaction_put_cf(_transaction_resource, _cf_name, _key, _value),
     do: :erlang.nif_error(:nif_not_loaded)
 
@@ -60,6 +89,53 @@ defmodule RustlerRocksDB do
   def transaction_get_cf(_transaction_resource, _cf_name, _key),
     do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc """
+  Gets a value within a transaction and reserves the key for it.
+
+  A pessimistic transaction locks the key until it ends, waiting while another
+  transaction holds the lock. An optimistic transaction never blocks: it tracks the
+  key, and a conflicting write makes the commit fail with
+  `{:error, {:busy, message}}` instead.
+
+  ## Options
+
+  * `:lock_timeout_ms` - how long to wait for the lock before returning
+    `{:error, :timeout}` (default 1000). `0` fails at once if the key is locked.
+    Optimistic transactions have no lock to wait for.
+
+  Unknown options are an error. Returns `{:ok, value}` or `{:ok, nil}` if the key
+  is not found.
+  """
+  def transaction_get_for_update_cf(_transaction_resource, _cf_name, _key, _opts),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Sets a savepoint in a transaction. Savepoints nest: each rollback undoes the
+  writes since the most recent one.
+  """
+  def transaction_set_savepoint(_transaction_resource),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Undoes the writes made since the most recent savepoint and removes it, keeping the
+  transaction open.
+
+  Returns `:ok`, or `{:error, :no_savepoint}` if no savepoint was set.
+  """
+  def transaction_rollback_to_savepoint(_transaction_resource),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Lists the keys written or read for update by the transaction whose committed
+  value has changed since, i.e. the keys that would make a commit fail now. Keys
+  first touched after a savepoint that was rolled back to are not listed.
+
+  Returns `{:ok, [{cf_name, key}]}`. Best effort: another write can land between
+  this check and the commit, so only the commit's result is authoritative.
+  """
+  def transaction_conflicting_keys(_transaction_resource),
+    do: :erlang.nif_error(:nif_not_loaded)
+
   # --- Iterator Management ---
 
   @doc """
@@ -77,8 +153,1273 @@ defmodule RustlerRocksDB do
   ## Returns
 
   * `{:ok, {key, value}}` if the next item exists.
+  * `{:ok, key}` instead for iterators created with `{:keys_only, true}`.
   * `:finished` if the iterator has reached the end.
-  * `{:error, reason}` on a database error.
+  * `{:error, {kind, message}}` on a database error.
+
+  Also steps the iterators of `iterator/1`, `db_iterator/2` and the other iterator
+  functions below.
   """
   def iterator_next(_iterator_resource), do: :erlang.nif_error(:nif_not_loaded)
+
+  # The functions below work on the database opened by `init/1` or one of the other
+  # `init_*` functions, or on a handle from `db_open/2` for the `db_*` ones.
+
+  # --- Single database ---
+
+  @doc """
+  Opens the database at `db_path`, creating it if missing, as the database of
+  the functions below that take no handle. Returns `true`.
+  """
+  def init(_db_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Opens the database at `db_path` with the options in the keyword list `opts`.
+
+  Supported options:
+  * `{:create_if_missing, bool}` (defaults to `true`)
+  * `{:merge_operator, :list_append}` - values become length-prefixed item lists
+    appended to with `merge_append/2`. The same operator must be given on every open.
+  * `{:merge_operator, :field_map}` - values become field maps updated field by
+    field with `merge_fields/2`.
+  * `{:comparator, :reverse_bytewise}` - iterate keys in descending byte order.
+    The comparator must be the same on every open: RocksDB refuses to open a
+    database whose comparator name differs, and a comparator with the same name
+    but a different ordering silently corrupts it.
+  * `{:block_cache, cache}` - use a cache from `create_cache/1` instead of a private
+    one, so every database opened with it shares the same memory budget.
+  * `{:target_file_size_base, bytes}` - target size of SST files produced at L1.
+  * `{:max_bytes_for_level_base, bytes}` - total size budget of L1.
+  * `{:level0_file_num_compaction_trigger, n}` - number of L0 files that triggers
+    an L0 -> L1 compaction.
+  * `{:compaction_style, :level | :universal | :fifo}` - how SST files are merged.
+    `:level` (the default) keeps read and space amplification low; `:universal`
+    rewrites less, suiting write-heavy, time-series-like data; `:fifo` never merges
+    and instead deletes the oldest files, for data where only the recent part matters.
+  * `{:fifo_max_table_files_size, bytes}` - with `:fifo`, the total SST size above
+    which the oldest files are dropped (1 GiB by default). Dropped data is gone
+    for good, whatever its keys.
+  * `{:universal_size_ratio, percent}` - with `:universal`, a file joins the run
+    being merged only if the run is at most `percent`% larger than it (RocksDB's
+    default is 1). Raising it merges more files per compaction, less often.
+  * `{:universal_min_merge_width, n}` - with `:universal`, the fewest files a
+    compaction merges (2 by default).
+  * `{:universal_max_size_amplification_percent, percent}` - with `:universal`, the
+    space overhead above the size of the data that triggers a full compaction
+    (200 by default). Raise it to make full compactions rarer for append-mostly
+    data, at the cost of disk space.
+
+    Unset `:universal_*` options keep their defaults. `cf_options/1` reports them
+    in `"compaction_options_universal"`, e.g. `"{size_ratio=10;min_merge_width=4;...}"`.
+  * `{:compaction_readahead_size, bytes}` - read SST inputs of compactions in
+    chunks of `bytes` instead of block by block. A few MiB speeds up compaction on
+    network-attached disks, where every small read pays a round trip. Read back
+    with `compaction_readahead/0`.
+  * `{:allow_mmap_reads, true}` - read SST files through memory maps instead of
+    `pread`, saving a syscall per block on read-heavy nodes whose data fits in
+    RAM. Off by default, as in RocksDB. Avoid it on network filesystems and FUSE
+    mounts, where a failed page-in kills the VM with `SIGBUS` instead of returning
+    an I/O error, and on 32-bit hosts with large databases (address space).
+  * `{:allow_mmap_writes, true}` - write SST files through memory maps.
+    Off by default. Same caution; it also cannot be combined with direct I/O.
+  * `{:compression, type}` - how SST blocks are compressed: `:none`, `:snappy` (the
+    default), `:zlib`, `:bz2`, `:lz4`, `:lz4hc` or `:zstd`. The open fails with
+    `:invalid_argument` for a type this build of RocksDB was compiled without;
+    `cf_compression/1` shows what took effect.
+  * `{:bottommost_compression, type}` - a different type for the bottommost level,
+    which holds most of the data and is rarely rewritten, e.g. `:zstd` there with
+    `:lz4` above it. Same types as `:compression`.
+  * `{:bytes_per_sync, bytes}` - have the OS write back SST files in the
+    background every `bytes` written during flush and compaction, instead of one
+    large write-back when the file is closed. Smooths out the I/O spikes of big
+    compactions. Off (0) by default.
+  * `{:wal_bytes_per_sync, bytes}` - the same for WAL files.
+  * `{:user_timestamps, true}` - append an 8-byte user timestamp to every key,
+    written with `put_ts/3` and read with `get_ts/2`. Replaces `:comparator`, and
+    must be given on every open of the database. Plain `put`/`get` fail on such a
+    database since they carry no timestamp. Needs the timestamp-aware APIs of the
+    rocksdb crate (0.22 or newer; this crate tracks master).
+  * `{:disable_wal, true}` - make every write through this handle skip the
+    write-ahead log unless the call overrides it. For stores rebuilt on restart:
+    writes not yet flushed to an SST are lost on a crash or a kill, and only a
+    clean close (or `flush_3`) persists them.
+  * `{:log_pid, pid}` - forward the lines RocksDB writes to its `LOG` file to
+    `pid` as `{:rocksdb_log, level, message}`, with `level` one of `:debug`,
+    `:info`, `:warning` or `:error` and `message` a binary. At most 100 lines a
+    second are sent; the rest are counted and reported in a single `:warning`
+    line, so a burst (opening logs every option) cannot flood the mailbox.
+    Forwarding stops when the pid exits or the database is replaced by another
+    init. Not available with `{:env, :mem}`, whose log never reaches the disk.
+  * `{:env, :default}` - read and write the files under `db_path` (the default).
+  * `{:env, :mem}` - keep every file in memory instead, for tests that should not
+    touch the disk. `db_path` only names the database; its contents are freed as
+    soon as the database is replaced by the next init, and a later open of the
+    same path starts empty.
+  * `{:env, {:rate_limited, bytes_per_sec}}` - use the default env with a rate
+    limiter capping flush and compaction writes at `bytes_per_sec`, to simulate
+    slow storage. Foreground writes (WAL, memtable) are not throttled.
+  * `{:wal_recovery_mode, mode}` - how much WAL corruption to accept when
+    replaying after an unclean shutdown. Defaults to RocksDB's `:point_in_time`.
+    * `:tolerate_corrupted_tail` - ignore an incomplete record at the end of the WAL.
+    * `:absolute_consistency` - refuse to open on any corruption, the tail included.
+    * `:point_in_time` - stop replaying at the first corrupt record, keeping
+      everything before it.
+    * `:skip_any_corruption` - skip corrupt records and keep replaying; may lose
+      writes in the middle of the log.
+  * `{:recycle_log_file_num, n}` - keep up to `n` obsolete WAL files and overwrite
+    them in place instead of allocating new files, avoiding the latency of file
+    creation and growth after each WAL switch. A recycled file still holds old
+    records past the new ones, which recovery cannot tell from a torn write in
+    `:tolerate_corrupted_tail` or `:absolute_consistency` mode, so RocksDB ignores
+    this option with those modes; use it with `:point_in_time` (the default) or
+    `:skip_any_corruption`.
+  * `{:prefix_extractor, n}` - treat the first `n` bytes of every key as its
+    prefix, so SST bloom filters can skip files and blocks without keys of the
+    prefix a `{:prefix, p}` iterator scans. Keys shorter than `n` bytes have no
+    prefix. Must stay the same across opens of a database.
+  * `{:value_cache_entries, n}` - keep the values of up to `n` recently read keys
+    in an LRU in front of `get/1`, which then answers hits without calling into
+    RocksDB. Every write NIF of this module evicts the keys it touches, so the
+    cache never serves stale values; writes made by other handles to the same
+    files (there are none while this one holds the LOCK) would not be seen.
+  """
+  def init_with_opts(_db_path, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Opens an empty database kept entirely in memory, for tests: no file is created
+  on disk, so there is no directory to clean up. Every other NIF works on it as
+  on a database opened with `init/1`. Returns `true`.
+
+  Same as `init_with_opts(name, env: :mem)`: the data is freed as soon as the
+  database is replaced by the next init, and each call starts empty.
+  """
+  def init_memory(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Opens the existing database at `db_path` with exactly the column families in
+  `cf_names`, failing before the open if the database has a different set.
+
+  Returns `true`, or `{:error, {:cf_mismatch, missing, extra}}` with the sorted
+  names expected but not on disk and on disk but not expected. `"default"` always
+  exists and need not be listed. A path without a database is an error too, so a
+  wrong mount point is caught instead of silently starting an empty database.
+  """
+  def open_expecting_cfs(_db_path, _cf_names), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Opens the database at `db_path` with every column family already on disk plus
+  those in `cf_names`, which are created if missing, so `get_4/3`, `put_5/4` and
+  the other `_cf` NIFs can reach all of them. `init/1` opens only the default CF,
+  leaving any other CF of an existing database unreachable.
+
+  A path without a database yet is created. Returns `true`.
+  """
+  def init_cf(_db_path, _cf_names), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Info log forwarding ---
+
+  @doc """
+  Opens the database at `db_path` in TTL mode: entries older than `ttl_secs` are
+  dropped by compaction (reads may still return them until then).
+  """
+  def init_with_ttl(_db_path, _ttl_secs), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Changes the TTL of the open database by closing and reopening it with `ttl_secs`.
+
+  RocksDB's `DBWithTTL::SetTtl` is not exposed by the rocksdb crate, so the TTL
+  the compaction filter uses cannot be changed in place. Reopening is cheap
+  (the WAL is replayed, nothing is rewritten) but:
+  * it fails while an iterator or snapshot of the handle is open or a stream or
+    compaction NIF is running, as those keep the old handle open;
+  * the new TTL applies to every column family;
+  * if the reopen fails the database is left closed and `init_with_ttl/2` must be called.
+  """
+  def reopen_with_ttl(_ttl_secs), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Gets the value of `key` from the default column family, or `nil` if absent."
+  def get(_key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Puts `value` under `key` in the default column family. Returns `true`."
+  def put(_key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Bulk reads ---
+
+  @doc """
+  Looks up keys across column families in one call. `requests` is a list of
+  `{cf_name, key}` or `{cf_name, key, opts}` tuples, answered in order with one of:
+  * `{:ok, value}` - shaped by the request's `{:decode, mode}` option:
+    * `:raw` (the default) - the value as a binary.
+    * `:u64` - an 8-byte big-endian value as an integer, or `{:error, :not_u64}`
+      for a value of another size.
+    * `:len` - the length of the value; the value is read but never copied, which
+      keeps size surveys over large values cheap.
+  * `nil` - the key does not exist.
+  * `{:error, reason}` - that entry failed: `:cf_not_found`, `:unknown_decode` for
+    an unknown `:decode` mode, or `{kind, message}` from RocksDB.
+
+  Keys are grouped by column family and each group read with one batched
+  `MultiGet` of pinned values.
+  """
+  def multi_get_cf_routed(_requests), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Bulk writes ---
+
+  @doc """
+  Atomically puts a list of `{key, value}` tuples into the default column family.
+
+  Either every entry is written or, if any entry is malformed, none are.
+  """
+  def multi_put(_entries), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `multi_put/1`, writing into a named column family."
+  def multi_put_cf(_cf_name, _entries), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Atomically deletes a list of keys from the default column family.
+
+  Either every delete applies or, if any key is malformed, none do.
+  Absent keys are deleted silently, as with single deletes.
+  """
+  def multi_delete(_keys), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `multi_delete/1`, deleting from a named column family."
+  def multi_delete_cf(_cf_name, _keys), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Atomically applies a list of `{:put, cf, key, value}` and `{:delete, cf, key}`
+  operations, which may span several column families (use `"default"` for the
+  default one).
+
+  The operations go through a single `WriteBatch`: they become visible together
+  or not at all, but no keys are locked, so there is no conflict detection against
+  concurrent writers. Use a transaction when a read must be validated.
+
+  Returns `:ok`, or `{:error, reason}` with nothing written if an operation is
+  malformed or names an unknown column family.
+  """
+  def atomic_write(_ops), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Creates an empty write batch. Puts and deletes added with `batch_put/3`,
+  `batch_delete/2` and their `_cf` variants are only applied, all together or
+  not at all, by `batch_write/2`.
+  """
+  def batch_new(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Adds a put into the default column family to `batch`. Returns `:ok`."
+  def batch_put(_batch, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Adds a put into `cf_name` to `batch`. The column family is resolved now, so
+  the batch must be written to the database open at this point: once another
+  init has replaced it, this and `batch_write/2` return
+  `{:error, :batch_db_mismatch}`.
+  """
+  def batch_put_cf(_batch, _cf_name, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Adds a delete from the default column family to `batch`. Returns `:ok`."
+  def batch_delete(_batch, _key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Adds a delete from `cf_name` to `batch`, resolving the column family as
+  `batch_put_cf/4` does.
+  """
+  def batch_delete_cf(_batch, _cf_name, _key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Atomically applies every operation in `batch`, with the write options of
+  `put_4/3` in `opts`. Returns `:ok`, or `{:error, :batch_db_mismatch}` with
+  nothing written if a `_cf` operation was added on a database that another init
+  has replaced since. The batch cannot be used afterwards, even if the write
+  fails.
+  """
+  def batch_write(_batch, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Deletes every key of the default column family in `[start_key, end_key)`
+  with a single range tombstone, instead of one delete per key.
+
+  Snapshots and iterators taken before the call still see the keys, as with
+  point deletes, but the span is not dropped key by key: the tombstone stays,
+  and is checked by every read over the span, until a compaction with no older
+  snapshot left removes it together with the data it covers. Disk space is only
+  reclaimed then. Returns `:ok`.
+  """
+  def delete_range(_start_key, _end_key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `delete_range/2` for column family `cf_name`."
+  def delete_range_cf(_cf_name, _start_key, _end_key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Atomically moves every key starting with `prefix` from column family `src_cf`
+  to `dst_cf`, overwriting keys already present in `dst_cf`.
+
+  All keys and values go through a single `WriteBatch`, held in memory until it
+  is written, and land in one memtable; RocksDB caps a batch at 4 GiB. For large
+  ranges use `move_range_4/4`, whose smaller batches keep every key in exactly one
+  of the CFs across a crash but do not move the whole range at once.
+
+  Returns `{:ok, moved}`.
+  """
+  def move_range(_src_cf, _dst_cf, _prefix), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `move_range/3`, moving at most `max_keys` keys in one atomic batch.
+
+  Moved keys are gone from `src_cf`, so calling it again with the same arguments
+  resumes where the previous call stopped; no cursor needs to be kept.
+
+  Returns `{:ok, moved, :more}` while keys with the prefix remain in `src_cf`,
+  then `{:ok, moved, :done}`.
+  """
+  def move_range_4(_src_cf, _dst_cf, _prefix, _max_keys), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Conditional writes ---
+
+  @doc """
+  Writes `value` under `key` only if the key does not exist yet.
+
+  Returns `true` if the value was written, `false` if the key was already present.
+  """
+  def put_if_absent(_key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `put_if_absent/2`, scoped to a named column family."
+  def put_if_absent_cf(_cf_name, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Column families ---
+
+  @doc """
+  Puts a key-value pair into `cf_name`, creating the column family first if needed,
+  with the options the database was opened with.
+  """
+  def put_cf_autocreate(_cf_name, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Creates several column families as one schema step: either all of them exist
+  afterwards or, on error, none of the new ones do.
+
+  `specs` is a list of `{name, opts}` tuples, with `opts` a keyword list of the
+  column family options of `init_with_opts/2` (`:merge_operator`, `:comparator`,
+  `:block_cache`, `:compaction_style`, ...); ones that apply to the whole handle,
+  like `:disable_wal` or `:log_pid`, are ignored.
+
+  Every spec is parsed and the names are checked against the open CFs before
+  anything is created. RocksDB's atomic `CreateColumnFamilies` is not bound by the
+  rocksdb crate, so the CFs are then created in order and, if one fails, those
+  created by this call are dropped again before the error is returned. A crash
+  in the middle can still leave some of them behind; calling again after one
+  reports them as already existing. Returns `:ok`.
+  """
+  def create_cfs(_specs), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Creates column family `name` with default options. Returns `:ok`, or
+  `{:error, :already_exists}` if it is already open.
+  """
+  def create_cf(_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Drops column family `name` and all of its data. Returns `:ok`, or
+  `{:error, :not_found}` if there is no such column family. The `"default"` CF
+  cannot be dropped.
+  """
+  def drop_cf(_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Lists the column families open on the handle as `{:ok, [name]}`, `"default"`
+  first. Unlike the other NIFs, an uninitialized database is reported as
+  `{:error, :db_not_initialized}`.
+
+  The rocksdb crate does not expose its handle map, so the names come from the
+  CFs on disk, keeping those that have an open handle; CFs that exist on disk
+  but were not opened (see `init/1`) are left out.
+  """
+  def list_cf(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns the approximate on-disk size in bytes of the named column family.
+
+  This is RocksDB's SST-based estimate and excludes data still in the memtable;
+  use `cf_size_2/2` to include it.
+  """
+  def cf_size(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `cf_size/1`; with `include_mem_table` set, the memory used by the CF's
+  active and unflushed memtables is added to the estimate. That is their arena
+  size, which includes RocksDB's per-entry overhead, so it overstates the data
+  they hold rather than estimating it the way the on-disk figure is.
+  """
+  def cf_size_2(_cf_name, _include_mem_table), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Replaces the contents of `cf_name` with the key-value pairs of a dump file
+  written by the dump tool, leaving the rest of the database and the open handle
+  alone. For repairing a single corrupt CF from a known-good dump.
+
+  A dump written with `--shard-bytes` is read from its parts in order: pass the
+  path without the part, e.g. `dump/contractstate.dump` for
+  `dump/contractstate.part0.dump` and onwards. A part whose header carries
+  another index than its name is an error.
+
+  The whole dump is read through first, and an unreadable, truncated or
+  keys-only dump is an error that leaves the CF untouched. Only then is the CF
+  dropped, recreated with the options the database was opened with (keeping its
+  `:merge_operator`, `:comparator` and the like) and the dump replayed in
+  batches. If replay still fails partway (a write error, or the dump changed in
+  between), the CF is dropped and recreated again so it is left empty rather than
+  half filled, and the error is returned.
+
+  The `"default"` CF cannot be dropped, so it cannot be rebuilt this way. Returns
+  `{:ok, entries}`.
+  """
+  def rebuild_cf_from_dump(_cf_name, _dump_path), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Integer keys ---
+
+  @doc "Puts `value` under the 8-byte big-endian encoding of the integer `key`."
+  def put_u64(_key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Gets the value stored under the integer `key`, or `nil` if absent."
+  def get_u64(_key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Deletes the integer `key`. Deleting an absent key succeeds."
+  def delete_u64(_key), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- User timestamps ---
+
+  @doc """
+  Puts `value` under `key` at the user timestamp `ts`.
+
+  `ts` is an unsigned 64-bit integer stored as the 8-byte timestamp RocksDB
+  appends to the key. Requires a database opened with `{:user_timestamps, true}`,
+  otherwise returns `{:error, :timestamps_disabled}`.
+  """
+  def put_ts(_key, _ts, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Gets the value of `key` as of the user timestamp `ts`: the newest version
+  written with a timestamp `<= ts`.
+
+  Returns `{:ok, value}`, `nil` if the key had no version at `ts`, or
+  `{:error, :timestamps_disabled}` if the database was not opened with
+  `{:user_timestamps, true}`.
+  """
+  def get_ts(_key, _ts), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Partial reads ---
+
+  @doc """
+  Gets the `len` bytes of the value of `key` starting at byte `offset`.
+
+  The value is read pinned, straight from the block cache or memtable, and only
+  the slice is copied into the returned binary.
+
+  Returns `{:ok, slice}`, `nil` if the key does not exist, or
+  `{:error, :out_of_range}` if `offset + len` is past the end of the value.
+  """
+  def get_slice(_key, _offset, _len), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns the length in bytes of the value of `key`, or `:not_found` if the key
+  does not exist; an empty value is `0`. The value is read pinned and never
+  copied, so this is cheap even for large values.
+  """
+  def value_size(_key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `value_size/1` for a key in `cf_name`."
+  def value_size_cf(_cf_name, _key), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Key order ---
+
+  @doc """
+  Compares two keys the way the open database orders them, returning `:lt`,
+  `:eq` or `:gt` for `a` relative to `b`.
+
+  Follows the comparator given to `init_with_opts/2`: bytewise by default,
+  descending with `{:comparator, :reverse_bytewise}`. With `{:user_timestamps, true}`
+  the keys are compared without timestamps, bytewise.
+  """
+  def compare_keys(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Merge operators ---
+
+  @doc """
+  Appends `item` to the list stored at `key` without reading the current value.
+
+  Requires the database to be opened with `{:merge_operator, :list_append}`.
+  Reads return the whole list as concatenated `<<len::32-big, item::binary>>` entries.
+  """
+  def merge_append(_key, _item), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Applies field-level updates to the field map stored at `key` without reading it.
+
+  `ops` is a list of `{field, value}` upserts and `{field, :delete}` removals,
+  applied in order. Requires the database to be opened with
+  `{:merge_operator, :field_map}`. Reads return the map as concatenated
+  `<<len::32-big, field::binary, len::32-big, value::binary>>` pairs sorted by field.
+  """
+  def merge_fields(_key, _ops), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Reads, writes and iterators with options ---
+
+  @doc "Not implemented yet; returns `:not_implemented`."
+  def transaction_get_3(_txn_id, _key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Not implemented yet; returns `:not_implemented`."
+  def transaction_get_4(_txn_id, _key, _opts, _cf), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Gets a value from the default column family using the read options in `opts`.
+
+  * `{:read_tier, :all | :cache_only | :memtable_only}` - where the value may be read from.
+  * `{:snapshot, snapshot}` - read as of a snapshot from `snapshot_new/0`.
+  * `{:fill_cache, bool}` - whether blocks read from disk are added to the block
+    cache (default `true`); turn off for one-off reads of cold data.
+  * `{:verify_checksums, bool}` - whether blocks read from disk are checked against
+    their checksums (default `true`).
+
+  Any other option is an error rather than being ignored.
+
+  Returns `{:ok, value}`, `nil` if the key does not exist, or `:not_found` if a
+  restricted `:read_tier` could not answer from memory (the key may still be on disk).
+  """
+  def get_3(_key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `get_3/2` for a key in column family `cf`. Returns
+  `{:error, :cf_not_found}` if there is no such column family.
+  """
+  def get_4(_key, _opts, _cf), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Not implemented yet; returns `:not_implemented`."
+  def transaction_put_3(_txn_id, _key, _value), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Puts a key-value pair into the default column family within a transaction of
+  `begin_transaction/1`, with the options of `transaction_get_for_update_cf/4`:
+  * `{:lock_timeout_ms, n}` - how long a pessimistic transaction waits for the
+    key's lock (default 1000); 0 fails at once if another transaction holds it.
+
+  Returns `true`, `{:error, :timeout}` if the lock stayed held, or an error if
+  the write fails or an option is unknown.
+  """
+  def transaction_put_4(_txn, _key, _value, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Puts a key-value pair into the default column family using the write options
+  in `opts`:
+  * `{:sync, bool}` - fsync the WAL before returning, so the write survives a
+    machine crash and not just a process crash (default `false`).
+  * `{:disable_wal, bool}` - skip the WAL for this write (defaults to the
+    handle's `:disable_wal` setting).
+
+  Returns `true`, or an error if the write fails or an option is unknown.
+  """
+  def put_4(_key, _value, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `put_4/3` for a key in column family `cf`. Returns
+  `{:error, :cf_not_found}` if there is no such column family.
+  """
+  def put_5(_key, _value, _opts, _cf), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Not implemented yet; returns `:not_implemented`."
+  def transaction_delete_2(_txn_id, _key), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Not implemented yet; returns `:not_implemented`."
+  def transaction_delete_3(_txn_id, _key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Deletes a key from the default column family using the write options in `opts`
+  (see `put_4/3`). Returns `true`, also when the key did not exist.
+  """
+  def delete_3(_key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `delete_3/2` for a key in column family `cf`. Returns
+  `{:error, :cf_not_found}` if there is no such column family.
+  """
+  def delete_4(_key, _opts, _cf), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Not implemented yet; returns `:not_implemented`."
+  def transaction_iterator_2(_txn_id), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Not implemented yet; returns `:not_implemented`."
+  def transaction_iterator_3(_txn_id, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Creates an iterator over the default column family.
+
+  With `{:prefix, prefix}` it starts at `prefix` and finishes at the first key not
+  starting with it, for scanning one logical table of `<<table, id>>` keys. The
+  scan is cheapest when the database was opened with a matching
+  `{:prefix_extractor, byte_size(prefix)}`.
+
+  `{:iterate_lower_bound, key}` (inclusive) and `{:iterate_upper_bound, key}`
+  (exclusive) confine the iterator to a key range inside RocksDB: `:start` and
+  `:end` begin at the bounds, no entry outside them is returned, and the scan
+  finishes at a bound without reading past it. A lower bound above the upper
+  bound gives an empty iterator.
+
+  `{:snapshot, snapshot}` iterates over a snapshot from `snapshot_new/0` instead
+  of the latest data. The same options apply to `iterator_2/2`.
+  """
+  def iterator(_opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `iterator/1` over column family `cf_name`."
+  def iterator_2(_opts, _cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Creates a forward tailing iterator over a column family, for change-feed style reads.
+
+  After `iterator_next` returns `:finished`, calling it again later yields keys
+  written since, continuing after the last key returned.
+
+  RocksDB caveats: a tailing iterator does not hold a snapshot, so it sees writes
+  as they land rather than a consistent view; it only moves forward; and it is
+  only a reliable feed for append-only CFs where new keys sort after existing ones
+  (e.g. big-endian sequence numbers). Keys inserted behind the cursor are skipped.
+  """
+  def tailing_iterator(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns the iterator's position as an opaque binary token, or `nil` if
+  `iterator_next` has not returned an entry yet.
+
+  The token can be persisted and passed back later as `{:iterator_mode, {:resume, token}}`
+  to a new iterator, which continues strictly after the last entry returned, in
+  the same direction. Tokens stay valid across process and node restarts.
+
+  Returns `{:ok, token}` or `nil`.
+  """
+  def iterator_position(_iter_res), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Moves the iterator and returns the entry it lands on, for scans that change
+  direction or jump around without creating a new iterator. `action` is one of:
+  * `:first` / `:last` - the first or last entry.
+  * `{:seek, key}` - the first entry at or after `key`.
+  * `{:seek_for_prev, key}` - the last entry at or before `key`.
+  * `:next` / `:prev` - one step forwards or backwards from the cursor.
+
+  The cursor stays on the returned entry, while `iterator_next/1` returns the
+  entry under the cursor and then steps: on a forward iterator, `:prev` right
+  after `iterator_next/1` returns the same entry again, and `iterator_next/1`
+  right after a move starts with the entry the move returned, continuing in the
+  iterator's original direction.
+
+  Returns `{:ok, {key, value}}` (`{:ok, key}` for iterators created with
+  `{:keys_only, true}`), or `:finished` if the move left the iterator's range.
+  """
+  def iterator_move(_iter_res, _action), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Flushes the memtables of the default column family, or of `{:cf, name}` in
+  `opts`, to SST files.
+
+  With `wait` true it returns only once the data is on disk, e.g. before taking
+  a backup; otherwise the flush is only scheduled. Returns `true`.
+  """
+  def flush_3(_opts, _wait), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `flush_3(cf: cf_name, wait)`."
+  def flush_cf(_cf_name, _wait), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Compacts the keys of `cf` in `[start, end]` (`nil` for an open end) into level
+  `output_level`, e.g. to reclaim the space of a range removed by `delete_range_cf/3`.
+  An `output_level` of -1 leaves the files at the level RocksDB picks. `opts` takes
+  no options yet; any given is an error.
+
+  Like `compact_all/0` it runs on a dirty scheduler, releases the database guard
+  while compacting and keeps the database open until it returns. It is not a
+  `compact_all/0` though: it does not show as `manual_compaction_running` and may
+  run alongside one, or another range compaction. Returns `true`.
+  """
+  def compact_range_5(_start, _end, _opts, _cf, _output_level),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Counts the live keys in the default column family by full iteration.
+
+  Unlike `rocksdb.estimate-num-keys` this is exact, at the cost of an O(n) scan.
+
+  Returns `{:ok, count, total_value_bytes}`.
+  """
+  def count_exact(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `count_exact/0`, scoped to a named column family."
+  def count_exact_cf(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Computes a deterministic digest of everything in a column family, so two nodes
+  can confirm they hold the same state without shipping it.
+
+  Entries are fed to a BLAKE3 hasher in key order as `key_len (u64 big-endian),
+  key, value_len (u64 big-endian), value`; the lengths keep different splits of
+  the same bytes from colliding. The scan reads from an implicit snapshot, so
+  concurrent writes do not tear the result. Nodes only agree if their CFs use the
+  same comparator, since that fixes the order.
+
+  Returns `{:ok, digest}` with a 32-byte binary digest.
+  """
+  def cf_digest(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns whether a column family holds no live keys, by checking if an
+  iterator from the start yields anything. Unlike `count_exact_cf/1` this stops at
+  the first key, though a CF whose keys were all deleted recently may still have
+  to skip over their tombstones.
+
+  Returns `true`/`false`, or `{:error, :cf_not_found}` for an unknown CF.
+  """
+  def cf_is_empty(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns `{smallest_key, largest_key}` of `cf_name`, or `:empty` if it has no
+  keys, with one seek to each end rather than a scan. Tombstones are skipped, so
+  these are the bounds of the live keys. Returns `{:error, :cf_not_found}` for an
+  unknown CF.
+  """
+  def cf_key_range(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Streaming ---
+
+  @doc """
+  Pushes every entry of `cf_name` to `pid` from a background thread, instead of
+  the caller pulling them with `iterator_next/1`. Returns `{:ok, stream}`.
+
+  `pid` receives `{:chunk, stream, [{key, value}, ...]}` messages of up to
+  `chunk_size` entries in key order, then `{:done, stream, count}`, or
+  `{:error, stream, {kind, message}}` if reading fails. `stream` identifies the
+  stream in the messages (match it with `^stream`).
+
+  Sending is credit based so a slow consumer's mailbox stays small: the first
+  two chunks go out at once, and each further chunk only after a `stream_ack/1`
+  for an earlier one. The entries are read from an implicit snapshot taken by
+  this call, and the database is kept open until the stream ends. The stream
+  stops early if `pid` exits or the database is replaced by another init, both
+  noticed within a second; in neither case is `:done` sent.
+  """
+  def stream_cf(_cf_name, _pid, _chunk_size), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Acknowledges one chunk of a `stream_cf/3` stream, letting it send another.
+  Returns `:ok`.
+  """
+  def stream_ack(_stream), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Snapshots ---
+
+  @doc """
+  Returns `{:ok, snapshot}`, a consistent view of the database as it is now: reads
+  with `get_3(key, snapshot: snapshot)` or an iterator created with
+  `{:snapshot, snapshot}` see none of the writes made after this call.
+
+  A snapshot keeps every version it sees from being compacted away, so release
+  it with `snapshot_release/1` when done rather than waiting for garbage
+  collection. Until then it also keeps the database it was taken on open, even
+  once another init has replaced that database; reads through it of any other
+  database return `{:error, :snapshot_db_mismatch}`.
+  """
+  def snapshot_new(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Releases `snapshot` now instead of when it is garbage collected. Later reads
+  through it return `{:error, :snapshot_released}`. Returns `:ok`, also when it
+  was already released.
+  """
+  def snapshot_release(_snapshot), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Block cache ---
+
+  @doc """
+  Creates an LRU block cache of `capacity_bytes` for use with `{:block_cache, cache}`.
+
+  Returns `{:ok, cache_resource}`.
+  """
+  def create_cache(_capacity_bytes), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Returns the number of bytes currently held in `cache`, across every database using it."
+  def cache_usage(_cache_res), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Reports approximate memory usage of the open database, including its block cache.
+
+  Returns `{:ok, %{mem_table_total: n, mem_table_unflushed: n,
+  mem_table_readers_total: n, cache_total: n}}`. `cache_total` is the usage of the
+  cache the database was opened with by `{:block_cache, cache}`, shared with every
+  other database using it. Without that option it is 0: RocksDB then creates a
+  private cache the rocksdb crate gives no access to.
+  """
+  def memory_usage(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Warms the block cache with the keys in `[start, end)` of the default column
+  family, e.g. before a latency-sensitive batch job, without returning any data.
+
+  Runs on a dirty scheduler as it may read a lot from disk. Blocks compete with
+  the rest of the working set, so the range should fit in the cache.
+
+  Returns `{:ok, entries_visited}`.
+  """
+  def prefetch_range(_start, _end), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `prefetch_range/2`, scoped to a named column family."
+  def prefetch_range_cf(_cf_name, _start, _end), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Perf context ---
+
+  @doc "Enables count and timing collection in the calling thread's perf context."
+  def perf_context_enable(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Zeroes the calling thread's perf context counters."
+  def perf_context_reset(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns the calling thread's perf context counters as a map of atom to integer.
+  Times are in nanoseconds.
+  """
+  def perf_context_report(), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Health ---
+
+  @doc """
+  Reports whether RocksDB has hit a background error (flush/compaction I/O failure).
+
+  Returns `:ok`, or `{:error, {:background_errors, count}}` where `count` is the
+  number of background errors since the database was opened.
+
+  RocksDB retries recoverable background errors on its own. `DB::Resume` is not
+  exposed by the rocksdb crate, so once the underlying problem is fixed a database
+  stuck in read-only mode has to be reopened with `init/1` or `init_with_opts/2`.
+  """
+  def background_error(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Reports whether RocksDB is currently throttling writes.
+
+  Returns `:normal`, `:delayed` (writes are being slowed down to the delayed write
+  rate) or `:stopped` (writes block until compaction catches up). Poll this from
+  producers to back off before a stall rather than after. The rocksdb crate has no
+  event-listener support, so there is no push notification of changes.
+  """
+  def write_stall_status(), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Effective options ---
+
+  @doc """
+  Returns the options a column family is actually running with, as RocksDB
+  recorded them in the latest OPTIONS file of the database.
+
+  The rocksdb crate cannot read options back from an open handle, so this parses
+  what RocksDB persisted after sanitizing them: values it rejected or could not
+  honour in this build (e.g. an unavailable compression) show up as the value it
+  fell back to, not the value requested.
+
+  Returns `{:ok, %{name => value}}` with RocksDB's option names and string values,
+  e.g. `"compression" => "kSnappyCompression"`, `"write_buffer_size" => "67108864"`,
+  `"comparator" => "leveldb.BytewiseComparator"`. Block-based table options are
+  included with a `table_options.` prefix, e.g. `"table_options.block_size"`.
+  """
+  def cf_options(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns `{:ok, bytes}`, the compaction readahead size the database runs with,
+  as recorded in its latest OPTIONS file (see `cf_options/1`).
+  """
+  def compaction_readahead(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Would change the compaction readahead size of the open database.
+
+  RocksDB can change it at runtime, but only through `SetDBOptions`; the rocksdb
+  crate binds `SetOptions` alone, which takes column family options and rejects
+  DB-wide ones like this. Always returns `{:error, :not_supported}`: pass
+  `{:compaction_readahead_size, bytes}` to `init_with_opts/2` instead.
+  """
+  def set_compaction_readahead(_bytes), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Reports the compression `cf_name` runs with, from its latest OPTIONS file
+  (see `cf_options/1`), to confirm which type each level ended up with.
+
+  Returns `{:ok, %{compression: type, bottommost_compression: type | nil,
+  levels: [type]}}`. `bottommost_compression` is `nil` when unset, i.e. the same
+  as `compression`. `levels` holds the type of each level from L0 down: the same
+  type everywhere but the last level, which gets `bottommost_compression`.
+  RocksDB actually applies it to the lowest level that holds data, which is the
+  last one once the database has grown. Types are as for `init_with_opts/2`, or
+  `:unknown` for one this module does not name.
+  """
+  def cf_compression(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Memtables ---
+
+  @doc """
+  Seals the active memtable of the default column family, making it immutable,
+  and starts a new one. Intended as a test hook for controlling memtable rotation.
+
+  This is emulated with a non-waiting flush, so the sealed memtable is also
+  written to an SST shortly after, in the background; it is not kept in memory
+  until the next natural flush. An empty memtable is left as is.
+  """
+  def switch_memtable(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `switch_memtable/0`, scoped to a named column family."
+  def switch_memtable_cf(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Compaction ---
+
+  @doc """
+  Compacts every column family over its whole key range, down to the bottommost
+  level, and blocks until done. Returns `true`.
+
+  This can take minutes, so it runs on a dirty scheduler and releases the
+  database guard while compacting: other NIFs, reads and writes keep working, and
+  `compaction_progress/0` can be polled from another process meanwhile. Like an
+  iterator, it keeps the database open until it returns even if an init replaces
+  it, and creating or dropping a column family waits for it.
+  """
+  def compact_all(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Rewrites the whole of `cf_name`, the bottommost level included, and blocks
+  until done. Returns `true`.
+
+  A plain compaction leaves files already at the bottommost level alone, so
+  tombstones and the versions they shadow can pile up there and slow scans down.
+  This forces those files through compaction too, dropping every tombstone no
+  snapshot still needs. It rewrites all of the CF's data; like `compact_all/0` it
+  runs on a dirty scheduler, releases the database guard while compacting, keeps
+  the database open until it returns and shows in `compaction_progress/0`.
+  """
+  def scrub_cf(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Compacts part of `[start, end]` in `cf_name` (`nil` for an open end), at most
+  about `max_bytes` of it, so a large compaction can be spread over several
+  maintenance windows.
+
+  The keys from `start` are scanned until their keys and values add up to
+  `max_bytes`, and only that prefix of the range is compacted. Returns
+  `{:ok, :done}` when the rest of the range was covered, or `{:ok, :more, cursor}`
+  where `cursor` is the first key left out: pass it as `start` in the next call
+  to carry on without redoing work. The budget counts uncompressed bytes, so the
+  I/O of a call is usually less (compression) or more (overlapping files in the
+  level below) than `max_bytes`. At least one key is compacted per call.
+
+  Like `compact_all/0` it runs on a dirty scheduler, releases the database guard
+  while compacting and keeps the database open until it returns. The range is in
+  the database's key order, so with `{:comparator, :reverse_bytewise}` `start` is
+  the larger key.
+  """
+  def compact_range_budgeted(_cf_name, _start, _end, _max_bytes),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Reports compaction activity, for showing progress of `compact_all/0` or of
+  background compactions.
+
+  Returns `{:ok, %{running_compactions: n, compaction_pending: bool,
+  pending_compaction_bytes: bytes, manual_compaction_running: bool}}`:
+  * `running_compactions` - compactions executing right now, manual ones included.
+  * `compaction_pending` - whether RocksDB has compactions queued.
+  * `pending_compaction_bytes` - RocksDB's estimate of the bytes compaction still
+    has to rewrite to bring every level under its target size; this shrinking
+    towards 0 is the best available measure of progress.
+  * `manual_compaction_running` - whether `compact_all/0` or `scrub_cf/1` has yet
+    to return.
+
+  The properties are those of the default column family. The rocksdb crate does
+  not support event listeners, so progress can only be polled.
+  """
+  def compaction_progress(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns RocksDB's estimate of the bytes compaction still has to rewrite in
+  `cf_name` (`"default"` for the default column family), as an integer.
+
+  This is `rocksdb.estimate-pending-compaction-bytes`: 0 once every level is
+  within its target size, and the measure write stalls are triggered by
+  (`soft_pending_compaction_bytes_limit`, 64 GiB by default).
+  """
+  def compaction_pending_bytes(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Starts a flush of the memtables of `cf_name` (`"default"` for the default
+  column family) and returns `true` without waiting for it to finish, so a write
+  loop can flush on its own schedule without stalling.
+
+  The rocksdb crate has no event listeners to report completion; poll
+  `flush_pending/1` until it returns `false` instead.
+  """
+  def flush_async(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Returns whether `cf_name` still has sealed memtables waiting for or being
+  written by a flush, e.g. one started by `flush_async/1`.
+  """
+  def flush_pending(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- SST files ---
+
+  @doc """
+  Returns the properties of one live SST file of the open database.
+
+  `file` may be a bare file name (`"000123.sst"`) or a path ending in one.
+
+  Returns `{:ok, %{column_family: name, level: n, size: bytes, num_entries: n,
+  num_deletions: n, smallest_key: key, largest_key: key}}`, or
+  `{:error, :not_found}` if no live SST has that name.
+
+  The rocksdb crate does not bind `SstFileReader`, so a standalone SST cannot be
+  opened or iterated on its own; this reads the metadata the open DB keeps for
+  its files. Files no longer referenced by the DB cannot be inspected.
+  """
+  def sst_dump(_file), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Lists the live SST files of the open database with their age, to tell data
+  that has sat in a level for long from data compaction rewrote recently.
+
+  Returns `{:ok, [%{name: file, column_family: name, level: n,
+  creation_time: secs | :unknown, modification_time: secs | :unknown}]}` with times
+  in Unix seconds.
+
+  The rocksdb crate does not expose table properties, so the `creation_time`
+  RocksDB stores inside each SST cannot be read; both times come from the
+  filesystem instead. `creation_time` is the file's birth time, which many Linux
+  filesystems do not report. SSTs are never modified once written, so
+  `modification_time` is when the flush or compaction that produced the file
+  finished, and is the more reliably available of the two.
+  """
+  def sst_file_times(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Summarizes the current version of the database, to cheaply tell whether two
+  replicas are at the same state without comparing their data.
+
+  Returns `{:ok, %{manifest: file, latest_sequence_number: n, num_live_files: n}}`:
+  * `manifest` - the MANIFEST file named by `CURRENT`, e.g. `"MANIFEST-000005"`.
+    Differs between nodes even when their data matches; mostly useful to know
+    which file to inspect.
+  * `latest_sequence_number` - the sequence number of the last write. A node with
+    a lower one than its peer, after applying the same writes, is behind.
+  * `num_live_files` - the number of SST files in the current version.
+  """
+  def manifest_summary(), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- LSM shape ---
+
+  @doc """
+  Returns the LSM shape of the default column family, one map per level.
+
+  Returns `{:ok, [%{level: n, num_files: n, size_bytes: bytes, score: float}]}`
+  for every configured level, empty ones included. File counts and sizes come from
+  the column family metadata. `score` is the compaction score RocksDB last computed
+  for the level (a level needs compacting once it reaches 1.0) and is `nil` for
+  levels `rocksdb.cfstats` does not report on, which are typically empty ones.
+  """
+  def levels_summary(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `levels_summary/0`, scoped to a named column family."
+  def levels_summary_cf(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Testing hook: pushes all data of the default column family through the LSM
+  tree (memtable flush, then a compaction of every level into the last one) and
+  returns `levels_summary/0` afterwards, so a test can assert where the data
+  ended up.
+
+  Runs synchronously on a dirty scheduler and holds the database for the whole
+  cycle; only meant for tests and small databases.
+  """
+  def force_full_lsm_cycle(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `force_full_lsm_cycle/0` for a named column family."
+  def force_full_lsm_cycle_cf(_cf_name), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Obsolete files ---
+
+  @doc """
+  Forces RocksDB to delete the files it considers obsolete: SSTs replaced by
+  compaction, WALs whose data has been flushed, old MANIFESTs and info logs.
+
+  RocksDB normally only scans for these every `delete_obsolete_files_period_micros`
+  (6 hours by default). The rocksdb crate has no direct binding for the purge, so
+  this toggles file deletions off and on again: re-enabling them runs a full scan
+  and purge. Safe to call while the database is serving traffic; files still
+  referenced by live iterators, snapshots or an ongoing backup are kept.
+
+  Returns `{:ok, %{files_deleted: n, bytes_reclaimed: bytes}}`, counting the files
+  in the DB directory that disappeared during the call. Files removed concurrently
+  by a background compaction are counted as well.
+  """
+  def delete_obsolete_files(), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- WAL archive ---
+
+  @doc """
+  Records that `replica` still needs every write from sequence number `sequence`
+  on, i.e. the next `get_updates_since` it will issue. `purge_wal_archive/1`
+  never deletes a file holding such writes for any registered replica. Calling
+  it again moves the replica's mark; registrations last until the next init.
+  """
+  def wal_replica_ack(_replica, _sequence), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Unregisters a replica from `wal_replica_ack/2`, e.g. one that was decommissioned
+  and would otherwise pin the archive forever.
+  """
+  def wal_replica_forget(_replica), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Deletes archived WAL files (those RocksDB moved to `archive/` after flushing
+  them, kept for `get_updates_since` when the database was opened with a WAL TTL
+  or size limit) according to `policy`:
+  * `{:keep_seconds, n}` - delete files last written more than `n` seconds ago.
+  * `{:keep_sequence, seq}` - delete files holding only writes below `seq`.
+
+  Either way a file is only deleted if all of its writes are below the lowest
+  sequence registered with `wal_replica_ack/2`. A file's last sequence is only
+  known from the start of the next one, so the newest archived file is kept
+  unless a live WAL follows it, and files whose first record cannot be read are
+  always kept.
+
+  Returns `{:ok, %{files_deleted: n, bytes_reclaimed: bytes, archive_bytes: bytes}}`,
+  with `archive_bytes` the size of what is left in the archive.
+  """
+  def purge_wal_archive(_policy), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Checkpoints ---
+
+  @doc """
+  Creates a checkpoint of the open database at `path`, a directory that must
+  not exist yet. SST files are hard-linked when `path` is on the same filesystem,
+  so checkpoints are cheap; the result can be opened as a database on its own.
+
+  Returns `:ok`.
+  """
+  def create_checkpoint(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `create_checkpoint/1`, with the options in `opts`:
+  * `{:fsync_dir, true}` - fsync the parent directory of `path` afterwards, so the
+    checkpoint's directory entry survives a power loss. A no-op on platforms whose
+    filesystems do not need it (Windows).
+  """
+  def create_checkpoint_2(_path, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Backups ---
+
+  @doc """
+  Creates a new backup of the open database in `backup_dir`.
+
+  Backups in the same directory share SST files, so only files created since
+  the previous backup are copied.
+
+  Returns `{:ok, backup_id}`.
+  """
+  def backup_create(_backup_dir), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `backup_create/1`, with the options in `opts`:
+  * `{:fsync_dir, true}` - also fsync the backup directories once the backup is
+    written, so the new entries survive a power loss (see `create_checkpoint_2/2`).
+  """
+  def backup_create_2(_backup_dir, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Lists the backups in `backup_dir`.
+
+  Returns `{:ok, [%{backup_id: id, timestamp: unix_secs, size: bytes, num_files: n}]}`.
+  """
+  def backup_list(_backup_dir), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Restores backup `backup_id` from `backup_dir` into `db_dir`, writing WAL files to `wal_dir`.
+
+  The backup is verified first, so a corrupt backup yields `{:error, reason}`
+  and the caller can fall back to an older one. The target database must not be open.
+  """
+  def backup_restore(_backup_dir, _backup_id, _db_dir, _wal_dir),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Deletes all but the `keep` most recent backups in `backup_dir`."
+  def backup_purge_old(_backup_dir, _keep), do: :erlang.nif_error(:nif_not_loaded)
+
+
+  # --- Database handles ---
+
+  @doc """
+  Opens the database at `db_path` as a new handle, with every column family on
+  disk, and returns `{:ok, db}`. It does not touch the database opened by
+  `init/1`, and the path must not be open elsewhere.
+
+  `opts` takes the RocksDB options of `init_with_opts/2`. The ones the NIFs of
+  the singleton act on outside of RocksDB (`:user_timestamps`,
+  `:value_cache_entries`, `:disable_wal`, `:log_pid`) have no handle equivalent
+  and are rejected.
+  """
+  def db_open(_db_path, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `get_3/2` on handle `db`. Snapshots from `snapshot_new/0` belong to
+  the `init/1` database, so passing one in `opts`, here or to `db_iterator/2`,
+  returns `{:error, :snapshot_db_mismatch}`.
+  """
+  def db_get(_db, _key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `get_4/3` on handle `db`."
+  def db_get_cf(_db, _cf_name, _key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `put_4/3` on handle `db`; `:disable_wal` defaults to `false`."
+  def db_put(_db, _key, _value, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `put_5/4` on handle `db`; `:disable_wal` defaults to `false`."
+  def db_put_cf(_db, _cf_name, _key, _value, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `delete_3/2` on handle `db`; `:disable_wal` defaults to `false`."
+  def db_delete(_db, _key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `delete_4/3` on handle `db`; `:disable_wal` defaults to `false`."
+  def db_delete_cf(_db, _cf_name, _key, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc """
+  Same as `iterator/1` on handle `db`. The iterator keeps the database open, and
+  works with `iterator_next/1` and the other iterator NIFs.
+  """
+  def db_iterator(_db, _opts), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Same as `iterator_2/2` on handle `db`."
+  def db_iterator_cf(_db, _cf_name, _opts), do: :erlang.nif_error(:nif_not_loaded)
 end
@@ -0,0 +1,121 @@
+//! The file format of the dumps written by the dump tool (main.rs), shared with
+//! the NIFs that read them back (`rebuild_cf_from_dump/2`). See the tool's `main`
+//! for the layout.
+
+use std::io::{self, BufRead, Read};
+use std::path::{Path, PathBuf};
+
+/// Magic bytes (including a format version) opening a dump file that has a header.
+/// Plain key-value dumps have no header; their first four bytes are a key length,
+/// which would have to be over 1 GiB to collide with this.
+pub(crate) const DUMP_MAGIC: &[u8; 8] = b"AMADUMP\x01";
+
+/// Magic bytes (including a format version) opening every part of a sharded dump.
+pub(crate) const PART_MAGIC: &[u8; 8] = b"AMAPART\x01";
+
+/// Which fields each entry of a dump file carries.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum DumpMode {
+    /// Length-prefixed key then length-prefixed value. Written without a header.
+    Full,
+    /// Only length-prefixed keys.
+    KeysOnly,
+    /// Only length-prefixed values.
+    ValuesOnly,
+}
+
+impl DumpMode {
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            DumpMode::Full => 0,
+            DumpMode::KeysOnly => 1,
+            DumpMode::ValuesOnly => 2,
+        }
+    }
+
+    pub(crate) fn from_byte(byte: u8) -> io::Result<Self> {
+        match byte {
+            0 => Ok(DumpMode::Full),
+            1 => Ok(DumpMode::KeysOnly),
+            2 => Ok(DumpMode::ValuesOnly),
+            other => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown dump mode {}", other),
+            )),
+        }
+    }
+}
+
+/// Reads one length-prefixed field, or `None` on a clean end of file.
+pub(crate) fn read_field<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len_bytes = [0u8; 4];
+    // Distinguish "no more entries" from a length prefix cut off mid-way.
+    let mut filled = 0;
+    while filled < len_bytes.len() {
+        match reader.read(&mut len_bytes[filled..])? {
+            0 if filled == 0 => return Ok(None),
+            0 => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+            n => filled += n,
+        }
+    }
+
+    let mut data = vec![0u8; u32::from_be_bytes(len_bytes) as usize];
+    reader.read_exact(&mut data)?;
+    Ok(Some(data))
+}
+
+/// Consumes the dump header if there is one and returns the file's mode, and its
+/// part index if it is part of a sharded dump. Headerless files are plain
+/// key-value dumps.
+pub(crate) fn read_header<R: BufRead>(reader: &mut R) -> io::Result<(DumpMode, Option<u32>)> {
+    let sharded = {
+        let start = reader.fill_buf()?;
+        if start.starts_with(PART_MAGIC) {
+            true
+        } else if start.starts_with(DUMP_MAGIC) {
+            false
+        } else {
+            return Ok((DumpMode::Full, None));
+        }
+    };
+    reader.consume(DUMP_MAGIC.len());
+
+    let mut mode_byte = [0u8; 1];
+    reader.read_exact(&mut mode_byte)?;
+    let mode = DumpMode::from_byte(mode_byte[0])?;
+    if !sharded {
+        return Ok((mode, None));
+    }
+    let mut part = [0u8; 4];
+    reader.read_exact(&mut part)?;
+    Ok((mode, Some(u32::from_be_bytes(part))))
+}
+
+/// The path of part `part` of a sharded dump, e.g. `dump/contractstate.part2.dump`
+/// for `dump/contractstate.dump`.
+pub(crate) fn part_path(dump_path: &Path, part: u32) -> PathBuf {
+    dump_path.with_extension(format!("part{}.dump", part))
+}
+
+/// The files holding the dump `dump_path`: the file itself, or if there is no such
+/// file, its parts `part0`, `part1`, ... up to the first missing one.
+pub(crate) fn dump_files(dump_path: &Path) -> Vec<PathBuf> {
+    if dump_path.exists() || !part_path(dump_path, 0).exists() {
+        return vec![dump_path.to_path_buf()];
+    }
+    (0..)
+        .map(|part| part_path(dump_path, part))
+        .take_while(|path| path.exists())
+        .collect()
+}
+
+/// Reads one key-value entry in the dump format, or `None` at the end of the file.
+pub(crate) fn read_entry<R: Read>(reader: &mut R) -> io::Result<Option<(Vec<u8>, Vec<u8>)>> {
+    let Some(key) = read_field(reader)? else {
+        return Ok(None);
+    };
+    let value = read_field(reader)?.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::UnexpectedEof, "entry is missing its value")
+    })?;
+    Ok(Some((key, value)))
+}
@@ -1,23 +1,35 @@
 // lib.rs
 
 use rustler::{
-    Atom, Binary, Encoder, Env, Error, ListIterator, NifResult, OwnedBinary, ResourceArc, Term,
+    Atom, Binary, Decoder, Encoder, Env, Error, ListIterator, NifResult, OwnedBinary, ResourceArc,
+    Term,
 };
 use rustler::types::atom;
+use std::collections::BTreeMap;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use rocksdb::Transaction;
+use rocksdb::SnapshotWithThreadMode;
 use rocksdb::BoundColumnFamily;
 use rocksdb::{
     ColumnFamily, ColumnFamilyDescriptor, DBAccess, Direction, IteratorMode, MultiThreaded,
     OptimisticTransactionDB, OptimisticTransactionOptions, Options, ReadOptions, WriteOptions,
-    DBIterator,
+    DBIterator, TransactionDB, TransactionDBOptions, TransactionOptions,
 };
 use std::path::Path;
 use rocksdb::DBCommon;
 // --- NIF Resources ---
 use std::sync::Arc;
 
+// The NIFs around the single database opened by `init/1`, and database handles
+// from `db_open/2`. They register with this library's `init!` below.
+mod lib2;
+
+// The file format of the dump tool; its writing half is only used by the tool.
+#[allow(dead_code)]
+mod dump_format;
+
 /// A resource holding a thread-safe reference to an open OptimisticTransactionDB.
 pub struct DbResource {
     pub db: OptimisticTransactionDB<MultiThreaded>
@@ -29,15 +41,104 @@ pub struct DbResource {
 /// The transaction itself is wrapped in a `Mutex<Option<...>>` to allow it to be
 /// consumed by `commit` or `rollback` operations.
 pub struct TransactionResource {
+    // The transaction object itself. We wrap it in a Mutex and Option
+    // so we can "take" it when we commit or rollback, preventing reuse.
+    // The lifetime is 'static because we've guaranteed safety with `_db_holder`.
+    txn: Mutex<Option<Transaction<'static, OptimisticTransactionDB<MultiThreaded>>>>,
+
+    // Backs `transaction_conflicting_keys/1`, as the rocksdb crate does not expose
+    // the transaction's own tracked key set.
+    tracked: Mutex<TrackedKeys>,
+
     // A transaction's lifetime is tied to its DB.
     // By holding a `ResourceArc<DbResource>`, we guarantee the DB is not
     // garbage collected while this transaction resource exists.
+    // Declared last so it is dropped after the transaction and snapshots above.
     _db_holder: ResourceArc<DbResource>,
+}
 
-    // The transaction object itself. We wrap it in a Mutex and Option
-    // so we can "take" it when we commit or rollback, preventing reuse.
-    // The lifetime is 'static because we've guaranteed safety with `_db_holder`.
-    txn: Mutex<Option<Transaction<'static, OptimisticTransactionDB<MultiThreaded>>>>,
+/// A resource holding a thread-safe reference to an open pessimistic `TransactionDB`.
+pub struct TransactionDbResource {
+    pub db: TransactionDB<MultiThreaded>
+}
+
+/// A transaction of a `TransactionDbResource`. It locks each key it writes or
+/// reads with `transaction_get_for_update_cf/4` until it commits or rolls back.
+pub struct PessimisticTransactionResource {
+    // Same as in `TransactionResource`.
+    txn: Mutex<Option<Transaction<'static, TransactionDB<MultiThreaded>>>>,
+    _db_holder: ResourceArc<TransactionDbResource>,
+}
+
+/// A transaction of either database, as the transaction NIFs take both.
+enum AnyTransaction {
+    Optimistic(ResourceArc<TransactionResource>),
+    Pessimistic(ResourceArc<PessimisticTransactionResource>),
+}
+
+impl<'a> Decoder<'a> for AnyTransaction {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        match term.decode() {
+            Ok(txn_res) => Ok(AnyTransaction::Optimistic(txn_res)),
+            Err(_) => Ok(AnyTransaction::Pessimistic(term.decode()?)),
+        }
+    }
+}
+
+type TrackedSnapshot = SnapshotWithThreadMode<'static, OptimisticTransactionDB<MultiThreaded>>;
+
+/// The keys a transaction will validate at commit.
+#[derive(Default)]
+struct TrackedKeys {
+    // Every `{cf_name, key}` with a snapshot of the database from when the
+    // transaction first touched it, to compare its committed value against.
+    keys: BTreeMap<(String, Vec<u8>), Arc<TrackedSnapshot>>,
+    // The keys of `keys` in the order they were first tracked.
+    order: Vec<(String, Vec<u8>)>,
+    // How many keys were tracked when each open savepoint was set.
+    savepoints: Vec<usize>,
+    // The last snapshot taken and the sequence number read just before it. Keys
+    // tracked while no write has landed since share it.
+    latest: Option<(u64, Arc<TrackedSnapshot>)>,
+}
+
+impl TrackedKeys {
+    // Untracks the keys first tracked since the most recent savepoint, as RocksDB
+    // does when rolling back to it, and pops that savepoint.
+    fn rollback_to_savepoint(&mut self) {
+        if let Some(mark) = self.savepoints.pop() {
+            for entry in self.order.drain(mark..) {
+                self.keys.remove(&entry);
+            }
+        }
+    }
+}
+
+impl TransactionResource {
+    // Remembers the database as it is the first time the transaction touches `key`.
+    // That is a snapshot, and often one shared with the keys tracked before it; the
+    // reads comparing values are left to `transaction_conflicting_keys/1`.
+    fn track(&self, cf_name: &str, key: &[u8]) {
+        let mut tracked = self.tracked.lock().unwrap();
+        let entry = (cf_name.to_string(), key.to_vec());
+        if tracked.keys.contains_key(&entry) {
+            return;
+        }
+        let db = &self._db_holder.db;
+        let sequence = db.latest_sequence_number();
+        let snapshot = match &tracked.latest {
+            Some((taken_at, snapshot)) if *taken_at == sequence => snapshot.clone(),
+            _ => {
+                // Same lifetime extension as `txn`.
+                let snapshot: TrackedSnapshot = unsafe { std::mem::transmute(db.snapshot()) };
+                let snapshot = Arc::new(snapshot);
+                tracked.latest = Some((sequence, snapshot.clone()));
+                snapshot
+            }
+        };
+        tracked.order.push(entry.clone());
+        tracked.keys.insert(entry, snapshot);
+    }
 }
 
 struct IteratorResource {
@@ -45,12 +146,14 @@ struct IteratorResource {
     _db_holder: ResourceArc<DbResource>,
 }
 
-fn load(env: Env, _: Term) -> bool {
+fn load(env: Env, info: Term) -> bool {
     // Use the `resource!` macro to implement the `Resource` trait for your structs.
     rustler::resource!(DbResource, env);
     rustler::resource!(TransactionResource, env);
     rustler::resource!(IteratorResource, env); // Don't forget this one too!
-    true
+    rustler::resource!(TransactionDbResource, env);
+    rustler::resource!(PessimisticTransactionResource, env);
+    lib2::load(env, info)
 }
 
 // --- Atoms ---
@@ -77,6 +180,25 @@ mod atoms {
         forward,
         reverse,
         next,
+
+        // Transaction option atoms
+        lock_timeout_ms,
+        no_savepoint,
+        timeout,
+
+        // RocksDB error kinds
+        not_found,
+        corruption,
+        io_error,
+        busy,
+        timed_out,
+        incomplete,
+        shutdown_in_progress,
+        invalid_argument,
+        not_supported,
+        try_again,
+        aborted,
+        unknown,
     }
 }
 
@@ -105,8 +227,27 @@ fn parse_db_options(opts_term: Term) -> NifResult<Options> {
     Ok(opts)
 }
 
+// Classifies a RocksDB error into `{kind, message}`, so callers can match on the
+// kind (retry on `:busy`, page someone on `:corruption`) without parsing text.
+fn error_kind(err: &rocksdb::Error) -> atom::Atom {
+    match err.kind() {
+        rocksdb::ErrorKind::NotFound => atoms::not_found(),
+        rocksdb::ErrorKind::Corruption => atoms::corruption(),
+        rocksdb::ErrorKind::IOError => atoms::io_error(),
+        rocksdb::ErrorKind::Busy => atoms::busy(),
+        rocksdb::ErrorKind::TimedOut => atoms::timed_out(),
+        rocksdb::ErrorKind::Incomplete => atoms::incomplete(),
+        rocksdb::ErrorKind::ShutdownInProgress => atoms::shutdown_in_progress(),
+        rocksdb::ErrorKind::InvalidArgument => atoms::invalid_argument(),
+        rocksdb::ErrorKind::NotSupported => atoms::not_supported(),
+        rocksdb::ErrorKind::TryAgain => atoms::try_again(),
+        rocksdb::ErrorKind::Aborted => atoms::aborted(),
+        _ => atoms::unknown(),
+    }
+}
+
 fn to_nif_err(err: rocksdb::Error) -> Error {
-    Error::Term(Box::new(err.to_string()))
+    Error::Term(Box::new((error_kind(&err), err.to_string())))
 }
 
 /// A helper to safely get a `ColumnFamily` handle from a `DbResource`.
@@ -114,21 +255,25 @@ fn get_cf_handle<'a>(db_res: &'a DbResource, cf_name: &'a str) -> NifResult<Arc<
     db_res
         .db
         .cf_handle(cf_name)
-        .ok_or_else(|| Error::Term(Box::new(format!("Column family not found: {}", cf_name))))
+        .ok_or_else(|| cf_not_found(cf_name))
 }
 
-/// Opens an `OptimisticTransactionDB` with specified column families and options.
-///
-/// Returns `{:ok, db_resource, list_of_cf_names}`.
-#[rustler::nif]
-fn open_optimistic_transaction_db<'a>(
-    env: Env<'a>,
-    path: String,
-    db_opts_term: Term<'a>,
-    cf_descriptors_term: Term<'a>,
-) -> NifResult<Term<'a>> {
-    let db_opts = parse_db_options(db_opts_term)?;
+/// Same as `get_cf_handle`, for a `TransactionDbResource`.
+fn get_txn_db_cf_handle<'a>(
+    db_res: &'a TransactionDbResource,
+    cf_name: &'a str,
+) -> NifResult<Arc<BoundColumnFamily<'a>>> {
+    db_res.db.cf_handle(cf_name).ok_or_else(|| cf_not_found(cf_name))
+}
 
+fn cf_not_found(cf_name: &str) -> Error {
+    Error::Term(Box::new(format!("Column family not found: {}", cf_name)))
+}
+
+/// Parses a list of `{cf_name, cf_opts}` into descriptors and their names.
+fn parse_cf_descriptors(
+    cf_descriptors_term: Term,
+) -> NifResult<(Vec<ColumnFamilyDescriptor>, Vec<String>)> {
     let cf_list_iter: ListIterator = cf_descriptors_term.decode()?;
     let mut cf_descriptors = Vec::new();
     let mut cf_names = Vec::new();
@@ -139,6 +284,21 @@ fn open_optimistic_transaction_db<'a>(
         cf_names.push(cf_name.clone());
         cf_descriptors.push(ColumnFamilyDescriptor::new(cf_name, cf_opts));
     }
+    Ok((cf_descriptors, cf_names))
+}
+
+/// Opens an `OptimisticTransactionDB` with specified column families and options.
+///
+/// Returns `{:ok, db_resource, list_of_cf_names}`.
+#[rustler::nif]
+fn open_optimistic_transaction_db<'a>(
+    env: Env<'a>,
+    path: String,
+    db_opts_term: Term<'a>,
+    cf_descriptors_term: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let db_opts = parse_db_options(db_opts_term)?;
+    let (cf_descriptors, cf_names) = parse_cf_descriptors(cf_descriptors_term)?;
 
     let path = Path::new(&path);
     match OptimisticTransactionDB::open_cf_descriptors(&db_opts, path, cf_descriptors) {
@@ -151,6 +311,35 @@ fn open_optimistic_transaction_db<'a>(
     }
 }
 
+/// Opens a pessimistic `TransactionDB`, with the same arguments and result as
+/// `open_optimistic_transaction_db/3`.
+///
+/// Its transactions lock the keys they write or read for update, so a writer
+/// waits for the lock instead of failing at commit; see
+/// `transaction_get_for_update_cf/4` for how long. Savepoints and
+/// `transaction_conflicting_keys/1` are for optimistic transactions only.
+#[rustler::nif]
+fn open_transaction_db<'a>(
+    env: Env<'a>,
+    path: String,
+    db_opts_term: Term<'a>,
+    cf_descriptors_term: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let db_opts = parse_db_options(db_opts_term)?;
+    let (cf_descriptors, cf_names) = parse_cf_descriptors(cf_descriptors_term)?;
+    let txn_db_opts = TransactionDBOptions::default();
+
+    let path = Path::new(&path);
+    match TransactionDB::open_cf_descriptors(&db_opts, &txn_db_opts, path, cf_descriptors) {
+        Ok(db) => {
+            let resource = ResourceArc::new(TransactionDbResource { db });
+            let cf_names_term = cf_names.encode(env);
+            Ok((atoms::ok(), resource, cf_names_term).encode(env))
+        }
+        Err(e) => Err(to_nif_err(e)),
+    }
+}
+
 #[rustler::nif]
 fn put_cf(
     db_res: ResourceArc<DbResource>,
@@ -200,8 +389,14 @@ fn delete_cf(db_res: ResourceArc<DbResource>, cf_name: String, key: Binary) -> N
         .map_err(to_nif_err)
 }
 
+/// Begins a transaction on a database of `open_optimistic_transaction_db/3` or
+/// `open_transaction_db/3`.
 #[rustler::nif]
-fn begin_transaction<'a>(env: Env<'a>, db_res: ResourceArc<DbResource>) -> NifResult<Term<'a>> {
+fn begin_transaction<'a>(env: Env<'a>, db_term: Term<'a>) -> NifResult<Term<'a>> {
+    let db_res: ResourceArc<DbResource> = match db_term.decode() {
+        Ok(db_res) => db_res,
+        Err(_) => return begin_pessimistic_transaction(env, db_term.decode()?),
+    };
     let db = &db_res.db;
     let write_opts = WriteOptions::default();
     let tx_opts = OptimisticTransactionOptions::default();
@@ -213,6 +408,31 @@ fn begin_transaction<'a>(env: Env<'a>, db_res: ResourceArc<DbResource>) -> NifRe
     let static_txn: Transaction<'static, _> = unsafe { std::mem::transmute(txn) };
 
     let txn_res = ResourceArc::new(TransactionResource {
+        txn: Mutex::new(Some(static_txn)),
+        tracked: Mutex::new(TrackedKeys::default()),
+        _db_holder: db_res.clone(),
+    });
+
+    Ok((atoms::ok(), txn_res).encode(env))
+}
+
+fn begin_pessimistic_transaction<'a>(
+    env: Env<'a>,
+    db_res: ResourceArc<TransactionDbResource>,
+) -> NifResult<Term<'a>> {
+    let db = &db_res.db;
+    let write_opts = WriteOptions::default();
+    // RocksDB would wait for a lock as long as the transaction's one timeout; a
+    // timeout of 0 has it fail at once instead, and `with_lock_timeout` waits
+    // as long as each call asks.
+    let mut tx_opts = TransactionOptions::default();
+    tx_opts.set_lock_timeout(0);
+
+    // Same lifetime extension as in `begin_transaction`.
+    let txn = db.transaction_opt(&write_opts, &tx_opts);
+    let static_txn: Transaction<'static, _> = unsafe { std::mem::transmute(txn) };
+
+    let txn_res = ResourceArc::new(PessimisticTransactionResource {
         txn: Mutex::new(Some(static_txn)),
         _db_holder: db_res.clone(),
     });
@@ -222,42 +442,91 @@ fn begin_transaction<'a>(env: Env<'a>, db_res: ResourceArc<DbResource>) -> NifRe
 
 /// Commits a transaction. The transaction resource cannot be used after this call.
 #[rustler::nif]
-fn commit_transaction(txn_res: ResourceArc<TransactionResource>) -> NifResult<atom::Atom> {
-    let mut guard = txn_res.txn.lock().unwrap();
-    if let Some(txn) = guard.take() {
-        txn.commit().map(|_| atoms::ok()).map_err(to_nif_err)
-    } else {
-        Err(Error::Atom("transaction_already_consumed"))
+fn commit_transaction(txn: AnyTransaction) -> NifResult<atom::Atom> {
+    let result = match &txn {
+        AnyTransaction::Optimistic(txn_res) => {
+            let mut guard = txn_res.txn.lock().unwrap();
+            guard.take().map(|txn| {
+                // Release the snapshots of the tracked keys now rather than on garbage collection.
+                *txn_res.tracked.lock().unwrap() = TrackedKeys::default();
+                txn.commit()
+            })
+        }
+        AnyTransaction::Pessimistic(txn_res) => {
+            txn_res.txn.lock().unwrap().take().map(|txn| txn.commit())
+        }
+    };
+    match result {
+        Some(result) => result.map(|_| atoms::ok()).map_err(to_nif_err),
+        None => Err(Error::Atom("transaction_already_consumed")),
     }
 }
 
 /// Rolls back a transaction. The transaction resource cannot be used after this call.
 #[rustler::nif]
-fn rollback_transaction(txn_res: ResourceArc<TransactionResource>) -> NifResult<atom::Atom> {
-    let mut guard = txn_res.txn.lock().unwrap();
-    if let Some(txn) = guard.take() {
-        txn.rollback().map(|_| atoms::ok()).map_err(to_nif_err)
-    } else {
-        Err(Error::Atom("transaction_already_consumed"))
+fn rollback_transaction(txn: AnyTransaction) -> NifResult<atom::Atom> {
+    let result = match &txn {
+        AnyTransaction::Optimistic(txn_res) => {
+            let mut guard = txn_res.txn.lock().unwrap();
+            guard.take().map(|txn| {
+                *txn_res.tracked.lock().unwrap() = TrackedKeys::default();
+                txn.rollback()
+            })
+        }
+        AnyTransaction::Pessimistic(txn_res) => {
+            txn_res.txn.lock().unwrap().take().map(|txn| txn.rollback())
+        }
+    };
+    match result {
+        Some(result) => result.map(|_| atoms::ok()).map_err(to_nif_err),
+        None => Err(Error::Atom("transaction_already_consumed")),
     }
 }
 
-/// Puts a key-value pair into a column family within a transaction.
-#[rustler::nif]
+/// Puts a key-value pair into a column family within a transaction. A pessimistic
+/// transaction waits for the key's lock as long as the default of
+/// `transaction_get_for_update_cf/4`.
+#[rustler::nif(schedule = "DirtyIo")]
 fn transaction_put_cf(
-    txn_res: ResourceArc<TransactionResource>,
+    txn: AnyTransaction,
     cf_name: String,
     key: Binary,
     value: Binary,
 ) -> NifResult<atom::Atom> {
-    let mut guard = txn_res.txn.lock().unwrap();
-    if let Some(txn) = guard.as_mut() {
-        let cf = get_cf_handle(&txn_res._db_holder, &cf_name)?;
-        txn.put_cf(&cf, key.as_slice(), value.as_slice())
-            .map(|_| atoms::ok())
-            .map_err(to_nif_err)
-    } else {
-        Err(Error::Atom("transaction_already_consumed"))
+    put_in_transaction(&txn, &cf_name, key.as_slice(), value.as_slice(), DEFAULT_LOCK_TIMEOUT_MS)
+}
+
+// The body of `transaction_put_cf/4` and `transaction_put_4/4`.
+fn put_in_transaction(
+    txn: &AnyTransaction,
+    cf_name: &str,
+    key: &[u8],
+    value: &[u8],
+    lock_timeout_ms: u64,
+) -> NifResult<atom::Atom> {
+    match txn {
+        AnyTransaction::Optimistic(txn_res) => {
+            let mut guard = txn_res.txn.lock().unwrap();
+            if let Some(txn) = guard.as_mut() {
+                let cf = get_cf_handle(&txn_res._db_holder, cf_name)?;
+                txn_res.track(cf_name, key);
+                txn.put_cf(&cf, key, value)
+                    .map(|_| atoms::ok())
+                    .map_err(to_nif_err)
+            } else {
+                Err(Error::Atom("transaction_already_consumed"))
+            }
+        }
+        AnyTransaction::Pessimistic(txn_res) => {
+            let guard = txn_res.txn.lock().unwrap();
+            if let Some(txn) = guard.as_ref() {
+                let cf = get_txn_db_cf_handle(&txn_res._db_holder, cf_name)?;
+                with_lock_timeout(lock_timeout_ms, || txn.put_cf(&cf, key, value))
+                    .map(|_| atoms::ok())
+            } else {
+                Err(Error::Atom("transaction_already_consumed"))
+            }
+        }
     }
 }
 
@@ -265,20 +534,162 @@ fn transaction_put_cf(
 #[rustler::nif]
 fn transaction_get_cf<'a>(
     env: Env<'a>,
-    txn_res: ResourceArc<TransactionResource>,
+    txn: AnyTransaction,
+    cf_name: String,
+    key: Binary,
+) -> NifResult<Term<'a>> {
+    let value = match &txn {
+        AnyTransaction::Optimistic(txn_res) => {
+            let mut guard = txn_res.txn.lock().unwrap();
+            let txn = guard.as_mut().ok_or(Error::Atom("transaction_already_consumed"))?;
+            let cf = get_cf_handle(&txn_res._db_holder, &cf_name)?;
+            txn.get_cf(&cf, key.as_slice())
+        }
+        AnyTransaction::Pessimistic(txn_res) => {
+            let guard = txn_res.txn.lock().unwrap();
+            let txn = guard.as_ref().ok_or(Error::Atom("transaction_already_consumed"))?;
+            let cf = get_txn_db_cf_handle(&txn_res._db_holder, &cf_name)?;
+            txn.get_cf(&cf, key.as_slice())
+        }
+    };
+    match value {
+        Ok(Some(value)) => {
+            let mut bin = OwnedBinary::new(value.len()).unwrap();
+            bin.as_mut_slice().copy_from_slice(&value);
+            Ok((atoms::ok(), bin.release(env)).encode(env))
+        }
+        Ok(None) => Ok((atoms::ok(), atoms::nil()).encode(env)),
+        Err(e) => Err(to_nif_err(e)),
+    }
+}
+
+// How long a pessimistic transaction waits for a key's lock without
+// `{:lock_timeout_ms, n}`: RocksDB's default `transaction_lock_timeout`.
+const DEFAULT_LOCK_TIMEOUT_MS: u64 = 1000;
+
+// How often `with_lock_timeout` tries again to take a lock.
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Parses the per-call options of `transaction_get_for_update_cf/4` and
+/// `transaction_put_4/4` into how long to wait for a key's lock:
+/// * `{:lock_timeout_ms, n}` - give up with `{:error, :timeout}` after `n`
+///   milliseconds, or at once if `n` is 0 (default 1000, as in RocksDB).
+///
+/// Only pessimistic transactions lock keys. An optimistic one never waits, so
+/// there the timeout is checked but has nothing to bound.
+fn parse_transaction_opts(opts_term: Term) -> NifResult<u64> {
+    let mut lock_timeout_ms = DEFAULT_LOCK_TIMEOUT_MS;
+    for (key_term, value) in lib2::option_pairs(opts_term)? {
+        let key: atom::Atom = key_term.decode()?;
+
+        if key == atoms::lock_timeout_ms() {
+            lock_timeout_ms = value.decode()?;
+        } else {
+            return Err(Error::Term(Box::new(format!(
+                "Unknown transaction option: {:?}",
+                key
+            ))));
+        }
+    }
+    Ok(lock_timeout_ms)
+}
+
+// Runs `op`, which takes a key's lock, until it gets the lock or `lock_timeout_ms`
+// has passed. The pessimistic transactions are begun with a lock timeout of 0, so
+// RocksDB fails a locked key at once with `TimedOut`.
+fn with_lock_timeout<T>(
+    lock_timeout_ms: u64,
+    mut op: impl FnMut() -> Result<T, rocksdb::Error>,
+) -> NifResult<T> {
+    let deadline = Instant::now() + Duration::from_millis(lock_timeout_ms);
+    loop {
+        match op() {
+            Err(e) if e.kind() == rocksdb::ErrorKind::TimedOut => {
+                if Instant::now() >= deadline {
+                    return Err(Error::Term(Box::new(atoms::timeout())));
+                }
+                std::thread::sleep(LOCK_RETRY_INTERVAL);
+            }
+            result => return result.map_err(to_nif_err),
+        }
+    }
+}
+
+/// Gets a value within a transaction and reserves the key for it, with the
+/// options of `parse_transaction_opts`.
+///
+/// A pessimistic transaction locks the key until it ends, waiting up to
+/// `{:lock_timeout_ms, n}` while another transaction holds it, and returns
+/// `{:error, :timeout}` if that one still does. An optimistic transaction never
+/// blocks: it tracks the key for conflict checking, so the commit fails with
+/// `{:error, {:busy, message}}` if another writer changed the key after this read.
+#[rustler::nif(schedule = "DirtyIo")]
+fn transaction_get_for_update_cf<'a>(
+    env: Env<'a>,
+    txn: AnyTransaction,
     cf_name: String,
     key: Binary,
+    opts: Term<'a>,
 ) -> NifResult<Term<'a>> {
-    let mut guard = txn_res.txn.lock().unwrap();
-    if let Some(txn) = guard.as_mut() {
-        let cf = get_cf_handle(&txn_res._db_holder, &cf_name)?;
-        match txn.get_cf(&cf, key.as_slice()) {
-            Ok(Some(value)) => {
-                let mut bin = OwnedBinary::new(value.len()).unwrap();
-                bin.as_mut_slice().copy_from_slice(&value);
-                Ok((atoms::ok(), bin.release(env)).encode(env))
+    let lock_timeout_ms = parse_transaction_opts(opts)?;
+
+    let value = match &txn {
+        AnyTransaction::Optimistic(txn_res) => {
+            let mut guard = txn_res.txn.lock().unwrap();
+            let txn = guard.as_mut().ok_or(Error::Atom("transaction_already_consumed"))?;
+            let cf = get_cf_handle(&txn_res._db_holder, &cf_name)?;
+            txn_res.track(&cf_name, key.as_slice());
+            txn.get_for_update_cf(&cf, key.as_slice(), true).map_err(to_nif_err)?
+        }
+        AnyTransaction::Pessimistic(txn_res) => {
+            let guard = txn_res.txn.lock().unwrap();
+            let txn = guard.as_ref().ok_or(Error::Atom("transaction_already_consumed"))?;
+            let cf = get_txn_db_cf_handle(&txn_res._db_holder, &cf_name)?;
+            with_lock_timeout(lock_timeout_ms, || {
+                txn.get_for_update_cf(&cf, key.as_slice(), true)
+            })?
+        }
+    };
+    match value {
+        Some(value) => Ok((atoms::ok(), vec_to_binary(env, value)?).encode(env)),
+        None => Ok((atoms::ok(), atoms::nil()).encode(env)),
+    }
+}
+
+/// Marks the current state of a transaction, so `transaction_rollback_to_savepoint/1`
+/// can later undo just the writes made after this point. Savepoints nest.
+#[rustler::nif]
+fn transaction_set_savepoint(txn_res: ResourceArc<TransactionResource>) -> NifResult<atom::Atom> {
+    let guard = txn_res.txn.lock().unwrap();
+    if let Some(txn) = guard.as_ref() {
+        txn.set_savepoint();
+        let mut tracked = txn_res.tracked.lock().unwrap();
+        let mark = tracked.order.len();
+        tracked.savepoints.push(mark);
+        Ok(atoms::ok())
+    } else {
+        Err(Error::Atom("transaction_already_consumed"))
+    }
+}
+
+/// Undoes the writes of a transaction since its most recent savepoint and pops
+/// that savepoint; the transaction stays open. Returns `{:error, :no_savepoint}`
+/// if no savepoint is left.
+#[rustler::nif]
+fn transaction_rollback_to_savepoint(
+    txn_res: ResourceArc<TransactionResource>,
+) -> NifResult<atom::Atom> {
+    let guard = txn_res.txn.lock().unwrap();
+    if let Some(txn) = guard.as_ref() {
+        match txn.rollback_to_savepoint() {
+            Ok(()) => {
+                txn_res.tracked.lock().unwrap().rollback_to_savepoint();
+                Ok(atoms::ok())
+            }
+            // RocksDB reports a missing savepoint as `NotFound`.
+            Err(e) if e.kind() == rocksdb::ErrorKind::NotFound => {
+                Err(Error::Term(Box::new(atoms::no_savepoint())))
             }
-            Ok(None) => Ok((atoms::ok(), atoms::nil()).encode(env)),
             Err(e) => Err(to_nif_err(e)),
         }
     } else {
@@ -286,6 +697,43 @@ fn transaction_get_cf<'a>(
     }
 }
 
+/// Lists the keys that would make committing the transaction fail right now, to
+/// give up on a doomed transaction before doing more work in it.
+///
+/// Checks every key the transaction wrote or read with
+/// `transaction_get_for_update_cf/4` against the database: a key whose committed
+/// value changed since the transaction first touched it is a conflict. Keys only
+/// written after a savepoint that was rolled back to are not checked, as the
+/// commit does not check them either. Returns `{:ok, [{cf_name, key}]}`, empty if
+/// the commit would currently succeed.
+///
+/// This is best effort. Another writer can still change a key between this check
+/// and the commit, so the commit can fail after an empty answer; a failed commit
+/// remains the only authoritative verdict. Values are compared rather than
+/// sequence numbers, so a key changed and then changed back is not reported,
+/// while RocksDB would still reject the commit.
+#[rustler::nif]
+fn transaction_conflicting_keys<'a>(
+    env: Env<'a>,
+    txn_res: ResourceArc<TransactionResource>,
+) -> NifResult<Term<'a>> {
+    if txn_res.txn.lock().unwrap().is_none() {
+        return Err(Error::Atom("transaction_already_consumed"));
+    }
+
+    let tracked = txn_res.tracked.lock().unwrap();
+    let mut conflicts = Vec::new();
+    for ((cf_name, key), snapshot) in tracked.keys.iter() {
+        let cf = get_cf_handle(&txn_res._db_holder, cf_name)?;
+        let seen = snapshot.get_cf(&cf, key).map_err(to_nif_err)?;
+        let current = txn_res._db_holder.db.get_cf(&cf, key).map_err(to_nif_err)?;
+        if current != seen {
+            conflicts.push((cf_name.encode(env), vec_to_binary(env, key.clone())?));
+        }
+    }
+    Ok((atoms::ok(), conflicts).encode(env))
+}
+
 /// Creates a new iterator over a column family.
 #[rustler::nif]
 fn iterator_cf<'a>(
@@ -314,9 +762,14 @@ fn vec_to_binary<'a>(env: Env<'a>, data: Vec<u8>) -> NifResult<Term<'a>> {
 
 /// Moves the iterator to the next key-value pair.
 ///
-/// Returns `{:ok, {key, value}}` or `:finished`.
+/// Returns `{:ok, {key, value}}` or `:finished`. Iterators of the `lib2` NIFs
+/// share this NIF's name, so they are passed on to `lib2::iterator_next`.
 #[rustler::nif]
-fn iterator_next<'a>(env: Env<'a>, iter_res: ResourceArc<IteratorResource>) -> NifResult<Term<'a>> {
+fn iterator_next<'a>(env: Env<'a>, iter_term: Term<'a>) -> NifResult<Term<'a>> {
+    let iter_res: ResourceArc<IteratorResource> = match iter_term.decode() {
+        Ok(iter_res) => iter_res,
+        Err(_) => return lib2::iterator_next(env, iter_term.decode()?),
+    };
     let mut guard = iter_res.iter.lock().unwrap();
     match guard.next() {
         Some(Ok((key, value))) => {
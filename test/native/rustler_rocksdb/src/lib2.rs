@@ -4,29 +4,130 @@
 use rustler::Error;
 use rustler::{Encoder, Env, NifResult, Term};
 
-use std::sync::Mutex;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{self, AtomicBool, AtomicU64};
+use std::sync::{Arc, Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
 use lazy_static::lazy_static;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use crate::dump_format::{dump_files, read_entry, read_header, DumpMode};
 use rustler::Binary;
 use rustler::OwnedBinary;
 
+use rocksdb::backup::{BackupEngine, BackupEngineOptions, RestoreOptions};
+use rocksdb::checkpoint::Checkpoint;
+use rocksdb::perf::{PerfContext, PerfMetric, PerfStatsLevel};
+use rocksdb::DBRecoveryMode;
 use rocksdb::Direction;
-use rocksdb::{Options, DB};
+use rocksdb::Env as RocksEnv;
+use rocksdb::{
+    BlockBasedOptions, BottommostLevelCompaction, Cache, ColumnFamily, CompactOptions,
+    DBCompactionStyle, DBCompressionType, FifoCompactOptions, FlushOptions, MergeOperands, Options,
+    SliceTransform, UniversalCompactOptions, WriteBatch, DB,
+};
 use rustler::types::atom;
 //use rustler::types::tuple;
-use rocksdb::DBIterator;
-use rocksdb::IteratorMode;
+use rocksdb::DBRawIterator;
 use rocksdb::ReadOptions;
+use rocksdb::ReadTier;
+use rocksdb::SnapshotWithThreadMode;
+use rocksdb::WriteOptions;
+use rustler::env::SavedTerm;
+use rustler::types::map::MapIterator;
 use rustler::ListIterator;
 use rustler::ResourceArc;
+use rustler::{LocalPid, OwnedEnv};
 
 lazy_static! {
-    static ref DB_INSTANCE: Mutex<Option<DB>> = Mutex::new(None);
+    static ref DB_INSTANCE: Mutex<Option<Arc<SharedDb>>> = Mutex::new(None);
+    // Set by `{:value_cache_entries, n}`. Only locked while `DB_INSTANCE` is held.
+    static ref VALUE_CACHE: Mutex<Option<ValueCache>> = Mutex::new(None);
+    // Per replica, the first sequence number it still needs, from `wal_replica_ack/2`.
+    static ref WAL_REPLICA_ACKS: Mutex<HashMap<String, u64>> = Mutex::new(HashMap::new());
 }
 
+// Whether the open database was opened with `{:user_timestamps, true}`. Only
+// written while `DB_INSTANCE` is locked, by whichever init replaces the handle.
+static TIMESTAMPS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+// Whether writes skip the WAL unless a call overrides it, from `{:disable_wal, true}`.
+static WAL_DISABLED_BY_DEFAULT: AtomicBool = AtomicBool::new(false);
+
+// Whether the open database orders keys with `{:comparator, :reverse_bytewise}`.
+static KEYS_REVERSED: AtomicBool = AtomicBool::new(false);
+
+// Bumped by every init, so background threads tied to a handle notice it is gone.
+static HANDLE_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+// Set while `compact_all/0` runs, which it does without holding `DB_INSTANCE`.
+static MANUAL_COMPACTION_RUNNING: AtomicBool = AtomicBool::new(false);
+
+// An open database, the one in `DB_INSTANCE` or that of a `DbResource`. Every use
+// of it read-locks it; creating and dropping column families write-locks it, as
+// `DB` needs `&mut` for those. Iterators and NIFs that run for long hold an `Arc`
+// of their own, so an init replacing the database in `DB_INSTANCE` does not close
+// it under them: it stays open until the last of them is done.
+pub struct SharedDb {
+    db: RwLock<DB>,
+    // What `db` was opened with, which is also what every column family of it
+    // gets when it is next opened.
+    options: Options,
+    // The `{:block_cache, cache}` it was opened with, for `memory_usage/0`: RocksDB
+    // only reports the usage of caches it is handed.
+    block_cache: Option<Cache>,
+}
+
+impl SharedDb {
+    fn new(db: DB, options: Options) -> Arc<SharedDb> {
+        SharedDb::with_block_cache(db, options, None)
+    }
+
+    fn with_block_cache(db: DB, options: Options, block_cache: Option<Cache>) -> Arc<SharedDb> {
+        Arc::new(SharedDb {
+            db: RwLock::new(db),
+            options,
+            block_cache,
+        })
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, DB> {
+        self.db.read().unwrap()
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, DB> {
+        self.db.write().unwrap()
+    }
+}
+
+// Read-locks the database in `DB_INSTANCE`, for callers holding its guard.
+fn read_db(instance: &Option<Arc<SharedDb>>) -> NifResult<RwLockReadGuard<'_, DB>> {
+    instance
+        .as_deref()
+        .map(SharedDb::read)
+        .ok_or(Error::Atom("db_not_initialized"))
+}
+
+// Write-locks the database in `DB_INSTANCE`, for callers holding its guard. This
+// waits for any long-running NIF reading it, like `compact_all/0`, to finish.
+fn write_db(instance: &Option<Arc<SharedDb>>) -> NifResult<RwLockWriteGuard<'_, DB>> {
+    instance
+        .as_deref()
+        .map(SharedDb::write)
+        .ok_or(Error::Atom("db_not_initialized"))
+}
+
+// The database in `DB_INSTANCE`, for NIFs that use it without holding that guard.
+fn current_db() -> NifResult<Arc<SharedDb>> {
+    DB_INSTANCE
+        .lock()
+        .unwrap()
+        .clone()
+        .ok_or(Error::Atom("db_not_initialized"))
+}
 
 mod atoms {
     rustler::atoms! {
@@ -34,12 +135,21 @@ mod atoms {
         ok,
         error,
         finished,
+        db_not_initialized,
 
         // Iterator option atoms
         iterator_mode,
         start,
         end,
         from,
+        after,
+        before,
+        resume,
+        flush_before,
+        keys_only,
+        prefix,
+        iterate_lower_bound,
+        iterate_upper_bound,
 
         // Direction atoms
         forward,
@@ -48,6 +158,193 @@ mod atoms {
         prev,
         first,
         last,
+        seek,
+        seek_for_prev,
+
+        // DB option atoms
+        create_if_missing,
+        merge_operator,
+        list_append,
+        field_map,
+        comparator,
+        reverse_bytewise,
+        block_cache,
+        target_file_size_base,
+        max_bytes_for_level_base,
+        level0_file_num_compaction_trigger,
+        compaction_style,
+        universal,
+        fifo,
+        fifo_max_table_files_size,
+        universal_size_ratio,
+        universal_min_merge_width,
+        universal_max_size_amplification_percent,
+        compaction_readahead_size,
+        bytes_per_sync,
+        wal_bytes_per_sync,
+        recycle_log_file_num,
+        prefix_extractor,
+        allow_mmap_reads,
+        allow_mmap_writes,
+        compression,
+        bottommost_compression,
+        user_timestamps,
+        timestamps_disabled,
+        value_cache_entries,
+        disable_wal,
+        log_pid,
+        env,
+        default,
+        mem,
+        rate_limited,
+        wal_recovery_mode,
+        tolerate_corrupted_tail,
+        absolute_consistency,
+        point_in_time,
+        skip_any_corruption,
+
+        // Compression types
+        none,
+        snappy,
+        zlib,
+        bz2,
+        lz4,
+        lz4hc,
+        zstd,
+        levels,
+
+        // Memory usage keys
+        mem_table_total,
+        mem_table_unflushed,
+        mem_table_readers_total,
+        cache_total,
+
+        // Merge operand and write operation atoms
+        delete,
+        put,
+        done,
+        more,
+
+        // Read option atoms
+        read_tier,
+        snapshot,
+        fill_cache,
+        verify_checksums,
+
+        // Flush option atoms
+        cf,
+
+        // Write option atoms
+        sync,
+        memtable_only,
+        cache_only,
+        all,
+        not_found,
+        out_of_range,
+        snapshot_released,
+        snapshot_db_mismatch,
+        batch_db_mismatch,
+        cf_mismatch,
+        cf_not_found,
+        already_exists,
+        empty,
+
+        // CF streaming messages
+        chunk,
+
+        // Routed multi-get decodes
+        decode,
+        raw,
+        u64,
+        len,
+        unknown_decode,
+        not_u64,
+
+        // Perf context counters
+        user_key_comparison_count,
+        block_cache_hit_count,
+        block_read_count,
+        block_read_byte,
+        block_read_time,
+        get_snapshot_time,
+        get_from_memtable_time,
+        get_from_memtable_count,
+        get_from_output_files_time,
+        seek_on_memtable_count,
+        internal_key_skipped_count,
+        internal_delete_skipped_count,
+
+        // Health atoms
+        background_errors,
+        normal,
+        delayed,
+        stopped,
+
+        // SST file property keys
+        column_family,
+        level,
+        num_entries,
+        num_deletions,
+        smallest_key,
+        largest_key,
+        name,
+        creation_time,
+        modification_time,
+
+        // Manifest summary keys
+        manifest,
+        latest_sequence_number,
+        num_live_files,
+
+        // Level summary keys
+        size_bytes,
+        score,
+
+        // Obsolete file cleanup keys
+        files_deleted,
+        bytes_reclaimed,
+        archive_bytes,
+        keep_seconds,
+        keep_sequence,
+
+        // RocksDB error kinds
+        corruption,
+        io_error,
+        busy,
+        timed_out,
+        incomplete,
+        shutdown_in_progress,
+        invalid_argument,
+        not_supported,
+        try_again,
+        aborted,
+        unknown,
+
+        // Key comparison results
+        lt,
+        eq,
+        gt,
+
+        // Backup info keys
+        backup_id,
+        timestamp,
+        size,
+        num_files,
+
+        // Info log forwarding atoms
+        rocksdb_log,
+        debug,
+        info,
+        warning,
+
+        // Compaction progress keys
+        running_compactions,
+        compaction_pending,
+        pending_compaction_bytes,
+        manual_compaction_running,
+
+        // Checkpoint and backup option atoms
+        fsync_dir,
     }
 }
 
@@ -61,6 +358,19 @@ fn binary_to_vec(binary: Term) -> NifResult<Vec<u8>> {
     }
 }
 
+// Helper to collect the `{key, value}` pairs of an options term. Options may be
+// passed either as a keyword list (`[iterator_mode: :start]`) or as a map
+// (`%{iterator_mode: :start}`); every options parser goes through here.
+pub(crate) fn option_pairs<'a>(opts: Term<'a>) -> NifResult<Vec<(Term<'a>, Term<'a>)>> {
+    if opts.is_map() {
+        let map = MapIterator::new(opts).ok_or(Error::BadArg)?;
+        Ok(map.collect())
+    } else {
+        let list: ListIterator = opts.decode()?;
+        list.map(|item| item.decode::<(Term, Term)>()).collect()
+    }
+}
+
 // Helper to convert Vec<u8> to Elixir binary
 fn vec_to_binary<'a>(env: Env<'a>, data: Vec<u8>) -> NifResult<Term<'a>> {
     let mut binary = OwnedBinary::new(data.len())
@@ -69,6 +379,35 @@ fn vec_to_binary<'a>(env: Env<'a>, data: Vec<u8>) -> NifResult<Term<'a>> {
     Ok(binary.release(env).encode(env))
 }
 
+// Classifies a RocksDB error into `{kind, message}`, so callers can match on the
+// kind (retry on `:busy`, page someone on `:corruption`) without parsing text.
+fn error_kind(err: &rocksdb::Error) -> atom::Atom {
+    match err.kind() {
+        rocksdb::ErrorKind::NotFound => atoms::not_found(),
+        rocksdb::ErrorKind::Corruption => atoms::corruption(),
+        rocksdb::ErrorKind::IOError => atoms::io_error(),
+        rocksdb::ErrorKind::Busy => atoms::busy(),
+        rocksdb::ErrorKind::TimedOut => atoms::timed_out(),
+        rocksdb::ErrorKind::Incomplete => atoms::incomplete(),
+        rocksdb::ErrorKind::ShutdownInProgress => atoms::shutdown_in_progress(),
+        rocksdb::ErrorKind::InvalidArgument => atoms::invalid_argument(),
+        rocksdb::ErrorKind::NotSupported => atoms::not_supported(),
+        rocksdb::ErrorKind::TryAgain => atoms::try_again(),
+        rocksdb::ErrorKind::Aborted => atoms::aborted(),
+        _ => atoms::unknown(),
+    }
+}
+
+fn to_nif_err(err: rocksdb::Error) -> Error {
+    Error::Term(Box::new((error_kind(&err), err.to_string())))
+}
+
+// Looks up `cf_name`, reporting a missing one as `{:error, :cf_not_found}`.
+fn cf_or_err<'d>(db: &'d DB, cf_name: &str) -> NifResult<&'d ColumnFamily> {
+    db.cf_handle(cf_name)
+        .ok_or_else(|| Error::Term(Box::new(atoms::cf_not_found())))
+}
+
 #[rustler::nif]
 fn init(db_path: String) -> NifResult<bool> {
     let mut db_guard = DB_INSTANCE.lock().unwrap();
@@ -83,7 +422,8 @@ fn init(db_path: String) -> NifResult<bool> {
 
     match DB::open(&options, Path::new(&db_path)) {
         Ok(db) => {
-            *db_guard = Some(db);
+            *db_guard = Some(SharedDb::new(db, options));
+            apply_handle_settings(None);
 
             Ok(true)
         }
@@ -96,21 +436,619 @@ fn init(db_path: String) -> NifResult<bool> {
     }
 }
 
+// Orders keys by descending bytes, so iterating from `:start` yields the largest key first.
+fn reverse_bytewise_compare(a: &[u8], b: &[u8]) -> Ordering {
+    b.cmp(a)
+}
+
+// Maps `:none | :snappy | :zlib | :bz2 | :lz4 | :lz4hc | :zstd` to a compression type.
+fn parse_compression_type(value: Term) -> NifResult<DBCompressionType> {
+    let kind: atom::Atom = value.decode()?;
+    [
+        (atoms::none(), DBCompressionType::None),
+        (atoms::snappy(), DBCompressionType::Snappy),
+        (atoms::zlib(), DBCompressionType::Zlib),
+        (atoms::bz2(), DBCompressionType::Bz2),
+        (atoms::lz4(), DBCompressionType::Lz4),
+        (atoms::lz4hc(), DBCompressionType::Lz4hc),
+        (atoms::zstd(), DBCompressionType::Zstd),
+    ]
+    .into_iter()
+    .find(|(name, _)| *name == kind)
+    .map(|(_, compression)| compression)
+    .ok_or_else(|| Error::Term(Box::new("Unknown compression")))
+}
+
+// The result of parsing the `init_with_opts/2` options: the RocksDB options
+// plus the settings that live outside of them.
+struct ParsedDbOptions {
+    options: Options,
+    user_timestamps: bool,
+    value_cache_entries: Option<usize>,
+    disable_wal: bool,
+    log_pid: Option<LocalPid>,
+    reverse_bytewise: bool,
+    block_cache: Option<Cache>,
+}
+
+// Installs the settings kept outside of RocksDB for a newly opened handle, or the
+// defaults when it was opened without `init_with_opts/2`. Every init calls this
+// while holding `DB_INSTANCE`, so nothing from the previous handle carries over.
+fn apply_handle_settings(parsed: Option<&ParsedDbOptions>) {
+    HANDLE_GENERATION.fetch_add(1, atomic::Ordering::SeqCst);
+    TIMESTAMPS_ENABLED.store(
+        parsed.is_some_and(|p| p.user_timestamps),
+        atomic::Ordering::SeqCst,
+    );
+    WAL_DISABLED_BY_DEFAULT.store(
+        parsed.is_some_and(|p| p.disable_wal),
+        atomic::Ordering::SeqCst,
+    );
+    // `:user_timestamps` replaces the comparator with a bytewise one.
+    KEYS_REVERSED.store(
+        parsed.is_some_and(|p| p.reverse_bytewise && !p.user_timestamps),
+        atomic::Ordering::SeqCst,
+    );
+    value_cache_reset(parsed.and_then(|p| p.value_cache_entries));
+    WAL_REPLICA_ACKS.lock().unwrap().clear();
+}
+
+// The write options every write NIF uses unless the call overrides them.
+fn default_write_options() -> WriteOptions {
+    let mut write_opts = WriteOptions::default();
+    write_opts.disable_wal(WAL_DISABLED_BY_DEFAULT.load(atomic::Ordering::SeqCst));
+    write_opts
+}
+
+/// Parses the options accepted by `init_with_opts/2` into `rocksdb::Options`.
+///
+/// Unlike `Options::default()`, `create_if_missing` defaults to `true` to match `init/1`.
+fn parse_db_options(opts_term: Term) -> NifResult<ParsedDbOptions> {
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    let mut user_timestamps = false;
+    let mut value_cache_entries = None;
+    let mut disable_wal = false;
+    let mut log_pid = None;
+    let mut reverse_bytewise = false;
+    let mut block_cache = None;
+    // Built up by the `:universal_*` options and installed once at the end.
+    let mut universal_opts: Option<UniversalCompactOptions> = None;
+
+    for (key_term, value) in option_pairs(opts_term)? {
+        let key: atom::Atom = key_term.decode()?;
+
+        if key == atoms::create_if_missing() {
+            options.create_if_missing(value.decode()?);
+        } else if key == atoms::merge_operator() {
+            let operator: atom::Atom = value.decode()?;
+            if operator == atoms::list_append() {
+                options.set_merge_operator_associative("list_append", list_append_merge);
+            } else if operator == atoms::field_map() {
+                options.set_merge_operator(
+                    "field_map",
+                    field_map_full_merge,
+                    field_map_partial_merge,
+                );
+            } else {
+                return Err(Error::Term(Box::new("Unknown merge operator")));
+            }
+        } else if key == atoms::comparator() {
+            let comparator: atom::Atom = value.decode()?;
+            if comparator == atoms::reverse_bytewise() {
+                // Same name as RocksDB's built-in, so either side can reopen the other's files.
+                options.set_comparator(
+                    "rocksdb.ReverseBytewiseComparator",
+                    Box::new(reverse_bytewise_compare),
+                );
+                reverse_bytewise = true;
+            } else {
+                return Err(Error::Term(Box::new("Unknown comparator")));
+            }
+        } else if key == atoms::block_cache() {
+            let cache_res: ResourceArc<CacheResource> = value.decode()?;
+            let mut block_opts = BlockBasedOptions::default();
+            block_opts.set_block_cache(&cache_res.cache);
+            options.set_block_based_table_factory(&block_opts);
+            block_cache = Some(cache_res.cache.clone());
+        } else if key == atoms::target_file_size_base() {
+            options.set_target_file_size_base(value.decode()?);
+        } else if key == atoms::max_bytes_for_level_base() {
+            options.set_max_bytes_for_level_base(value.decode()?);
+        } else if key == atoms::level0_file_num_compaction_trigger() {
+            options.set_level_zero_file_num_compaction_trigger(value.decode()?);
+        } else if key == atoms::compaction_style() {
+            let style: atom::Atom = value.decode()?;
+            let style = if style == atoms::level() {
+                DBCompactionStyle::Level
+            } else if style == atoms::universal() {
+                DBCompactionStyle::Universal
+            } else if style == atoms::fifo() {
+                DBCompactionStyle::Fifo
+            } else {
+                return Err(Error::Term(Box::new("Unknown compaction_style")));
+            };
+            options.set_compaction_style(style);
+        } else if key == atoms::fifo_max_table_files_size() {
+            let mut fifo_opts = FifoCompactOptions::default();
+            fifo_opts.set_max_table_files_size(value.decode()?);
+            options.set_fifo_compaction_options(&fifo_opts);
+        } else if key == atoms::universal_size_ratio() {
+            universal_opts
+                .get_or_insert_with(UniversalCompactOptions::default)
+                .set_size_ratio(value.decode()?);
+        } else if key == atoms::universal_min_merge_width() {
+            universal_opts
+                .get_or_insert_with(UniversalCompactOptions::default)
+                .set_min_merge_width(value.decode()?);
+        } else if key == atoms::universal_max_size_amplification_percent() {
+            universal_opts
+                .get_or_insert_with(UniversalCompactOptions::default)
+                .set_max_size_amplification_percent(value.decode()?);
+        } else if key == atoms::compaction_readahead_size() {
+            options.set_compaction_readahead_size(value.decode()?);
+        } else if key == atoms::bytes_per_sync() {
+            options.set_bytes_per_sync(value.decode()?);
+        } else if key == atoms::wal_bytes_per_sync() {
+            options.set_wal_bytes_per_sync(value.decode()?);
+        } else if key == atoms::recycle_log_file_num() {
+            options.set_recycle_log_file_num(value.decode()?);
+        } else if key == atoms::prefix_extractor() {
+            options.set_prefix_extractor(SliceTransform::create_fixed_prefix(value.decode()?));
+        } else if key == atoms::allow_mmap_reads() {
+            options.set_allow_mmap_reads(value.decode()?);
+        } else if key == atoms::allow_mmap_writes() {
+            options.set_allow_mmap_writes(value.decode()?);
+        } else if key == atoms::compression() {
+            options.set_compression_type(parse_compression_type(value)?);
+        } else if key == atoms::bottommost_compression() {
+            options.set_bottommost_compression_type(parse_compression_type(value)?);
+        } else if key == atoms::user_timestamps() {
+            user_timestamps = value.decode()?;
+        } else if key == atoms::value_cache_entries() {
+            value_cache_entries = Some(value.decode()?);
+        } else if key == atoms::disable_wal() {
+            disable_wal = value.decode()?;
+        } else if key == atoms::log_pid() {
+            log_pid = Some(value.decode()?);
+        } else if key == atoms::env() {
+            if let Ok(kind) = value.decode::<atom::Atom>() {
+                if kind == atoms::mem() {
+                    options.set_env(&RocksEnv::mem_env().map_err(to_nif_err)?);
+                } else if kind != atoms::default() {
+                    return Err(Error::Term(Box::new("Unknown env")));
+                }
+            } else {
+                let (kind, bytes_per_sec): (atom::Atom, i64) = value.decode()?;
+                if kind != atoms::rate_limited() {
+                    return Err(Error::Term(Box::new("Unknown env")));
+                }
+                // 100ms refill period and fairness 10 are RocksDB's defaults.
+                options.set_ratelimiter(bytes_per_sec, 100_000, 10);
+            }
+        } else if key == atoms::wal_recovery_mode() {
+            let mode: atom::Atom = value.decode()?;
+            let mode = if mode == atoms::tolerate_corrupted_tail() {
+                DBRecoveryMode::TolerateCorruptedTailRecords
+            } else if mode == atoms::absolute_consistency() {
+                DBRecoveryMode::AbsoluteConsistency
+            } else if mode == atoms::point_in_time() {
+                DBRecoveryMode::PointInTime
+            } else if mode == atoms::skip_any_corruption() {
+                DBRecoveryMode::SkipAnyCorruptedRecord
+            } else {
+                return Err(Error::Term(Box::new("Unknown wal_recovery_mode")));
+            };
+            options.set_wal_recovery_mode(mode);
+        }
+        // Add more supported DB options here...
+    }
+
+    if let Some(universal_opts) = &universal_opts {
+        options.set_universal_compaction_options(universal_opts);
+    }
+    if user_timestamps {
+        // Replaces any `:comparator` given; timestamped keys only support bytewise order.
+        options.set_comparator_with_ts(
+            "ama.BytewiseComparator.u64ts",
+            TIMESTAMP_SIZE,
+            Box::new(compare_with_timestamps),
+            Box::new(compare_timestamps),
+            Box::new(compare_without_timestamps),
+        );
+    }
+
+    Ok(ParsedDbOptions {
+        options,
+        user_timestamps,
+        value_cache_entries,
+        disable_wal,
+        log_pid,
+        reverse_bytewise,
+        block_cache,
+    })
+}
+
+/// Opens the database at `db_path` with the options in the keyword list `opts`.
+///
+/// Supported options:
+/// * `{:create_if_missing, bool}` (defaults to `true`)
+/// * `{:merge_operator, :list_append}` - values become length-prefixed item lists
+///   appended to with `merge_append/2`. The same operator must be given on every open.
+/// * `{:merge_operator, :field_map}` - values become field maps updated field by
+///   field with `merge_fields/2`.
+/// * `{:comparator, :reverse_bytewise}` - iterate keys in descending byte order.
+///   The comparator must be the same on every open: RocksDB refuses to open a
+///   database whose comparator name differs, and a comparator with the same name
+///   but a different ordering silently corrupts it.
+/// * `{:block_cache, cache}` - use a cache from `create_cache/1` instead of a private
+///   one, so every database opened with it shares the same memory budget.
+/// * `{:target_file_size_base, bytes}` - target size of SST files produced at L1.
+/// * `{:max_bytes_for_level_base, bytes}` - total size budget of L1.
+/// * `{:level0_file_num_compaction_trigger, n}` - number of L0 files that triggers
+///   an L0 -> L1 compaction.
+/// * `{:compaction_style, :level | :universal | :fifo}` - how SST files are merged.
+///   `:level` (the default) keeps read and space amplification low; `:universal`
+///   rewrites less, suiting write-heavy, time-series-like data; `:fifo` never merges
+///   and instead deletes the oldest files, for data where only the recent part matters.
+/// * `{:fifo_max_table_files_size, bytes}` - with `:fifo`, the total SST size above
+///   which the oldest files are dropped (1 GiB by default). Dropped data is gone
+///   for good, whatever its keys.
+/// * `{:universal_size_ratio, percent}` - with `:universal`, a file joins the run
+///   being merged only if the run is at most `percent`% larger than it (RocksDB's
+///   default is 1). Raising it merges more files per compaction, less often.
+/// * `{:universal_min_merge_width, n}` - with `:universal`, the fewest files a
+///   compaction merges (2 by default).
+/// * `{:universal_max_size_amplification_percent, percent}` - with `:universal`, the
+///   space overhead above the size of the data that triggers a full compaction
+///   (200 by default). Raise it to make full compactions rarer for append-mostly
+///   data, at the cost of disk space.
+///
+///   Unset `:universal_*` options keep their defaults. `cf_options/1` reports them
+///   in `"compaction_options_universal"`, e.g. `"{size_ratio=10;min_merge_width=4;...}"`.
+/// * `{:compaction_readahead_size, bytes}` - read SST inputs of compactions in
+///   chunks of `bytes` instead of block by block. A few MiB speeds up compaction on
+///   network-attached disks, where every small read pays a round trip. Read back
+///   with `compaction_readahead/0`.
+/// * `{:allow_mmap_reads, true}` - read SST files through memory maps instead of
+///   `pread`, saving a syscall per block on read-heavy nodes whose data fits in
+///   RAM. Off by default, as in RocksDB. Avoid it on network filesystems and FUSE
+///   mounts, where a failed page-in kills the VM with `SIGBUS` instead of returning
+///   an I/O error, and on 32-bit hosts with large databases (address space).
+/// * `{:allow_mmap_writes, true}` - write SST files through memory maps.
+///   Off by default. Same caution; it also cannot be combined with direct I/O.
+/// * `{:compression, type}` - how SST blocks are compressed: `:none`, `:snappy` (the
+///   default), `:zlib`, `:bz2`, `:lz4`, `:lz4hc` or `:zstd`. The open fails with
+///   `:invalid_argument` for a type this build of RocksDB was compiled without;
+///   `cf_compression/1` shows what took effect.
+/// * `{:bottommost_compression, type}` - a different type for the bottommost level,
+///   which holds most of the data and is rarely rewritten, e.g. `:zstd` there with
+///   `:lz4` above it. Same types as `:compression`.
+/// * `{:bytes_per_sync, bytes}` - have the OS write back SST files in the
+///   background every `bytes` written during flush and compaction, instead of one
+///   large write-back when the file is closed. Smooths out the I/O spikes of big
+///   compactions. Off (0) by default.
+/// * `{:wal_bytes_per_sync, bytes}` - the same for WAL files.
+/// * `{:user_timestamps, true}` - append an 8-byte user timestamp to every key,
+///   written with `put_ts/3` and read with `get_ts/2`. Replaces `:comparator`, and
+///   must be given on every open of the database. Plain `put`/`get` fail on such a
+///   database since they carry no timestamp. Needs the timestamp-aware APIs of the
+///   rocksdb crate (0.22 or newer; this crate tracks master).
+/// * `{:disable_wal, true}` - make every write through this handle skip the
+///   write-ahead log unless the call overrides it. For stores rebuilt on restart:
+///   writes not yet flushed to an SST are lost on a crash or a kill, and only a
+///   clean close (or `flush_3`) persists them.
+/// * `{:log_pid, pid}` - forward the lines RocksDB writes to its `LOG` file to
+///   `pid` as `{:rocksdb_log, level, message}`, with `level` one of `:debug`,
+///   `:info`, `:warning` or `:error` and `message` a binary. At most 100 lines a
+///   second are sent; the rest are counted and reported in a single `:warning`
+///   line, so a burst (opening logs every option) cannot flood the mailbox.
+///   Forwarding stops when the pid exits or the database is replaced by another
+///   init. Not available with `{:env, :mem}`, whose log never reaches the disk.
+/// * `{:env, :default}` - read and write the files under `db_path` (the default).
+/// * `{:env, :mem}` - keep every file in memory instead, for tests that should not
+///   touch the disk. `db_path` only names the database; its contents are freed as
+///   soon as the database is replaced by the next init, and a later open of the
+///   same path starts empty.
+/// * `{:env, {:rate_limited, bytes_per_sec}}` - use the default env with a rate
+///   limiter capping flush and compaction writes at `bytes_per_sec`, to simulate
+///   slow storage. Foreground writes (WAL, memtable) are not throttled.
+/// * `{:wal_recovery_mode, mode}` - how much WAL corruption to accept when
+///   replaying after an unclean shutdown. Defaults to RocksDB's `:point_in_time`.
+///   * `:tolerate_corrupted_tail` - ignore an incomplete record at the end of the WAL.
+///   * `:absolute_consistency` - refuse to open on any corruption, the tail included.
+///   * `:point_in_time` - stop replaying at the first corrupt record, keeping
+///     everything before it.
+///   * `:skip_any_corruption` - skip corrupt records and keep replaying; may lose
+///     writes in the middle of the log.
+/// * `{:recycle_log_file_num, n}` - keep up to `n` obsolete WAL files and overwrite
+///   them in place instead of allocating new files, avoiding the latency of file
+///   creation and growth after each WAL switch. A recycled file still holds old
+///   records past the new ones, which recovery cannot tell from a torn write in
+///   `:tolerate_corrupted_tail` or `:absolute_consistency` mode, so RocksDB ignores
+///   this option with those modes; use it with `:point_in_time` (the default) or
+///   `:skip_any_corruption`.
+/// * `{:prefix_extractor, n}` - treat the first `n` bytes of every key as its
+///   prefix, so SST bloom filters can skip files and blocks without keys of the
+///   prefix a `{:prefix, p}` iterator scans. Keys shorter than `n` bytes have no
+///   prefix. Must stay the same across opens of a database.
+/// * `{:value_cache_entries, n}` - keep the values of up to `n` recently read keys
+///   in an LRU in front of `get/1`, which then answers hits without calling into
+///   RocksDB. Every write NIF of this module evicts the keys it touches, so the
+///   cache never serves stale values; writes made by other handles to the same
+///   files (there are none while this one holds the LOCK) would not be seen.
+#[rustler::nif(name = "init_with_opts")]
+fn init_with_opts(db_path: String, opts: Term) -> NifResult<bool> {
+    let parsed = parse_db_options(opts)?;
+    let db = DB::open(&parsed.options, Path::new(&db_path)).map_err(to_nif_err)?;
+
+    let mut db_guard = DB_INSTANCE.lock().unwrap();
+    *db_guard = Some(SharedDb::with_block_cache(
+        db,
+        parsed.options.clone(),
+        parsed.block_cache.clone(),
+    ));
+    apply_handle_settings(Some(&parsed));
+    if let Some(pid) = parsed.log_pid {
+        spawn_log_forwarder(
+            Path::new(&db_path).join("LOG"),
+            pid,
+            HANDLE_GENERATION.load(atomic::Ordering::SeqCst),
+        );
+    }
+    Ok(true)
+}
+
+/// Opens an empty database kept entirely in memory, for tests: no file is created
+/// on disk, so there is no directory to clean up. Every other NIF works on it as
+/// on a database opened with `init/1`. Returns `true`.
+///
+/// Same as `init_with_opts(name, env: :mem)`: the data is freed as soon as the
+/// database is replaced by the next init, and each call starts empty.
+#[rustler::nif(name = "init_memory")]
+fn init_memory() -> NifResult<bool> {
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    options.set_env(&RocksEnv::mem_env().map_err(to_nif_err)?);
+    // Only names the database inside its own env; nothing is written there.
+    let db = DB::open(&options, Path::new("/ama-memory-db")).map_err(to_nif_err)?;
+
+    let mut db_guard = DB_INSTANCE.lock().unwrap();
+    *db_guard = Some(SharedDb::new(db, options));
+    apply_handle_settings(None);
+    Ok(true)
+}
+
+/// Opens the existing database at `db_path` with exactly the column families in
+/// `cf_names`, failing before the open if the database has a different set.
+///
+/// Returns `true`, or `{:error, {:cf_mismatch, missing, extra}}` with the sorted
+/// names expected but not on disk and on disk but not expected. `"default"` always
+/// exists and need not be listed. A path without a database is an error too, so a
+/// wrong mount point is caught instead of silently starting an empty database.
+#[rustler::nif(name = "open_expecting_cfs")]
+fn open_expecting_cfs(db_path: String, cf_names: Vec<String>) -> NifResult<bool> {
+    let path = Path::new(&db_path);
+    let options = Options::default();
+
+    let expected: BTreeSet<&str> = cf_names
+        .iter()
+        .map(String::as_str)
+        .chain(["default"])
+        .collect();
+    let on_disk = DB::list_cf(&options, path).map_err(to_nif_err)?;
+    let actual: BTreeSet<&str> = on_disk.iter().map(String::as_str).collect();
+    if expected != actual {
+        // Owned, as the error term must outlive this call.
+        let missing: Vec<String> = expected
+            .difference(&actual)
+            .map(|n| n.to_string())
+            .collect();
+        let extra: Vec<String> = actual
+            .difference(&expected)
+            .map(|n| n.to_string())
+            .collect();
+        return Err(Error::Term(Box::new((
+            atoms::cf_mismatch(),
+            missing,
+            extra,
+        ))));
+    }
+
+    let db = DB::open_cf(&options, path, &on_disk).map_err(to_nif_err)?;
+    let mut db_guard = DB_INSTANCE.lock().unwrap();
+    *db_guard = Some(SharedDb::new(db, options));
+    apply_handle_settings(None);
+    Ok(true)
+}
+
+/// Opens the database at `db_path` with every column family already on disk plus
+/// those in `cf_names`, which are created if missing, so `get_4/3`, `put_5/4` and
+/// the other `_cf` NIFs can reach all of them. `init/1` opens only the default CF,
+/// leaving any other CF of an existing database unreachable.
+///
+/// A path without a database yet is created. Returns `true`.
+#[rustler::nif(name = "init_cf")]
+fn init_cf(db_path: String, cf_names: Vec<String>) -> NifResult<bool> {
+    let path = Path::new(&db_path);
+    let mut options = Options::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+
+    // Listing fails when there is no database yet, which then starts with just "default".
+    let on_disk = DB::list_cf(&options, path).unwrap_or_else(|_| vec!["default".to_string()]);
+    let all: BTreeSet<String> = on_disk.into_iter().chain(cf_names).collect();
+
+    let db = DB::open_cf(&options, path, &all).map_err(to_nif_err)?;
+    let mut db_guard = DB_INSTANCE.lock().unwrap();
+    *db_guard = Some(SharedDb::new(db, options));
+    apply_handle_settings(None);
+    Ok(true)
+}
+
+// ------------------------ Info log forwarding ------------------------
+
+const LOG_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const LOG_LINES_PER_SECOND: u32 = 100;
+
+// RocksDB tags lines logged above INFO level, e.g.
+// `2024/05/01-10:00:00.000000 7f3a [WARN] [db/db_impl.cc:42] message`.
+fn log_line_level(line: &str) -> atom::Atom {
+    if line.contains("[ERROR]") || line.contains("[FATAL]") {
+        atoms::error()
+    } else if line.contains("[WARN]") {
+        atoms::warning()
+    } else if line.contains("[DEBUG]") {
+        atoms::debug()
+    } else {
+        atoms::info()
+    }
+}
+
+// The rocksdb crate cannot install a custom `Logger`, so a thread tails the `LOG`
+// file instead. It runs until the handle it was started for is replaced (see
+// `HANDLE_GENERATION`) or `pid` is gone.
+fn spawn_log_forwarder(log_path: PathBuf, pid: LocalPid, generation: u64) {
+    use std::io::{BufRead, BufReader};
+
+    std::thread::spawn(move || {
+        let mut owned_env = OwnedEnv::new();
+        let mut send = |level: atom::Atom, message: &str| {
+            owned_env
+                .send_and_clear(&pid, |env| {
+                    (atoms::rocksdb_log(), level, message).encode(env)
+                })
+                .is_ok()
+        };
+
+        let mut reader: Option<BufReader<std::fs::File>> = None;
+        let mut read_bytes: u64 = 0;
+        let mut line = String::new();
+        let mut window_start = Instant::now();
+        let mut sent_in_window: u32 = 0;
+        let mut dropped: u64 = 0;
+
+        while HANDLE_GENERATION.load(atomic::Ordering::SeqCst) == generation {
+            if window_start.elapsed() >= Duration::from_secs(1) {
+                if dropped > 0
+                    && !send(
+                        atoms::warning(),
+                        &format!("{} RocksDB log lines dropped", dropped),
+                    )
+                {
+                    return;
+                }
+                window_start = Instant::now();
+                sent_in_window = 0;
+                dropped = 0;
+            }
+
+            // The file only appears once RocksDB logs, and is replaced if it shrinks.
+            let shrunk = std::fs::metadata(&log_path).map_or(true, |meta| meta.len() < read_bytes);
+            if reader.is_none() || shrunk {
+                reader = std::fs::File::open(&log_path).ok().map(BufReader::new);
+                read_bytes = 0;
+                line.clear();
+            }
+            let Some(log) = reader.as_mut() else {
+                std::thread::sleep(LOG_POLL_INTERVAL);
+                continue;
+            };
+
+            // `read_line` appends, so a line RocksDB is still writing is completed
+            // by the next read rather than sent in two halves.
+            match log.read_line(&mut line) {
+                Ok(0) | Err(_) => std::thread::sleep(LOG_POLL_INTERVAL),
+                Ok(n) => {
+                    read_bytes += n as u64;
+                    if !line.ends_with('\n') {
+                        continue;
+                    }
+                    if sent_in_window < LOG_LINES_PER_SECOND {
+                        let message = line.trim_end();
+                        if !send(log_line_level(message), message) {
+                            return;
+                        }
+                        sent_in_window += 1;
+                    } else {
+                        dropped += 1;
+                    }
+                    line.clear();
+                }
+            }
+        }
+    });
+}
+
+// Opens `path` as a TTL database with every column family already on disk.
+// A path without a database yet is created with just the default CF.
+fn open_with_ttl_all_cfs(path: &Path, ttl: Duration) -> Result<Arc<SharedDb>, rocksdb::Error> {
+    let mut options = Options::default();
+    options.create_if_missing(true);
+
+    let cf_names = DB::list_cf(&options, path).unwrap_or_else(|_| vec!["default".to_string()]);
+    let db = DB::open_cf_with_ttl(&options, path, cf_names, ttl)?;
+    Ok(SharedDb::new(db, options))
+}
+
+/// Opens the database at `db_path` in TTL mode: entries older than `ttl_secs` are
+/// dropped by compaction (reads may still return them until then).
+#[rustler::nif(name = "init_with_ttl")]
+fn init_with_ttl(db_path: String, ttl_secs: u64) -> NifResult<bool> {
+    let db = open_with_ttl_all_cfs(Path::new(&db_path), Duration::from_secs(ttl_secs))
+        .map_err(to_nif_err)?;
+
+    let mut db_guard = DB_INSTANCE.lock().unwrap();
+    *db_guard = Some(db);
+    apply_handle_settings(None);
+    Ok(true)
+}
+
+/// Changes the TTL of the open database by closing and reopening it with `ttl_secs`.
+///
+/// RocksDB's `DBWithTTL::SetTtl` is not exposed by the rocksdb crate, so the TTL
+/// the compaction filter uses cannot be changed in place. Reopening is cheap
+/// (the WAL is replayed, nothing is rewritten) but:
+/// * it fails while an iterator or snapshot of the handle is open or a stream or
+///   compaction NIF is running, as those keep the old handle open;
+/// * the new TTL applies to every column family;
+/// * if the reopen fails the database is left closed and `init_with_ttl/2` must be called.
+#[rustler::nif(name = "reopen_with_ttl", schedule = "DirtyIo")]
+fn reopen_with_ttl(ttl_secs: u64) -> NifResult<bool> {
+    let mut db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    // The old handle must release the LOCK file before the path can be reopened,
+    // which it only does once nothing else holds it.
+    if Arc::strong_count(shared) > 1 {
+        return Err(Error::Term(Box::new("Database handle in use")));
+    }
+    let path = shared.read().path().to_path_buf();
+    *db_guard = None;
+    let db = open_with_ttl_all_cfs(&path, Duration::from_secs(ttl_secs)).map_err(to_nif_err)?;
+    *db_guard = Some(db);
+    apply_handle_settings(None);
+    Ok(true)
+}
+
 #[rustler::nif]
 fn get(key: String) -> NifResult<Option<Vec<u8>>> {
     let db_guard = DB_INSTANCE.lock().unwrap();
 
-    if let Some(db) = db_guard.as_ref() {
+    if let Some(shared) = db_guard.as_ref() {
+        let db = shared.read();
+        if let Some(value) = value_cache_get(key.as_bytes()) {
+            return Ok(Some(value));
+        }
+
         match db.get(key.as_bytes()) {
-            Ok(Some(value)) => Ok(Some(value)),
+            Ok(Some(value)) => {
+                value_cache_insert(key.as_bytes(), &value);
+                Ok(Some(value))
+            }
 
             Ok(None) => Ok(None),
 
-            Err(e) => {
-                eprintln!("Error getting value: {:?}", e);
-
-                Ok(None)
-            }
+            Err(e) => Err(to_nif_err(e)),
         }
     } else {
         eprintln!("Database not initialized");
@@ -123,15 +1061,13 @@ fn get(key: String) -> NifResult<Option<Vec<u8>>> {
 fn put(key: String, value: Vec<u8>) -> NifResult<bool> {
     let db_guard = DB_INSTANCE.lock().unwrap();
 
-    if let Some(db) = db_guard.as_ref() {
-        match db.put(key.as_bytes(), value) {
+    if let Some(shared) = db_guard.as_ref() {
+        let db = shared.read();
+        value_cache_invalidate(key.as_bytes());
+        match db.put_opt(key.as_bytes(), value, &default_write_options()) {
             Ok(_) => Ok(true),
 
-            Err(e) => {
-                eprintln!("Error putting value: {:?}", e);
-
-                Ok(false)
-            }
+            Err(e) => Err(to_nif_err(e)),
         }
     } else {
         eprintln!("Database not initialized");
@@ -140,250 +1076,4262 @@ fn put(key: String, value: Vec<u8>) -> NifResult<bool> {
     }
 }
 
-// ------------------------ NIF skeletons ------------------------
+// ------------------------ Value cache ------------------------
 
-#[rustler::nif(name = "transaction_get_3")]
-fn transaction_get_3(_txn_id: String, _key: String, _opts: Term) -> NifResult<Option<Vec<u8>>> {
-    Err(Error::Atom("not_implemented"))
+// An LRU of recently read values, in front of `get/1`.
+//
+// Correctness relies on every write NIF evicting the keys it writes, before the
+// write and while holding the `DB_INSTANCE` guard, so no `get` can repopulate a key
+// between the eviction and the write landing. Writes to any column family evict
+// the key: a few needless evictions are cheaper than tracking which CF a cached
+// key belongs to.
+struct ValueCache {
+    capacity: usize,
+    // Key -> (value, tick of its last use).
+    entries: HashMap<Vec<u8>, (Vec<u8>, u64)>,
+    // Tick -> key, so the least recently used key is the first entry.
+    recency: BTreeMap<u64, Vec<u8>>,
+    tick: u64,
 }
 
-#[rustler::nif(name = "transaction_get_4")]
-fn transaction_get_4(
-    _txn_id: String,
-    _key: String,
-    _opts: Term,
-    _cf: String,
-) -> NifResult<Option<Vec<u8>>> {
-    Err(Error::Atom("not_implemented"))
-}
+impl ValueCache {
+    fn new(capacity: usize) -> Self {
+        ValueCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
 
-#[rustler::nif(name = "get_3")]
-fn get_3(_key: String, _opts: Term) -> NifResult<Option<Vec<u8>>> {
-    Err(Error::Atom("not_implemented"))
-}
+    fn get(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        self.tick += 1;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.recency.remove(last_used);
+        *last_used = self.tick;
+        self.recency.insert(self.tick, key.to_vec());
+        Some(value.clone())
+    }
 
-#[rustler::nif(name = "get_4")]
-fn get_4(_key: String, _opts: Term, _cf: String) -> NifResult<Option<Vec<u8>>> {
-    Err(Error::Atom("not_implemented"))
-}
+    fn insert(&mut self, key: &[u8], value: &[u8]) {
+        self.remove(key);
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        self.entries
+            .insert(key.to_vec(), (value.to_vec(), self.tick));
+        self.recency.insert(self.tick, key.to_vec());
+    }
 
-#[rustler::nif(name = "transaction_put_3")]
-fn transaction_put_3(_txn_id: String, _key: String, _value: Vec<u8>) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
-}
+    fn remove(&mut self, key: &[u8]) {
+        if let Some((_, last_used)) = self.entries.remove(key) {
+            self.recency.remove(&last_used);
+        }
+    }
 
-#[rustler::nif(name = "transaction_put_4")]
-fn transaction_put_4(
-    _txn_id: String,
-    _key: String,
-    _value: Vec<u8>,
-    _opts: Term,
-) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
 }
 
-#[rustler::nif(name = "put_4")]
-fn put_4(_key: String, _value: Vec<u8>, _opts: Term) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+// Installs a fresh cache of `capacity` entries, or none. Called by every init so a
+// reopened database never sees values cached from the previous handle.
+fn value_cache_reset(capacity: Option<usize>) {
+    *VALUE_CACHE.lock().unwrap() = capacity.map(ValueCache::new);
 }
 
-#[rustler::nif(name = "put_5")]
-fn put_5(_key: String, _value: Vec<u8>, _opts: Term, _cf: String) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+fn value_cache_get(key: &[u8]) -> Option<Vec<u8>> {
+    VALUE_CACHE.lock().unwrap().as_mut()?.get(key)
 }
 
-#[rustler::nif(name = "transaction_delete_2")]
-fn transaction_delete_2(_txn_id: String, _key: String) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+fn value_cache_insert(key: &[u8], value: &[u8]) {
+    if let Some(cache) = VALUE_CACHE.lock().unwrap().as_mut() {
+        cache.insert(key, value);
+    }
 }
 
-#[rustler::nif(name = "transaction_delete_3")]
-fn transaction_delete_3(_txn_id: String, _key: String, _opts: Term) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+fn value_cache_invalidate(key: &[u8]) {
+    if let Some(cache) = VALUE_CACHE.lock().unwrap().as_mut() {
+        cache.remove(key);
+    }
 }
 
-#[rustler::nif(name = "delete_3")]
-fn delete_3(_key: String, _opts: Term) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+// For writes whose keys are not known one by one, such as range deletes.
+fn value_cache_clear() {
+    if let Some(cache) = VALUE_CACHE.lock().unwrap().as_mut() {
+        cache.clear();
+    }
 }
 
-#[rustler::nif(name = "delete_4")]
-fn delete_4(_key: String, _opts: Term, _cf: String) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
-}
+// ------------------------ Bulk reads ------------------------
 
-#[rustler::nif(name = "transaction_iterator_2")]
-fn transaction_iterator_2(env: Env, _txn_id: String) -> NifResult<Term> {
-    Err(Error::Atom("not_implemented"))
+// What `multi_get_cf_routed/1` returns for a value that was found.
+#[derive(Clone, Copy)]
+enum ValueDecode {
+    // The value as a binary.
+    Raw,
+    // An 8-byte big-endian value (the `put_u64/2` key encoding) as an integer.
+    U64,
+    // Only the value's length; the value itself is never copied.
+    Len,
 }
 
-#[rustler::nif(name = "transaction_iterator_3")]
-fn transaction_iterator_3<'a>(
-    env: Env<'a>,
-    _txn_id: String,
-    _opts: Term<'a>,
-) -> NifResult<Term<'a>> {
-    Err(Error::Atom("not_implemented"))
+// Parses the options of one request. An unknown `:decode` is reported for that
+// entry alone, so it is the `Err` of the inner result.
+fn parse_routed_get_opts(opts: Term) -> NifResult<Result<ValueDecode, atom::Atom>> {
+    let mut decode = Ok(ValueDecode::Raw);
+    for (key_term, value) in option_pairs(opts)? {
+        let key: atom::Atom = key_term.decode()?;
+        if key == atoms::decode() {
+            let mode: atom::Atom = value.decode()?;
+            decode = if mode == atoms::raw() {
+                Ok(ValueDecode::Raw)
+            } else if mode == atoms::u64() {
+                Ok(ValueDecode::U64)
+            } else if mode == atoms::len() {
+                Ok(ValueDecode::Len)
+            } else {
+                Err(atoms::unknown_decode())
+            };
+        }
+    }
+    Ok(decode)
 }
 
-pub struct IteratorResource {
-    // The iterator has a lifetime dependency on the DB instance.
-    // Since the DB is in a lazy_static, it will live for the lifetime of the
-    // program. We can use `unsafe` to extend the iterator's lifetime to `'static`.
-    // This is safe as long as the DB is not closed while iterators exist.
-    iter: Mutex<DBIterator<'static>>,
+fn encode_routed_value<'a>(env: Env<'a>, value: &[u8], decode: ValueDecode) -> NifResult<Term<'a>> {
+    Ok(match decode {
+        ValueDecode::Raw => {
+            let mut binary = OwnedBinary::new(value.len())
+                .ok_or_else(|| Error::Term(Box::new("Failed to allocate binary")))?;
+            binary.as_mut_slice().copy_from_slice(value);
+            (atoms::ok(), binary.release(env)).encode(env)
+        }
+        ValueDecode::U64 => match <[u8; 8]>::try_from(value) {
+            Ok(bytes) => (atoms::ok(), u64::from_be_bytes(bytes)).encode(env),
+            Err(_) => (atoms::error(), atoms::not_u64()).encode(env),
+        },
+        ValueDecode::Len => (atoms::ok(), value.len()).encode(env),
+    })
 }
 
-enum ParsedIteratorMode {
-    Start,
-    End,
-    From { key: Vec<u8>, dir: Direction },
-}
+/// Looks up keys across column families in one call. `requests` is a list of
+/// `{cf_name, key}` or `{cf_name, key, opts}` tuples, answered in order with one of:
+/// * `{:ok, value}` - shaped by the request's `{:decode, mode}` option:
+///   * `:raw` (the default) - the value as a binary.
+///   * `:u64` - an 8-byte big-endian value as an integer, or `{:error, :not_u64}`
+///     for a value of another size.
+///   * `:len` - the length of the value; the value is read but never copied, which
+///     keeps size surveys over large values cheap.
+/// * `nil` - the key does not exist.
+/// * `{:error, reason}` - that entry failed: `:cf_not_found`, `:unknown_decode` for
+///   an unknown `:decode` mode, or `{kind, message}` from RocksDB.
+///
+/// Keys are grouped by column family and each group read with one batched
+/// `MultiGet` of pinned values.
+#[rustler::nif(name = "multi_get_cf_routed")]
+fn multi_get_cf_routed<'a>(env: Env<'a>, requests: ListIterator<'a>) -> NifResult<Vec<Term<'a>>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
 
-fn parse_iterator_opts(env: Env, opts: Term) -> NifResult<ParsedIteratorMode> {
-    let list: ListIterator = opts.decode()?;
+    let mut parsed = Vec::new();
+    for request in requests {
+        let (cf_name, key, decode) = match request.decode::<(String, Binary, Term)>() {
+            Ok((cf_name, key, opts)) => (cf_name, key, parse_routed_get_opts(opts)?),
+            Err(_) => {
+                let (cf_name, key): (String, Binary) = request.decode()?;
+                (cf_name, key, Ok(ValueDecode::Raw))
+            }
+        };
+        parsed.push((cf_name, key.as_slice().to_vec(), decode));
+    }
 
-    for item in list {
-        let (key_term, value_term) = item.decode::<(Term, Term)>()?;
+    let mut by_cf: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, (cf_name, _, _)) in parsed.iter().enumerate() {
+        by_cf.entry(cf_name.as_str()).or_default().push(i);
+    }
 
-        if key_term.decode::<atom::Atom>()? == atoms::iterator_mode() {
-            // Try to decode the value as a simple atom first (e.g., :start, :end)
-            if let Ok(mode_atom) = value_term.decode::<atom::Atom>() {
-                if mode_atom == atoms::start() {
-                    return Ok(ParsedIteratorMode::Start);
-                } else if mode_atom == atoms::end() {
-                    return Ok(ParsedIteratorMode::End);
-                }
-            // Otherwise, try to decode it as a tuple (e.g., {:from, key, :forward})
-            } else if let Ok((from_atom, key_term, dir_atom)) =
-                value_term.decode::<(atom::Atom, Term, atom::Atom)>()
-            {
-                // Ensure the tuple starts with the :from atom
-                if from_atom == atoms::from() {
-                    let key = binary_to_vec(key_term)?;
-                    let dir = if dir_atom == atoms::reverse() {
-                        Direction::Reverse
-                    } else {
-                        // Default to forward if not reverse
-                        Direction::Forward
-                    };
-                    return Ok(ParsedIteratorMode::From { key, dir });
-                }
+    let mut results = vec![atom::nil().encode(env); parsed.len()];
+    for (cf_name, indices) in by_cf {
+        let Some(cf) = db.cf_handle(cf_name) else {
+            for &i in &indices {
+                results[i] = (atoms::error(), atoms::cf_not_found()).encode(env);
             }
+            continue;
+        };
+        let values = db.batched_multi_get_cf(cf, indices.iter().map(|&i| &parsed[i].1), false);
+        for (&i, value) in indices.iter().zip(values) {
+            results[i] = match (&parsed[i].2, value) {
+                (Err(reason), _) => (atoms::error(), *reason).encode(env),
+                (Ok(decode), Ok(Some(value))) => encode_routed_value(env, &value, *decode)?,
+                (Ok(_), Ok(None)) => atom::nil().encode(env),
+                (Ok(_), Err(e)) => (atoms::error(), (error_kind(&e), e.to_string())).encode(env),
+            };
         }
     }
-    // Default mode if not specified
-    Ok(ParsedIteratorMode::Start)
+    Ok(results)
 }
 
-#[rustler::nif(name = "iterator")]
-fn iterator(env: Env, opts: Term) -> NifResult<ResourceArc<IteratorResource>> {
-    let db_guard = DB_INSTANCE.lock().unwrap();
-    let db = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
-
-    let parsed_mode = parse_iterator_opts(env, opts)?;
+// ------------------------ Bulk writes ------------------------
 
-    let db_iter = match parsed_mode {
-        ParsedIteratorMode::Start => db.iterator(IteratorMode::Start),
-        ParsedIteratorMode::End => db.iterator(IteratorMode::End),
-        ParsedIteratorMode::From { ref key, dir } => {
-            db.iterator(IteratorMode::From(key.as_slice(), dir))
+// Decodes every `{key, value}` tuple into a `WriteBatch` before anything is written,
+// so a malformed entry anywhere in the list leaves the database untouched.
+fn decode_put_batch(entries: ListIterator, cf: Option<&ColumnFamily>) -> NifResult<WriteBatch> {
+    let mut batch = WriteBatch::default();
+    for entry in entries {
+        let (key, value): (Binary, Binary) = entry.decode()?;
+        value_cache_invalidate(key.as_slice());
+        match cf {
+            Some(cf) => batch.put_cf(cf, key.as_slice(), value.as_slice()),
+            None => batch.put(key.as_slice(), value.as_slice()),
         }
-    };
+    }
+    Ok(batch)
+}
 
-    let static_iter: DBIterator<'static> = unsafe { std::mem::transmute(db_iter) };
-    let resource = ResourceArc::new(IteratorResource {
-        iter: Mutex::new(static_iter),
-    });
-    Ok(resource)
+/// Atomically puts a list of `{key, value}` tuples into the default column family.
+///
+/// Either every entry is written or, if any entry is malformed, none are.
+#[rustler::nif(name = "multi_put")]
+fn multi_put(entries: ListIterator) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let batch = decode_put_batch(entries, None)?;
+    db.write_opt(batch, &default_write_options())
+        .map(|_| true)
+        .map_err(to_nif_err)
 }
 
-#[rustler::nif(name = "iterator_2")]
-fn iterator_2(env: Env, opts: Term, cf_name: String) -> NifResult<ResourceArc<IteratorResource>> {
+/// Same as `multi_put/1`, writing into a named column family.
+#[rustler::nif(name = "multi_put_cf")]
+fn multi_put_cf(cf_name: String, entries: ListIterator) -> NifResult<bool> {
     let db_guard = DB_INSTANCE.lock().unwrap();
-    let db = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
-    let cf = db
-        .cf_handle(&cf_name)
-        .ok_or_else(|| Error::Term(Box::new("Column family not found")))?;
-    let parsed_mode = parse_iterator_opts(env, opts)?;
-    let read_opts = ReadOptions::default();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
 
-    let db_iter = match parsed_mode {
-        ParsedIteratorMode::Start => db.iterator_cf_opt(cf, read_opts, IteratorMode::Start),
-        ParsedIteratorMode::End => db.iterator_cf_opt(cf, read_opts, IteratorMode::End),
-        ParsedIteratorMode::From { ref key, dir } => {
-            db.iterator_cf_opt(cf, read_opts, IteratorMode::From(key.as_slice(), dir))
-        }
-    };
+    let batch = decode_put_batch(entries, Some(cf))?;
+    db.write_opt(batch, &default_write_options())
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
 
-    let static_iter: DBIterator<'static> = unsafe { std::mem::transmute(db_iter) };
+// Same as `decode_put_batch`, for a list of keys to delete.
+fn decode_delete_batch(keys: ListIterator, cf: Option<&ColumnFamily>) -> NifResult<WriteBatch> {
+    let mut batch = WriteBatch::default();
+    for key in keys {
+        let key: Binary = key.decode()?;
+        value_cache_invalidate(key.as_slice());
+        match cf {
+            Some(cf) => batch.delete_cf(cf, key.as_slice()),
+            None => batch.delete(key.as_slice()),
+        }
+    }
+    Ok(batch)
+}
+
+/// Atomically deletes a list of keys from the default column family.
+///
+/// Either every delete applies or, if any key is malformed, none do.
+/// Absent keys are deleted silently, as with single deletes.
+#[rustler::nif(name = "multi_delete")]
+fn multi_delete(keys: ListIterator) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let batch = decode_delete_batch(keys, None)?;
+    db.write_opt(batch, &default_write_options())
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+/// Same as `multi_delete/1`, deleting from a named column family.
+#[rustler::nif(name = "multi_delete_cf")]
+fn multi_delete_cf(cf_name: String, keys: ListIterator) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    let batch = decode_delete_batch(keys, Some(cf))?;
+    db.write_opt(batch, &default_write_options())
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+// Decodes `{:put, cf, key, value}` and `{:delete, cf, key}` operations into one
+// `WriteBatch`, resolving every column family before anything is written.
+fn decode_mixed_batch(db: &DB, ops: ListIterator) -> NifResult<WriteBatch> {
+    let cf_for = |cf_name: String| cf_or_err(db, &cf_name);
+
+    let mut batch = WriteBatch::default();
+    for op in ops {
+        if let Ok((tag, cf_name, key, value)) = op.decode::<(atom::Atom, String, Binary, Binary)>()
+        {
+            if tag != atoms::put() {
+                return Err(Error::BadArg);
+            }
+            value_cache_invalidate(key.as_slice());
+            batch.put_cf(cf_for(cf_name)?, key.as_slice(), value.as_slice());
+        } else {
+            let (tag, cf_name, key): (atom::Atom, String, Binary) = op.decode()?;
+            if tag != atoms::delete() {
+                return Err(Error::BadArg);
+            }
+            value_cache_invalidate(key.as_slice());
+            batch.delete_cf(cf_for(cf_name)?, key.as_slice());
+        }
+    }
+    Ok(batch)
+}
+
+/// Atomically applies a list of `{:put, cf, key, value}` and `{:delete, cf, key}`
+/// operations, which may span several column families (use `"default"` for the
+/// default one).
+///
+/// The operations go through a single `WriteBatch`: they become visible together
+/// or not at all, but no keys are locked, so there is no conflict detection against
+/// concurrent writers. Use a transaction when a read must be validated.
+///
+/// Returns `:ok`, or `{:error, reason}` with nothing written if an operation is
+/// malformed or names an unknown column family.
+#[rustler::nif(name = "atomic_write")]
+fn atomic_write(ops: ListIterator) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let batch = decode_mixed_batch(db, ops)?;
+    db.write_opt(batch, &default_write_options())
+        .map(|_| atoms::ok())
+        .map_err(to_nif_err)
+}
+
+// A write batch built up over several calls, for writes that are not all known
+// at once. `batch_write/2` takes the batch, so it can only be written once.
+pub struct BatchResource {
+    batch: Mutex<Option<PendingBatch>>,
+}
+
+struct PendingBatch {
+    batch: WriteBatch,
+    // Every key written or deleted, to drop from the value cache on commit.
+    keys: Vec<Vec<u8>>,
+    // The database the first `_cf` operation resolved its column family against.
+    // A batch only records column family ids, which mean other column families in
+    // another database. Weak, so a pending batch does not keep a database open.
+    db: Option<Weak<SharedDb>>,
+}
+
+impl PendingBatch {
+    // Ties the batch to `shared`, or fails if a `_cf` operation tied it to
+    // another database.
+    fn bind_db(&mut self, shared: &Arc<SharedDb>) -> NifResult<()> {
+        match &self.db {
+            Some(db) if !Weak::ptr_eq(db, &Arc::downgrade(shared)) => {
+                Err(Error::Term(Box::new(atoms::batch_db_mismatch())))
+            }
+            Some(_) => Ok(()),
+            None => {
+                self.db = Some(Arc::downgrade(shared));
+                Ok(())
+            }
+        }
+    }
+}
+
+impl BatchResource {
+    fn with_pending<T>(&self, f: impl FnOnce(&mut PendingBatch) -> T) -> NifResult<T> {
+        let mut pending = self.batch.lock().unwrap();
+        let pending = pending
+            .as_mut()
+            .ok_or_else(|| Error::Term(Box::new("Batch already written")))?;
+        Ok(f(pending))
+    }
+}
+
+/// Creates an empty write batch. Puts and deletes added with `batch_put/3`,
+/// `batch_delete/2` and their `_cf` variants are only applied, all together or
+/// not at all, by `batch_write/2`.
+#[rustler::nif(name = "batch_new")]
+fn batch_new() -> ResourceArc<BatchResource> {
+    ResourceArc::new(BatchResource {
+        batch: Mutex::new(Some(PendingBatch {
+            batch: WriteBatch::default(),
+            keys: Vec::new(),
+            db: None,
+        })),
+    })
+}
+
+/// Adds a put into the default column family to `batch`. Returns `:ok`.
+#[rustler::nif(name = "batch_put")]
+fn batch_put(
+    batch: ResourceArc<BatchResource>,
+    key: Binary,
+    value: Binary,
+) -> NifResult<atom::Atom> {
+    batch.with_pending(|pending| {
+        pending.batch.put(key.as_slice(), value.as_slice());
+        pending.keys.push(key.to_vec());
+        atoms::ok()
+    })
+}
+
+/// Adds a put into `cf_name` to `batch`. The column family is resolved now, so
+/// the batch must be written to the database open at this point: once another
+/// init has replaced it, this and `batch_write/2` return
+/// `{:error, :batch_db_mismatch}`.
+#[rustler::nif(name = "batch_put_cf")]
+fn batch_put_cf(
+    batch: ResourceArc<BatchResource>,
+    cf_name: String,
+    key: Binary,
+    value: Binary,
+) -> NifResult<atom::Atom> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    batch.with_pending(|pending| {
+        pending.bind_db(&shared)?;
+        pending.batch.put_cf(cf, key.as_slice(), value.as_slice());
+        pending.keys.push(key.to_vec());
+        Ok(atoms::ok())
+    })?
+}
+
+/// Adds a delete from the default column family to `batch`. Returns `:ok`.
+#[rustler::nif(name = "batch_delete")]
+fn batch_delete(batch: ResourceArc<BatchResource>, key: Binary) -> NifResult<atom::Atom> {
+    batch.with_pending(|pending| {
+        pending.batch.delete(key.as_slice());
+        pending.keys.push(key.to_vec());
+        atoms::ok()
+    })
+}
+
+/// Adds a delete from `cf_name` to `batch`, resolving the column family as
+/// `batch_put_cf/4` does.
+#[rustler::nif(name = "batch_delete_cf")]
+fn batch_delete_cf(
+    batch: ResourceArc<BatchResource>,
+    cf_name: String,
+    key: Binary,
+) -> NifResult<atom::Atom> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    batch.with_pending(|pending| {
+        pending.bind_db(&shared)?;
+        pending.batch.delete_cf(cf, key.as_slice());
+        pending.keys.push(key.to_vec());
+        Ok(atoms::ok())
+    })?
+}
+
+/// Atomically applies every operation in `batch`, with the write options of
+/// `put_4/3` in `opts`. Returns `:ok`, or `{:error, :batch_db_mismatch}` with
+/// nothing written if a `_cf` operation was added on a database that another init
+/// has replaced since. The batch cannot be used afterwards, even if the write
+/// fails.
+#[rustler::nif(name = "batch_write")]
+fn batch_write(batch: ResourceArc<BatchResource>, opts: Term) -> NifResult<atom::Atom> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let write_opts = parse_write_options(opts)?;
+    let mut pending = batch
+        .batch
+        .lock()
+        .unwrap()
+        .take()
+        .ok_or_else(|| Error::Term(Box::new("Batch already written")))?;
+    pending.bind_db(&shared)?;
+
+    for key in &pending.keys {
+        value_cache_invalidate(key);
+    }
+    db.write_opt(pending.batch, &write_opts)
+        .map(|_| atoms::ok())
+        .map_err(to_nif_err)
+}
+
+/// Deletes every key of the default column family in `[start_key, end_key)`
+/// with a single range tombstone, instead of one delete per key.
+///
+/// Snapshots and iterators taken before the call still see the keys, as with
+/// point deletes, but the span is not dropped key by key: the tombstone stays,
+/// and is checked by every read over the span, until a compaction with no older
+/// snapshot left removes it together with the data it covers. Disk space is only
+/// reclaimed then. Returns `:ok`.
+#[rustler::nif(name = "delete_range")]
+fn delete_range(start_key: Binary, end_key: Binary) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let mut batch = WriteBatch::default();
+    batch.delete_range(start_key.as_slice(), end_key.as_slice());
+    value_cache_clear();
+    db.write_opt(batch, &default_write_options())
+        .map(|_| atoms::ok())
+        .map_err(to_nif_err)
+}
+
+/// Same as `delete_range/2` for column family `cf_name`.
+#[rustler::nif(name = "delete_range_cf")]
+fn delete_range_cf(cf_name: String, start_key: Binary, end_key: Binary) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    value_cache_clear();
+    db.delete_range_cf_opt(
+        cf,
+        start_key.as_slice(),
+        end_key.as_slice(),
+        &default_write_options(),
+    )
+    .map(|_| atoms::ok())
+    .map_err(to_nif_err)
+}
+
+// Moves up to `max_keys` keys starting with `prefix` from `src` to `dst` in one
+// `WriteBatch`. Each key's put and delete land together, so a crash at any point
+// leaves every key in exactly one of the two CFs. Returns the number moved and
+// whether keys with the prefix are left behind.
+fn move_prefix_batch(
+    db: &DB,
+    src: &ColumnFamily,
+    dst: &ColumnFamily,
+    prefix: &[u8],
+    max_keys: Option<u64>,
+) -> NifResult<(u64, bool)> {
+    let mut batch = WriteBatch::default();
+    let mut moved: u64 = 0;
+    let mut more = false;
+
+    let mut iter = db.raw_iterator_cf(src);
+    iter.seek(prefix);
+    while let Some((key, value)) = iter.item() {
+        if !key.starts_with(prefix) {
+            break;
+        }
+        if max_keys == Some(moved) {
+            more = true;
+            break;
+        }
+        value_cache_invalidate(key);
+        batch.put_cf(dst, key, value);
+        batch.delete_cf(src, key);
+        moved += 1;
+        iter.next();
+    }
+    iter.status().map_err(to_nif_err)?;
+    drop(iter);
+
+    db.write_opt(batch, &default_write_options())
+        .map_err(to_nif_err)?;
+    Ok((moved, more))
+}
+
+fn move_range_cfs<'d>(
+    db: &'d DB,
+    src_cf: &str,
+    dst_cf: &str,
+) -> NifResult<(&'d ColumnFamily, &'d ColumnFamily)> {
+    if src_cf == dst_cf {
+        return Err(Error::Term(Box::new(
+            "Source and destination column families are the same",
+        )));
+    }
+    let src = cf_or_err(db, src_cf)?;
+    let dst = cf_or_err(db, dst_cf)?;
+    Ok((src, dst))
+}
+
+/// Atomically moves every key starting with `prefix` from column family `src_cf`
+/// to `dst_cf`, overwriting keys already present in `dst_cf`.
+///
+/// All keys and values go through a single `WriteBatch`, held in memory until it
+/// is written, and land in one memtable; RocksDB caps a batch at 4 GiB. For large
+/// ranges use `move_range_4/4`, whose smaller batches keep every key in exactly one
+/// of the CFs across a crash but do not move the whole range at once.
+///
+/// Returns `{:ok, moved}`.
+#[rustler::nif(name = "move_range", schedule = "DirtyIo")]
+fn move_range<'a>(
+    env: Env<'a>,
+    src_cf: String,
+    dst_cf: String,
+    prefix: Binary,
+) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let (src, dst) = move_range_cfs(db, &src_cf, &dst_cf)?;
+
+    let (moved, _) = move_prefix_batch(db, src, dst, prefix.as_slice(), None)?;
+    Ok((atoms::ok(), moved).encode(env))
+}
+
+/// Same as `move_range/3`, moving at most `max_keys` keys in one atomic batch.
+///
+/// Moved keys are gone from `src_cf`, so calling it again with the same arguments
+/// resumes where the previous call stopped; no cursor needs to be kept.
+///
+/// Returns `{:ok, moved, :more}` while keys with the prefix remain in `src_cf`,
+/// then `{:ok, moved, :done}`.
+#[rustler::nif(name = "move_range_4", schedule = "DirtyIo")]
+fn move_range_4<'a>(
+    env: Env<'a>,
+    src_cf: String,
+    dst_cf: String,
+    prefix: Binary,
+    max_keys: u64,
+) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let (src, dst) = move_range_cfs(db, &src_cf, &dst_cf)?;
+
+    let (moved, more) = move_prefix_batch(db, src, dst, prefix.as_slice(), Some(max_keys))?;
+    let status = if more { atoms::more() } else { atoms::done() };
+    Ok((atoms::ok(), moved, status).encode(env))
+}
+
+// ------------------------ Conditional writes ------------------------
+
+// The existence check and the write happen under the same `DB_INSTANCE` guard.
+// Every NIF that writes holds that guard too, so no other caller can slip a write
+// in between and two concurrent callers cannot both observe the key as absent.
+fn put_if_absent_locked(
+    db: &DB,
+    cf: Option<&ColumnFamily>,
+    key: &[u8],
+    value: &[u8],
+) -> NifResult<bool> {
+    let existing = match cf {
+        Some(cf) => db.get_pinned_cf(cf, key),
+        None => db.get_pinned(key),
+    }
+    .map_err(to_nif_err)?;
+    if existing.is_some() {
+        return Ok(false);
+    }
+
+    value_cache_invalidate(key);
+    match cf {
+        Some(cf) => db.put_cf_opt(cf, key, value, &default_write_options()),
+        None => db.put_opt(key, value, &default_write_options()),
+    }
+    .map(|_| true)
+    .map_err(to_nif_err)
+}
+
+/// Writes `value` under `key` only if the key does not exist yet.
+///
+/// Returns `true` if the value was written, `false` if the key was already present.
+#[rustler::nif(name = "put_if_absent")]
+fn put_if_absent(key: Binary, value: Binary) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    put_if_absent_locked(db, None, key.as_slice(), value.as_slice())
+}
+
+/// Same as `put_if_absent/2`, scoped to a named column family.
+#[rustler::nif(name = "put_if_absent_cf")]
+fn put_if_absent_cf(cf_name: String, key: Binary, value: Binary) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    put_if_absent_locked(db, Some(cf), key.as_slice(), value.as_slice())
+}
+
+// ------------------------ Column families ------------------------
+
+// RocksDB reports a duplicate column family as `InvalidArgument`, distinguishable only by message.
+fn is_cf_already_exists(err: &rocksdb::Error) -> bool {
+    err.kind() == rocksdb::ErrorKind::InvalidArgument && err.to_string().contains("already exists")
+}
+
+// Creates `cf_name` with `options` unless it is already open. An "already exists"
+// error from a concurrent creator counts as success.
+fn ensure_cf(db: &mut DB, cf_name: &str, options: &Options) -> NifResult<()> {
+    if db.cf_handle(cf_name).is_some() {
+        return Ok(());
+    }
+    match db.create_cf(cf_name, options) {
+        Ok(()) => Ok(()),
+        Err(e) if is_cf_already_exists(&e) => Ok(()),
+        Err(e) => Err(to_nif_err(e)),
+    }
+}
+
+/// Puts a key-value pair into `cf_name`, creating the column family first if needed,
+/// with the options the database was opened with.
+#[rustler::nif(name = "put_cf_autocreate")]
+fn put_cf_autocreate(cf_name: String, key: Binary, value: Binary) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &mut *shared.write();
+
+    ensure_cf(db, &cf_name, &shared.options)?;
+    let cf = cf_or_err(db, &cf_name)?;
+    value_cache_invalidate(key.as_slice());
+    db.put_cf_opt(
+        cf,
+        key.as_slice(),
+        value.as_slice(),
+        &default_write_options(),
+    )
+    .map(|_| true)
+    .map_err(to_nif_err)
+}
+
+/// Creates several column families as one schema step: either all of them exist
+/// afterwards or, on error, none of the new ones do.
+///
+/// `specs` is a list of `{name, opts}` tuples, with `opts` a keyword list of the
+/// column family options of `init_with_opts/2` (`:merge_operator`, `:comparator`,
+/// `:block_cache`, `:compaction_style`, ...); ones that apply to the whole handle,
+/// like `:disable_wal` or `:log_pid`, are ignored.
+///
+/// Every spec is parsed and the names are checked against the open CFs before
+/// anything is created. RocksDB's atomic `CreateColumnFamilies` is not bound by the
+/// rocksdb crate, so the CFs are then created in order and, if one fails, those
+/// created by this call are dropped again before the error is returned. A crash
+/// in the middle can still leave some of them behind; calling again after one
+/// reports them as already existing. Returns `:ok`.
+#[rustler::nif(name = "create_cfs", schedule = "DirtyIo")]
+fn create_cfs(specs: ListIterator) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &mut *write_db(&db_guard)?;
+
+    let mut parsed = Vec::new();
+    for spec in specs {
+        let (name, opts): (String, Term) = spec.decode()?;
+        if db.cf_handle(&name).is_some() || parsed.iter().any(|(n, _)| *n == name) {
+            return Err(Error::Term(Box::new(format!(
+                "Column family already exists: {}",
+                name
+            ))));
+        }
+        parsed.push((name, parse_db_options(opts)?.options));
+    }
+
+    for (created, (name, options)) in parsed.iter().enumerate() {
+        if let Err(e) = db.create_cf(name, options) {
+            // Best effort: the original error is what the caller needs to see.
+            for (undo, _) in &parsed[..created] {
+                let _ = db.drop_cf(undo);
+            }
+            return Err(to_nif_err(e));
+        }
+    }
+    Ok(atoms::ok())
+}
+
+/// Creates column family `name` with default options. Returns `:ok`, or
+/// `{:error, :already_exists}` if it is already open.
+#[rustler::nif(name = "create_cf", schedule = "DirtyIo")]
+fn create_cf(name: String) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &mut *write_db(&db_guard)?;
+
+    if db.cf_handle(&name).is_some() {
+        return Err(Error::Term(Box::new(atoms::already_exists())));
+    }
+    match db.create_cf(&name, &Options::default()) {
+        Ok(()) => Ok(atoms::ok()),
+        Err(e) if is_cf_already_exists(&e) => Err(Error::Term(Box::new(atoms::already_exists()))),
+        Err(e) => Err(to_nif_err(e)),
+    }
+}
+
+/// Drops column family `name` and all of its data. Returns `:ok`, or
+/// `{:error, :not_found}` if there is no such column family. The `"default"` CF
+/// cannot be dropped.
+#[rustler::nif(name = "drop_cf", schedule = "DirtyIo")]
+fn drop_cf(name: String) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &mut *write_db(&db_guard)?;
+
+    if db.cf_handle(&name).is_none() {
+        return Err(Error::Term(Box::new(atoms::not_found())));
+    }
+    db.drop_cf(&name).map(|_| atoms::ok()).map_err(to_nif_err)
+}
+
+/// Lists the column families open on the handle as `{:ok, [name]}`, `"default"`
+/// first. Unlike the other NIFs, an uninitialized database is reported as
+/// `{:error, :db_not_initialized}`.
+///
+/// The rocksdb crate does not expose its handle map, so the names come from the
+/// CFs on disk, keeping those that have an open handle; CFs that exist on disk
+/// but were not opened (see `init/1`) are left out.
+#[rustler::nif(name = "list_cf")]
+fn list_cf() -> NifResult<(atom::Atom, Vec<String>)> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard
+        .as_ref()
+        .ok_or_else(|| Error::Term(Box::new(atoms::db_not_initialized())))?;
+    let db = &*shared.read();
+
+    let on_disk = DB::list_cf(&Options::default(), db.path()).map_err(to_nif_err)?;
+    let mut names = vec!["default".to_string()];
+    names.extend(
+        on_disk
+            .into_iter()
+            .filter(|name| name != "default" && db.cf_handle(name).is_some()),
+    );
+    Ok((atoms::ok(), names))
+}
+
+// A key ordered right after `key` by `key_order()`, to end an exclusive range that
+// takes `key` in. None when `key` is the empty key under reverse_bytewise, which
+// nothing orders after.
+fn key_after(key: &[u8]) -> Option<Vec<u8>> {
+    // Appending a zero byte gives the next possible key in bytewise order.
+    let mut next = [key, &[0]].concat();
+    if key_order()(&next, key) == Ordering::Greater {
+        return Some(next);
+    }
+    // Under reverse_bytewise, any key bytewise below `key` does: its prefix, or
+    // the same key with the last byte decremented.
+    next = key.to_vec();
+    match next.pop()? {
+        0 => {}
+        last => next.push(last - 1),
+    }
+    Some(next)
+}
+
+// Approximate on-disk bytes of the whole of `cf`, optionally plus its memtables.
+// Callers hold the `DB_INSTANCE` guard, for `key_order()`.
+fn approximate_cf_size(db: &DB, cf: &ColumnFamily, include_mem_table: bool) -> NifResult<u64> {
+    // Find the real key bounds so the range covers every key whatever they look like.
+    let mut iter = db.raw_iterator_cf(cf);
+    iter.seek_to_first();
+    let first_key = iter.key().map(|k| k.to_vec());
+    iter.seek_to_last();
+    let last_key = iter.key().map(|k| k.to_vec());
+    iter.status().map_err(to_nif_err)?;
+
+    let mut size = match (first_key, last_key) {
+        (Some(first_key), Some(last_key)) => {
+            // The range end is exclusive. Past the empty key it just leaves that key out.
+            let end = key_after(&last_key).unwrap_or(last_key);
+            let range = rocksdb::Range::new(&first_key, &end);
+            db.get_approximate_sizes_cf(cf, &[range])[0]
+        }
+        _ => 0,
+    };
+
+    // RocksDB can count the memtable data of a range into the estimate, but the
+    // rocksdb crate only exposes the files-only form of the call, so the memory the
+    // memtables take is added instead.
+    if include_mem_table {
+        size += db
+            .property_int_value_cf(cf, "rocksdb.cur-size-all-mem-tables")
+            .map_err(to_nif_err)?
+            .unwrap_or(0);
+    }
+    Ok(size)
+}
+
+/// Returns the approximate on-disk size in bytes of the named column family.
+///
+/// This is RocksDB's SST-based estimate and excludes data still in the memtable;
+/// use `cf_size_2/2` to include it.
+#[rustler::nif(name = "cf_size")]
+fn cf_size(cf_name: String) -> NifResult<u64> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    approximate_cf_size(db, cf, false)
+}
+
+/// Same as `cf_size/1`; with `include_mem_table` set, the memory used by the CF's
+/// active and unflushed memtables is added to the estimate. That is their arena
+/// size, which includes RocksDB's per-entry overhead, so it overstates the data
+/// they hold rather than estimating it the way the on-disk figure is.
+#[rustler::nif(name = "cf_size_2")]
+fn cf_size_2(cf_name: String, include_mem_table: bool) -> NifResult<u64> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    approximate_cf_size(db, cf, include_mem_table)
+}
+
+// Entries replayed per write batch by `rebuild_cf_from_dump/2`.
+const REBUILD_BATCH_ENTRIES: usize = 10_000;
+
+// Calls `f` with every key-value pair of a full dump of the dump tool, returning
+// the number of entries. A sharded dump is read from its parts in order.
+fn for_each_dump_entry(
+    dump_path: &Path,
+    mut f: impl FnMut(Vec<u8>, Vec<u8>) -> NifResult<()>,
+) -> NifResult<u64> {
+    let to_err = |e: std::io::Error| Error::Term(Box::new(e.to_string()));
+    let files = dump_files(dump_path);
+    let sharded = files.len() > 1 || files[0] != dump_path;
+
+    let mut entries = 0;
+    for (index, path) in files.iter().enumerate() {
+        let file = std::fs::File::open(path).map_err(to_err)?;
+        let mut reader = std::io::BufReader::new(file);
+        let (mode, part) = read_header(&mut reader).map_err(to_err)?;
+        // Keys-only and values-only dumps cannot be replayed.
+        if mode != DumpMode::Full {
+            return Err(Error::Term(Box::new("Not a key-value dump")));
+        }
+        if sharded && part != Some(index as u32) {
+            return Err(Error::Term(Box::new(format!(
+                "Not part {} of the dump: {}",
+                index,
+                path.display()
+            ))));
+        }
+        while let Some((key, value)) = read_entry(&mut reader).map_err(to_err)? {
+            f(key, value)?;
+            entries += 1;
+        }
+    }
+    Ok(entries)
+}
+
+// Writes every key-value pair of a full dump into `cf`, returning the number of
+// entries.
+fn replay_dump(db: &DB, cf: &ColumnFamily, dump_path: &Path) -> NifResult<u64> {
+    let mut batch = WriteBatch::default();
+    let entries = for_each_dump_entry(dump_path, |key, value| {
+        batch.put_cf(cf, key, value);
+        if batch.len() >= REBUILD_BATCH_ENTRIES {
+            db.write_opt(std::mem::take(&mut batch), &default_write_options())
+                .map_err(to_nif_err)?;
+        }
+        Ok(())
+    })?;
+    db.write_opt(batch, &default_write_options())
+        .map_err(to_nif_err)?;
+    Ok(entries)
+}
+
+// Drops `cf_name` (which must exist) and creates it again, empty, with `options`.
+fn recreate_cf(db: &mut DB, cf_name: &str, options: &Options) -> NifResult<()> {
+    db.drop_cf(cf_name).map_err(to_nif_err)?;
+    db.create_cf(cf_name, options).map_err(to_nif_err)
+}
+
+/// Replaces the contents of `cf_name` with the key-value pairs of a dump file
+/// written by the dump tool, leaving the rest of the database and the open handle
+/// alone. For repairing a single corrupt CF from a known-good dump.
+///
+/// A dump written with `--shard-bytes` is read from its parts in order: pass the
+/// path without the part, e.g. `dump/contractstate.dump` for
+/// `dump/contractstate.part0.dump` and onwards. A part whose header carries
+/// another index than its name is an error.
+///
+/// The whole dump is read through first, and an unreadable, truncated or
+/// keys-only dump is an error that leaves the CF untouched. Only then is the CF
+/// dropped, recreated with the options the database was opened with (keeping its
+/// `:merge_operator`, `:comparator` and the like) and the dump replayed in
+/// batches. If replay still fails partway (a write error, or the dump changed in
+/// between), the CF is dropped and recreated again so it is left empty rather than
+/// half filled, and the error is returned.
+///
+/// The `"default"` CF cannot be dropped, so it cannot be rebuilt this way. Returns
+/// `{:ok, entries}`.
+#[rustler::nif(name = "rebuild_cf_from_dump", schedule = "DirtyIo")]
+fn rebuild_cf_from_dump<'a>(
+    env: Env<'a>,
+    cf_name: String,
+    dump_path: String,
+) -> NifResult<Term<'a>> {
+    // Read without holding the database, which is blocked while the CF is replaced.
+    let dump_path = Path::new(&dump_path);
+    for_each_dump_entry(dump_path, |_, _| Ok(()))?;
+
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &mut *shared.write();
+    cf_or_err(db, &cf_name)?;
+
+    // Only default-CF keys are in the value cache, and the default CF is never
+    // dropped, so nothing here needs invalidating.
+    recreate_cf(db, &cf_name, &shared.options)?;
+    let replayed = {
+        let cf = cf_or_err(db, &cf_name)?;
+        replay_dump(db, cf, dump_path)
+    };
+    match replayed {
+        Ok(entries) => Ok((atoms::ok(), entries).encode(env)),
+        Err(e) => {
+            recreate_cf(db, &cf_name, &shared.options)?;
+            Err(e)
+        }
+    }
+}
+
+// ------------------------ Integer keys ------------------------
+
+// Big-endian keeps the byte order of the encoded keys equal to their numeric
+// order, so range scans over the default bytewise comparator come out sorted.
+fn u64_key(key: u64) -> [u8; 8] {
+    key.to_be_bytes()
+}
+
+/// Puts `value` under the 8-byte big-endian encoding of the integer `key`.
+#[rustler::nif(name = "put_u64")]
+fn put_u64(key: u64, value: Binary) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    value_cache_invalidate(&u64_key(key));
+    db.put_opt(u64_key(key), value.as_slice(), &default_write_options())
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+/// Gets the value stored under the integer `key`, or `nil` if absent.
+#[rustler::nif(name = "get_u64")]
+fn get_u64<'a>(env: Env<'a>, key: u64) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    match db.get(u64_key(key)).map_err(to_nif_err)? {
+        Some(value) => vec_to_binary(env, value),
+        None => Ok(atom::nil().encode(env)),
+    }
+}
+
+/// Deletes the integer `key`. Deleting an absent key succeeds.
+#[rustler::nif(name = "delete_u64")]
+fn delete_u64(key: u64) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    value_cache_invalidate(&u64_key(key));
+    db.delete_opt(u64_key(key), &default_write_options())
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+// ------------------------ User timestamps ------------------------
+
+// Width of the timestamps appended to every key when user timestamps are enabled.
+const TIMESTAMP_SIZE: usize = 8;
+
+// Timestamps are encoded as fixed64 little-endian, like RocksDB's own
+// `EncodeFixed64`, so tools built on its U64 timestamp comparator can read them.
+fn encode_timestamp(ts: u64) -> [u8; TIMESTAMP_SIZE] {
+    ts.to_le_bytes()
+}
+
+fn compare_timestamps(a: &[u8], b: &[u8]) -> Ordering {
+    let decode = |ts: &[u8]| ts.try_into().map(u64::from_le_bytes).unwrap_or(0);
+    decode(a).cmp(&decode(b))
+}
+
+fn strip_timestamp(key: &[u8], has_ts: bool) -> &[u8] {
+    if has_ts {
+        &key[..key.len().saturating_sub(TIMESTAMP_SIZE)]
+    } else {
+        key
+    }
+}
+
+fn compare_without_timestamps(a: &[u8], a_has_ts: bool, b: &[u8], b_has_ts: bool) -> Ordering {
+    strip_timestamp(a, a_has_ts).cmp(strip_timestamp(b, b_has_ts))
+}
+
+// User keys sort bytewise; versions of the same key sort newest timestamp first,
+// which is the order RocksDB requires to answer "as of" reads.
+fn compare_with_timestamps(a: &[u8], b: &[u8]) -> Ordering {
+    compare_without_timestamps(a, true, b, true).then_with(|| {
+        let a_ts = &a[a.len().saturating_sub(TIMESTAMP_SIZE)..];
+        let b_ts = &b[b.len().saturating_sub(TIMESTAMP_SIZE)..];
+        compare_timestamps(b_ts, a_ts)
+    })
+}
+
+fn ensure_timestamps_enabled() -> NifResult<()> {
+    if TIMESTAMPS_ENABLED.load(atomic::Ordering::SeqCst) {
+        Ok(())
+    } else {
+        Err(Error::Term(Box::new(atoms::timestamps_disabled())))
+    }
+}
+
+/// Puts `value` under `key` at the user timestamp `ts`.
+///
+/// `ts` is an unsigned 64-bit integer stored as the 8-byte timestamp RocksDB
+/// appends to the key. Requires a database opened with `{:user_timestamps, true}`,
+/// otherwise returns `{:error, :timestamps_disabled}`.
+#[rustler::nif(name = "put_ts")]
+fn put_ts(key: Binary, ts: u64, value: Binary) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    ensure_timestamps_enabled()?;
+
+    value_cache_invalidate(key.as_slice());
+    db.put_with_ts_opt(
+        key.as_slice(),
+        encode_timestamp(ts),
+        value.as_slice(),
+        &default_write_options(),
+    )
+    .map(|_| true)
+    .map_err(to_nif_err)
+}
+
+/// Gets the value of `key` as of the user timestamp `ts`: the newest version
+/// written with a timestamp `<= ts`.
+///
+/// Returns `{:ok, value}`, `nil` if the key had no version at `ts`, or
+/// `{:error, :timestamps_disabled}` if the database was not opened with
+/// `{:user_timestamps, true}`.
+#[rustler::nif(name = "get_ts")]
+fn get_ts<'a>(env: Env<'a>, key: Binary, ts: u64) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    ensure_timestamps_enabled()?;
+
+    let mut read_opts = ReadOptions::default();
+    read_opts.set_timestamp(encode_timestamp(ts));
+    match db.get_opt(key.as_slice(), &read_opts).map_err(to_nif_err)? {
+        Some(value) => Ok((atoms::ok(), vec_to_binary(env, value)?).encode(env)),
+        None => Ok(atom::nil().encode(env)),
+    }
+}
+
+// ------------------------ Partial reads ------------------------
+
+/// Gets the `len` bytes of the value of `key` starting at byte `offset`.
+///
+/// The value is read pinned, straight from the block cache or memtable, and only
+/// the slice is copied into the returned binary.
+///
+/// Returns `{:ok, slice}`, `nil` if the key does not exist, or
+/// `{:error, :out_of_range}` if `offset + len` is past the end of the value.
+#[rustler::nif(name = "get_slice")]
+fn get_slice<'a>(env: Env<'a>, key: Binary, offset: usize, len: usize) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let Some(value) = db.get_pinned(key.as_slice()).map_err(to_nif_err)? else {
+        return Ok(atom::nil().encode(env));
+    };
+    let slice = offset
+        .checked_add(len)
+        .and_then(|end| value.get(offset..end))
+        .ok_or_else(|| Error::Term(Box::new(atoms::out_of_range())))?;
+
+    let mut binary = OwnedBinary::new(slice.len())
+        .ok_or_else(|| Error::Term(Box::new("Failed to allocate binary")))?;
+    binary.as_mut_slice().copy_from_slice(slice);
+    Ok((atoms::ok(), binary.release(env)).encode(env))
+}
+
+fn value_size_in<'a>(
+    env: Env<'a>,
+    db: &DB,
+    cf: Option<&ColumnFamily>,
+    key: &[u8],
+) -> NifResult<Term<'a>> {
+    let value = match cf {
+        Some(cf) => db.get_pinned_cf(cf, key),
+        None => db.get_pinned(key),
+    }
+    .map_err(to_nif_err)?;
+    Ok(match value {
+        Some(value) => value.len().encode(env),
+        None => atoms::not_found().encode(env),
+    })
+}
+
+/// Returns the length in bytes of the value of `key`, or `:not_found` if the key
+/// does not exist; an empty value is `0`. The value is read pinned and never
+/// copied, so this is cheap even for large values.
+#[rustler::nif(name = "value_size")]
+fn value_size<'a>(env: Env<'a>, key: Binary) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    value_size_in(env, db, None, key.as_slice())
+}
+
+/// Same as `value_size/1` for a key in `cf_name`.
+#[rustler::nif(name = "value_size_cf")]
+fn value_size_cf<'a>(env: Env<'a>, cf_name: String, key: Binary) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    value_size_in(env, db, Some(cf), key.as_slice())
+}
+
+// ------------------------ Key order ------------------------
+
+// The order of the database in `DB_INSTANCE`, for callers holding its guard.
+fn key_order() -> fn(&[u8], &[u8]) -> Ordering {
+    if KEYS_REVERSED.load(atomic::Ordering::SeqCst) {
+        reverse_bytewise_compare
+    } else {
+        <[u8]>::cmp
+    }
+}
+
+/// Compares two keys the way the open database orders them, returning `:lt`,
+/// `:eq` or `:gt` for `a` relative to `b`.
+///
+/// Follows the comparator given to `init_with_opts/2`: bytewise by default,
+/// descending with `{:comparator, :reverse_bytewise}`. With `{:user_timestamps, true}`
+/// the keys are compared without timestamps, bytewise.
+#[rustler::nif(name = "compare_keys")]
+fn compare_keys(a: Binary, b: Binary) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+
+    Ok(match key_order()(a.as_slice(), b.as_slice()) {
+        Ordering::Less => atoms::lt(),
+        Ordering::Equal => atoms::eq(),
+        Ordering::Greater => atoms::gt(),
+    })
+}
+
+// ------------------------ Merge operators ------------------------
+
+// Appends `<<len::32-big, bytes::binary>>`, the same framing the dump tool uses.
+fn push_framed(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+// Reads one framed field at `*pos` and advances past it; `None` if truncated.
+fn take_framed<'b>(bytes: &'b [u8], pos: &mut usize) -> Option<&'b [u8]> {
+    let len_bytes: [u8; 4] = bytes.get(*pos..*pos + 4)?.try_into().ok()?;
+    let start = *pos + 4;
+    let field = bytes.get(start..start + u32::from_be_bytes(len_bytes) as usize)?;
+    *pos = start + field.len();
+    Some(field)
+}
+
+// Encodes one list item as `<<len::32-big, item::binary>>`.
+fn encode_list_item(item: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(4 + item.len());
+    push_framed(&mut encoded, item);
+    encoded
+}
+
+// Operands are already framed by `merge_append`, so both full and partial merges
+// are plain concatenation. A missing base value starts a fresh list.
+fn list_append_merge(
+    _key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    let mut list = existing.map(|v| v.to_vec()).unwrap_or_default();
+    for operand in operands.iter() {
+        list.extend_from_slice(operand);
+    }
+    Some(list)
+}
+
+/// Appends `item` to the list stored at `key` without reading the current value.
+///
+/// Requires the database to be opened with `{:merge_operator, :list_append}`.
+/// Reads return the whole list as concatenated `<<len::32-big, item::binary>>` entries.
+#[rustler::nif(name = "merge_append")]
+fn merge_append(key: Binary, item: Binary) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    value_cache_invalidate(key.as_slice());
+    db.merge_opt(
+        key.as_slice(),
+        encode_list_item(item.as_slice()),
+        &default_write_options(),
+    )
+    .map(|_| true)
+    .map_err(to_nif_err)
+}
+
+// Field maps are stored as framed `field, value` pairs sorted by field name.
+// Merge operands are sequences of operations, each a tag byte followed by the
+// framed field and, for upserts, the framed value.
+const FIELD_UPSERT: u8 = 0;
+const FIELD_DELETE: u8 = 1;
+
+fn decode_field_map(bytes: &[u8]) -> Option<BTreeMap<Vec<u8>, Vec<u8>>> {
+    let mut fields = BTreeMap::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let field = take_framed(bytes, &mut pos)?;
+        let value = take_framed(bytes, &mut pos)?;
+        fields.insert(field.to_vec(), value.to_vec());
+    }
+    Some(fields)
+}
+
+fn apply_field_ops(fields: &mut BTreeMap<Vec<u8>, Vec<u8>>, ops: &[u8]) -> Option<()> {
+    let mut pos = 0;
+    while pos < ops.len() {
+        let tag = *ops.get(pos)?;
+        pos += 1;
+        let field = take_framed(ops, &mut pos)?;
+        match tag {
+            FIELD_UPSERT => {
+                let value = take_framed(ops, &mut pos)?;
+                fields.insert(field.to_vec(), value.to_vec());
+            }
+            // Removing a field that is not there is a no-op.
+            FIELD_DELETE => {
+                fields.remove(field);
+            }
+            _ => return None,
+        }
+    }
+    Some(())
+}
+
+// Returning `None` on a malformed base or operand makes RocksDB report corruption
+// rather than silently dropping fields.
+fn field_map_full_merge(
+    _key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    let mut fields = match existing {
+        Some(bytes) => decode_field_map(bytes)?,
+        None => BTreeMap::new(),
+    };
+    for operand in operands.iter() {
+        apply_field_ops(&mut fields, operand)?;
+    }
+
+    let mut encoded = Vec::new();
+    for (field, value) in &fields {
+        push_framed(&mut encoded, field);
+        push_framed(&mut encoded, value);
+    }
+    Some(encoded)
+}
+
+// Operation sequences compose by concatenation; applying the result is the same
+// as applying each operand in turn.
+fn field_map_partial_merge(
+    _key: &[u8],
+    _existing: Option<&[u8]>,
+    operands: &MergeOperands,
+) -> Option<Vec<u8>> {
+    let mut combined = Vec::new();
+    for operand in operands.iter() {
+        combined.extend_from_slice(operand);
+    }
+    Some(combined)
+}
+
+/// Applies field-level updates to the field map stored at `key` without reading it.
+///
+/// `ops` is a list of `{field, value}` upserts and `{field, :delete}` removals,
+/// applied in order. Requires the database to be opened with
+/// `{:merge_operator, :field_map}`. Reads return the map as concatenated
+/// `<<len::32-big, field::binary, len::32-big, value::binary>>` pairs sorted by field.
+#[rustler::nif(name = "merge_fields")]
+fn merge_fields(key: Binary, ops: ListIterator) -> NifResult<bool> {
+    let mut encoded = Vec::new();
+    for op in ops {
+        let (field, value): (Binary, Term) = op.decode()?;
+        if value.decode::<atom::Atom>().ok() == Some(atoms::delete()) {
+            encoded.push(FIELD_DELETE);
+            push_framed(&mut encoded, field.as_slice());
+        } else {
+            let value: Binary = value.decode()?;
+            encoded.push(FIELD_UPSERT);
+            push_framed(&mut encoded, field.as_slice());
+            push_framed(&mut encoded, value.as_slice());
+        }
+    }
+
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    value_cache_invalidate(key.as_slice());
+    db.merge_opt(key.as_slice(), encoded, &default_write_options())
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+// ------------------------ NIF skeletons ------------------------
+
+#[rustler::nif(name = "transaction_get_3")]
+fn transaction_get_3(_txn_id: String, _key: String, _opts: Term) -> NifResult<Option<Vec<u8>>> {
+    Err(Error::Atom("not_implemented"))
+}
+
+#[rustler::nif(name = "transaction_get_4")]
+fn transaction_get_4(
+    _txn_id: String,
+    _key: String,
+    _opts: Term,
+    _cf: String,
+) -> NifResult<Option<Vec<u8>>> {
+    Err(Error::Atom("not_implemented"))
+}
+
+/// Parses read options (keyword list or map) into `rocksdb::ReadOptions`.
+///
+/// Supported options:
+/// * `{:read_tier, :all | :cache_only | :memtable_only}` - restrict which tiers a
+///   read may touch; `:all` (the default) goes to disk when needed.
+// Also returns the `{:snapshot, snapshot}` to read through, for the caller to set
+// with `set_read_snapshot` while it reads.
+fn parse_read_options(
+    opts: Term,
+) -> NifResult<(ReadOptions, Option<ResourceArc<SnapshotResource>>)> {
+    let mut read_opts = ReadOptions::default();
+    let mut snapshot = None;
+
+    for (key_term, value) in option_pairs(opts)? {
+        let key: atom::Atom = key_term.decode()?;
+
+        if key == atoms::read_tier() {
+            let tier: atom::Atom = value.decode()?;
+            let tier = if tier == atoms::all() {
+                ReadTier::All
+            } else if tier == atoms::cache_only() {
+                ReadTier::BlockCache
+            } else if tier == atoms::memtable_only() {
+                ReadTier::Memtable
+            } else {
+                return Err(Error::Term(Box::new("Unknown read_tier")));
+            };
+            read_opts.set_read_tier(tier);
+        } else if key == atoms::snapshot() {
+            snapshot = Some(value.decode()?);
+        } else if key == atoms::fill_cache() {
+            read_opts.fill_cache(value.decode()?);
+        } else if key == atoms::verify_checksums() {
+            read_opts.set_verify_checksums(value.decode()?);
+        } else {
+            return Err(Error::Term(Box::new(format!(
+                "Unknown read option: {:?}",
+                key
+            ))));
+        }
+    }
+    Ok((read_opts, snapshot))
+}
+
+/// Gets a value from the default column family using the read options in `opts`.
+///
+/// * `{:read_tier, :all | :cache_only | :memtable_only}` - where the value may be read from.
+/// * `{:snapshot, snapshot}` - read as of a snapshot from `snapshot_new/0`.
+/// * `{:fill_cache, bool}` - whether blocks read from disk are added to the block
+///   cache (default `true`); turn off for one-off reads of cold data.
+/// * `{:verify_checksums, bool}` - whether blocks read from disk are checked against
+///   their checksums (default `true`).
+///
+/// Any other option is an error rather than being ignored.
+///
+/// Returns `{:ok, value}`, `nil` if the key does not exist, or `:not_found` if a
+/// restricted `:read_tier` could not answer from memory (the key may still be on disk).
+#[rustler::nif(name = "get_3")]
+fn get_3<'a>(env: Env<'a>, key: Binary, opts: Term<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+
+    get_with_opts(env, shared, db, None, key.as_slice(), opts)
+}
+
+/// Same as `get_3/2` for a key in column family `cf`. Returns
+/// `{:error, :cf_not_found}` if there is no such column family.
+#[rustler::nif(name = "get_4")]
+fn get_4<'a>(env: Env<'a>, key: Binary, opts: Term<'a>, cf: String) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf)?;
+
+    get_with_opts(env, shared, db, Some(cf), key.as_slice(), opts)
+}
+
+// `db` is `shared`, read-locked by the caller.
+fn get_with_opts<'a>(
+    env: Env<'a>,
+    shared: &Arc<SharedDb>,
+    db: &DB,
+    cf: Option<&ColumnFamily>,
+    key: &[u8],
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let (mut read_opts, snapshot) = parse_read_options(opts)?;
+    let _snapshot = snapshot
+        .as_deref()
+        .map(|snapshot| set_read_snapshot(&mut read_opts, snapshot, shared))
+        .transpose()?;
+    let value = match cf {
+        Some(cf) => db.get_cf_opt(cf, key, &read_opts),
+        None => db.get_opt(key, &read_opts),
+    };
+
+    match value {
+        Ok(Some(value)) => Ok((atoms::ok(), vec_to_binary(env, value)?).encode(env)),
+        Ok(None) => Ok(atom::nil().encode(env)),
+        // Memory-only tiers report `Incomplete` when the answer would need disk I/O.
+        Err(e) if e.kind() == rocksdb::ErrorKind::Incomplete => Ok(atoms::not_found().encode(env)),
+        Err(e) => Err(to_nif_err(e)),
+    }
+}
+
+#[rustler::nif(name = "transaction_put_3")]
+fn transaction_put_3(_txn_id: String, _key: String, _value: Vec<u8>) -> NifResult<bool> {
+    Err(Error::Atom("not_implemented"))
+}
+
+/// Puts a key-value pair into the default column family within a transaction of
+/// `begin_transaction/1`, with the options of `transaction_get_for_update_cf/4`:
+/// * `{:lock_timeout_ms, n}` - how long a pessimistic transaction waits for the
+///   key's lock (default 1000); 0 fails at once if another transaction holds it.
+///
+/// Returns `true`, `{:error, :timeout}` if the lock stayed held, or an error if
+/// the write fails or an option is unknown.
+#[rustler::nif(name = "transaction_put_4", schedule = "DirtyIo")]
+fn transaction_put_4(
+    txn: crate::AnyTransaction,
+    key: String,
+    value: Vec<u8>,
+    opts: Term,
+) -> NifResult<bool> {
+    let lock_timeout_ms = crate::parse_transaction_opts(opts)?;
+    crate::put_in_transaction(&txn, "default", key.as_bytes(), &value, lock_timeout_ms)
+        .map(|_| true)
+}
+
+// Parses per-call write options on top of the handle's defaults, so a call that
+// does not mention `:disable_wal` keeps the `init_with_opts/2` setting.
+fn parse_write_options(opts: Term) -> NifResult<WriteOptions> {
+    write_options_from(default_write_options(), opts)
+}
+
+fn write_options_from(mut write_opts: WriteOptions, opts: Term) -> NifResult<WriteOptions> {
+    for (key_term, value) in option_pairs(opts)? {
+        let key: atom::Atom = key_term.decode()?;
+
+        if key == atoms::sync() {
+            write_opts.set_sync(value.decode()?);
+        } else if key == atoms::disable_wal() {
+            write_opts.disable_wal(value.decode()?);
+        } else {
+            return Err(Error::Term(Box::new(format!(
+                "Unknown write option: {:?}",
+                key
+            ))));
+        }
+    }
+    Ok(write_opts)
+}
+
+/// Puts a key-value pair into the default column family using the write options
+/// in `opts`:
+/// * `{:sync, bool}` - fsync the WAL before returning, so the write survives a
+///   machine crash and not just a process crash (default `false`).
+/// * `{:disable_wal, bool}` - skip the WAL for this write (defaults to the
+///   handle's `:disable_wal` setting).
+///
+/// Returns `true`, or an error if the write fails or an option is unknown.
+#[rustler::nif(name = "put_4")]
+fn put_4(key: String, value: Vec<u8>, opts: Term) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let write_opts = parse_write_options(opts)?;
+
+    value_cache_invalidate(key.as_bytes());
+    db.put_opt(key.as_bytes(), value, &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+/// Same as `put_4/3` for a key in column family `cf`. Returns
+/// `{:error, :cf_not_found}` if there is no such column family.
+#[rustler::nif(name = "put_5")]
+fn put_5(key: String, value: Vec<u8>, opts: Term, cf: String) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf)?;
+    let write_opts = parse_write_options(opts)?;
+
+    value_cache_invalidate(key.as_bytes());
+    db.put_cf_opt(cf, key.as_bytes(), value, &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+#[rustler::nif(name = "transaction_delete_2")]
+fn transaction_delete_2(_txn_id: String, _key: String) -> NifResult<bool> {
+    Err(Error::Atom("not_implemented"))
+}
+
+#[rustler::nif(name = "transaction_delete_3")]
+fn transaction_delete_3(_txn_id: String, _key: String, _opts: Term) -> NifResult<bool> {
+    Err(Error::Atom("not_implemented"))
+}
+
+/// Deletes a key from the default column family using the write options in `opts`
+/// (see `put_4/3`). Returns `true`, also when the key did not exist.
+#[rustler::nif(name = "delete_3")]
+fn delete_3(key: String, opts: Term) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let write_opts = parse_write_options(opts)?;
+
+    value_cache_invalidate(key.as_bytes());
+    db.delete_opt(key.as_bytes(), &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+/// Same as `delete_3/2` for a key in column family `cf`. Returns
+/// `{:error, :cf_not_found}` if there is no such column family.
+#[rustler::nif(name = "delete_4")]
+fn delete_4(key: String, opts: Term, cf: String) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf)?;
+    let write_opts = parse_write_options(opts)?;
+
+    value_cache_invalidate(key.as_bytes());
+    db.delete_cf_opt(cf, key.as_bytes(), &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+#[rustler::nif(name = "transaction_iterator_2")]
+fn transaction_iterator_2(env: Env, _txn_id: String) -> NifResult<Term> {
+    Err(Error::Atom("not_implemented"))
+}
+
+#[rustler::nif(name = "transaction_iterator_3")]
+fn transaction_iterator_3<'a>(
+    env: Env<'a>,
+    _txn_id: String,
+    _opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    Err(Error::Atom("not_implemented"))
+}
+
+pub struct IteratorResource {
+    // The iterator has a lifetime dependency on the DB instance, which `db` keeps
+    // open for as long as the iterator exists. We can use `unsafe` to extend the
+    // iterator's lifetime to `'static`.
+    iter: Mutex<DBRawIterator<'static>>,
+    // Keeps the database the iterator reads open while it exists, and is
+    // read-locked around every step. Declared after `iter` so it is dropped last.
+    db: Arc<SharedDb>,
+    // The direction `iterator_next` steps in, fixed at creation:
+    // `Reverse` for `:end` and `{:from, key, :reverse}`, `Forward` otherwise.
+    direction: Direction,
+    // Tailing iterators re-seek past `last_key` once they run dry, so the next
+    // `iterator_next` picks up new writes.
+    tailing: bool,
+    // The last key handed out by `iterator_next`; backs `iterator_position/1`.
+    last_key: Mutex<Option<Vec<u8>>>,
+    // `iterator_next` returns bare keys and never copies values out.
+    keys_only: bool,
+    // `{:prefix, p}`: entries whose key does not start with `p` end the scan.
+    prefix: Option<Vec<u8>>,
+}
+
+impl IteratorResource {
+    fn in_prefix(&self, key: &[u8]) -> bool {
+        self.prefix
+            .as_ref()
+            .is_none_or(|prefix| key.starts_with(prefix))
+    }
+}
+
+enum ParsedIteratorMode {
+    Start,
+    End,
+    From { key: Vec<u8>, dir: Direction },
+    // Exclusive cursors for pagination: strictly after / strictly before `key`.
+    After(Vec<u8>),
+    Before(Vec<u8>),
+}
+
+struct ParsedIteratorOpts {
+    mode: ParsedIteratorMode,
+    // Flush the memtable before creating the iterator so it only sees SST data.
+    flush_before: bool,
+    // `{:keys_only, true}`: for scans that never look at values, e.g. index builds.
+    keys_only: bool,
+    prefix: Option<Vec<u8>>,
+    // `{:iterate_lower_bound, key}` / `{:iterate_upper_bound, key}`, for `ReadOptions`.
+    lower_bound: Option<Vec<u8>>,
+    upper_bound: Option<Vec<u8>>,
+    // `{:snapshot, snapshot}`: iterate over the snapshot's view instead of the latest.
+    snapshot: Option<ResourceArc<SnapshotResource>>,
+}
+
+fn parse_iterator_mode(value_term: Term) -> NifResult<Option<ParsedIteratorMode>> {
+    // Try to decode the value as a simple atom first (e.g., :start, :end)
+    if let Ok(mode_atom) = value_term.decode::<atom::Atom>() {
+        if mode_atom == atoms::start() {
+            return Ok(Some(ParsedIteratorMode::Start));
+        } else if mode_atom == atoms::end() {
+            return Ok(Some(ParsedIteratorMode::End));
+        }
+    // Otherwise, try to decode it as a tuple (e.g., {:from, key, :forward})
+    } else if let Ok((from_atom, key_term, dir_atom)) =
+        value_term.decode::<(atom::Atom, Term, atom::Atom)>()
+    {
+        // Ensure the tuple starts with the :from atom
+        if from_atom == atoms::from() {
+            let key = binary_to_vec(key_term)?;
+            let dir = if dir_atom == atoms::reverse() {
+                Direction::Reverse
+            } else {
+                // Default to forward if not reverse
+                Direction::Forward
+            };
+            return Ok(Some(ParsedIteratorMode::From { key, dir }));
+        }
+    // Finally, the exclusive cursors {:after, key} and {:before, key}
+    } else if let Ok((cursor_atom, key_term)) = value_term.decode::<(atom::Atom, Term)>() {
+        if cursor_atom == atoms::after() {
+            return Ok(Some(ParsedIteratorMode::After(binary_to_vec(key_term)?)));
+        } else if cursor_atom == atoms::before() {
+            return Ok(Some(ParsedIteratorMode::Before(binary_to_vec(key_term)?)));
+        } else if cursor_atom == atoms::resume() {
+            return decode_position_token(&binary_to_vec(key_term)?).map(Some);
+        }
+    }
+    Ok(None)
+}
+
+// Position tokens are `<<direction, last_key::binary>>`, with direction 0 for
+// forward and 1 for reverse iterators. They are opaque to callers, so the layout
+// can change as long as old tokens keep decoding.
+const TOKEN_FORWARD: u8 = 0;
+const TOKEN_REVERSE: u8 = 1;
+
+fn encode_position_token(direction: Direction, last_key: &[u8]) -> Vec<u8> {
+    let mut token = Vec::with_capacity(1 + last_key.len());
+    token.push(match direction {
+        Direction::Forward => TOKEN_FORWARD,
+        Direction::Reverse => TOKEN_REVERSE,
+    });
+    token.extend_from_slice(last_key);
+    token
+}
+
+// Resuming continues strictly past the last key seen, in the original direction.
+fn decode_position_token(token: &[u8]) -> NifResult<ParsedIteratorMode> {
+    match token.split_first() {
+        Some((&TOKEN_FORWARD, key)) => Ok(ParsedIteratorMode::After(key.to_vec())),
+        Some((&TOKEN_REVERSE, key)) => Ok(ParsedIteratorMode::Before(key.to_vec())),
+        _ => Err(Error::Term(Box::new("Invalid iterator position token"))),
+    }
+}
+
+fn parse_iterator_opts(env: Env, opts: Term) -> NifResult<ParsedIteratorOpts> {
+    let mut mode = None;
+    let mut flush_before = false;
+    let mut keys_only = false;
+    let mut prefix = None;
+    let mut lower_bound = None;
+    let mut upper_bound = None;
+    let mut snapshot = None;
+
+    for (key_term, value_term) in option_pairs(opts)? {
+        let key = key_term.decode::<atom::Atom>()?;
+
+        if key == atoms::iterator_mode() {
+            // Like `Keyword.get/2`, the first occurrence wins.
+            if mode.is_none() {
+                mode = parse_iterator_mode(value_term)?;
+            }
+        } else if key == atoms::flush_before() {
+            flush_before = value_term.decode()?;
+        } else if key == atoms::keys_only() {
+            keys_only = value_term.decode()?;
+        } else if key == atoms::prefix() {
+            prefix = Some(binary_to_vec(value_term)?);
+        } else if key == atoms::iterate_lower_bound() {
+            lower_bound = Some(binary_to_vec(value_term)?);
+        } else if key == atoms::iterate_upper_bound() {
+            upper_bound = Some(binary_to_vec(value_term)?);
+        } else if key == atoms::snapshot() {
+            snapshot = Some(value_term.decode()?);
+        }
+    }
+
+    // A prefix scan starts at the prefix unless a mode says otherwise.
+    let default_mode = match &prefix {
+        Some(prefix) => ParsedIteratorMode::From {
+            key: prefix.clone(),
+            dir: Direction::Forward,
+        },
+        None => ParsedIteratorMode::Start,
+    };
+    Ok(ParsedIteratorOpts {
+        mode: mode.unwrap_or(default_mode),
+        flush_before,
+        keys_only,
+        prefix,
+        lower_bound,
+        upper_bound,
+        snapshot,
+    })
+}
+
+// Flushes the memtables of `cf` (or the default CF) and waits for completion.
+// Skipped entirely when there is nothing buffered in memory.
+fn flush_if_dirty(db: &DB, cf: Option<&ColumnFamily>) -> NifResult<()> {
+    let int_property = |name: &str| -> NifResult<u64> {
+        let value = match cf {
+            Some(cf) => db.property_int_value_cf(cf, name),
+            None => db.property_int_value(name),
+        };
+        Ok(value.map_err(to_nif_err)?.unwrap_or(0))
+    };
+
+    let active = int_property("rocksdb.num-entries-active-mem-table")?;
+    let immutable = int_property("rocksdb.num-immutable-mem-table")?;
+    if active == 0 && immutable == 0 {
+        return Ok(());
+    }
+
+    match cf {
+        Some(cf) => db.flush_cf(cf),
+        None => db.flush(),
+    }
+    .map_err(to_nif_err)
+}
+
+// Seeks a freshly created raw iterator according to `mode` and returns the
+// direction subsequent `iterator_next` calls must step in.
+fn position_iterator(iter: &mut DBRawIterator, mode: &ParsedIteratorMode) -> Direction {
+    match mode {
+        ParsedIteratorMode::Start => {
+            iter.seek_to_first();
+            Direction::Forward
+        }
+        ParsedIteratorMode::End => {
+            iter.seek_to_last();
+            Direction::Reverse
+        }
+        ParsedIteratorMode::From {
+            key,
+            dir: Direction::Forward,
+        } => {
+            iter.seek(key);
+            Direction::Forward
+        }
+        ParsedIteratorMode::From {
+            key,
+            dir: Direction::Reverse,
+        } => {
+            iter.seek_for_prev(key);
+            Direction::Reverse
+        }
+        ParsedIteratorMode::After(key) => {
+            iter.seek(key);
+            if iter.key() == Some(key.as_slice()) {
+                iter.next();
+            }
+            Direction::Forward
+        }
+        ParsedIteratorMode::Before(key) => {
+            iter.seek_for_prev(key);
+            if iter.key() == Some(key.as_slice()) {
+                iter.prev();
+            }
+            Direction::Reverse
+        }
+    }
+}
+
+// Creates an iterator over `cf` (or the default CF) of `db`, positioned as `opts`
+// asks. `db` is `shared`, read-locked by the caller.
+fn new_iterator(
+    env: Env,
+    shared: &Arc<SharedDb>,
+    db: &DB,
+    cf: Option<&ColumnFamily>,
+    opts: Term,
+) -> NifResult<ResourceArc<IteratorResource>> {
+    let parsed = parse_iterator_opts(env, opts)?;
+    if parsed.flush_before {
+        flush_if_dirty(db, cf)?;
+    }
+    let mut read_opts = ReadOptions::default();
+    if parsed.prefix.is_some() {
+        // Lets RocksDB stop at the prefix itself when `:prefix_extractor` is set; the
+        // prefix check in `iterator_next` covers databases opened without one.
+        read_opts.set_prefix_same_as_start(true);
+    }
+    if let Some(lower_bound) = parsed.lower_bound {
+        read_opts.set_iterate_lower_bound(lower_bound);
+    }
+    if let Some(upper_bound) = parsed.upper_bound {
+        read_opts.set_iterate_upper_bound(upper_bound);
+    }
+    // The iterator pins the snapshot's view when created, so it stays valid even if
+    // the snapshot is released before the iterator is done.
+    let _snapshot = parsed
+        .snapshot
+        .as_deref()
+        .map(|snapshot| set_read_snapshot(&mut read_opts, snapshot, shared))
+        .transpose()?;
+
+    let mut db_iter = match cf {
+        Some(cf) => db.raw_iterator_cf_opt(cf, read_opts),
+        None => db.raw_iterator_opt(read_opts),
+    };
+    let direction = position_iterator(&mut db_iter, &parsed.mode);
+
+    let static_iter: DBRawIterator<'static> = unsafe { std::mem::transmute(db_iter) };
+    let resource = ResourceArc::new(IteratorResource {
+        iter: Mutex::new(static_iter),
+        db: shared.clone(),
+        direction,
+        tailing: false,
+        last_key: Mutex::new(None),
+        keys_only: parsed.keys_only,
+        prefix: parsed.prefix,
+    });
+    Ok(resource)
+}
+
+/// Creates an iterator over the default column family.
+///
+/// With `{:prefix, prefix}` it starts at `prefix` and finishes at the first key not
+/// starting with it, for scanning one logical table of `<<table, id>>` keys. The
+/// scan is cheapest when the database was opened with a matching
+/// `{:prefix_extractor, byte_size(prefix)}`.
+///
+/// `{:iterate_lower_bound, key}` (inclusive) and `{:iterate_upper_bound, key}`
+/// (exclusive) confine the iterator to a key range inside RocksDB: `:start` and
+/// `:end` begin at the bounds, no entry outside them is returned, and the scan
+/// finishes at a bound without reading past it. A lower bound above the upper
+/// bound gives an empty iterator.
+///
+/// `{:snapshot, snapshot}` iterates over a snapshot from `snapshot_new/0` instead
+/// of the latest data. The same options apply to `iterator_2/2`.
+#[rustler::nif(name = "iterator")]
+fn iterator(env: Env, opts: Term) -> NifResult<ResourceArc<IteratorResource>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+
+    new_iterator(env, shared, db, None, opts)
+}
+
+#[rustler::nif(name = "iterator_2")]
+fn iterator_2(env: Env, opts: Term, cf_name: String) -> NifResult<ResourceArc<IteratorResource>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    new_iterator(env, shared, db, Some(cf), opts)
+}
+
+/// Creates a forward tailing iterator over a column family, for change-feed style reads.
+///
+/// After `iterator_next` returns `:finished`, calling it again later yields keys
+/// written since, continuing after the last key returned.
+///
+/// RocksDB caveats: a tailing iterator does not hold a snapshot, so it sees writes
+/// as they land rather than a consistent view; it only moves forward; and it is
+/// only a reliable feed for append-only CFs where new keys sort after existing ones
+/// (e.g. big-endian sequence numbers). Keys inserted behind the cursor are skipped.
+#[rustler::nif(name = "tailing_iterator")]
+fn tailing_iterator(cf_name: String) -> NifResult<ResourceArc<IteratorResource>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+    let mut read_opts = ReadOptions::default();
+    read_opts.set_tailing(true);
+
+    let mut db_iter = db.raw_iterator_cf_opt(cf, read_opts);
+    db_iter.seek_to_first();
+
+    let static_iter: DBRawIterator<'static> = unsafe { std::mem::transmute(db_iter) };
     let resource = ResourceArc::new(IteratorResource {
         iter: Mutex::new(static_iter),
+        db: shared.clone(),
+        direction: Direction::Forward,
+        tailing: true,
+        last_key: Mutex::new(None),
+        keys_only: false,
+        prefix: None,
+    });
+    Ok(resource)
+}
+
+/// Moves the iterator to the next position in the direction it was created with.
+///
+/// Iterators created with `:end` or `{:from, key, :reverse}` step backwards,
+/// all others step forwards.
+///
+/// Unlike `iterator_move(iter, :next)`, it returns the entry under the cursor
+/// before stepping, so a new iterator yields its first entry.
+///
+/// Returns `{:ok, {key, value}}` for the entry at the current position (`{:ok, key}`
+/// for iterators created with `{:keys_only, true}`), or `:finished` once the
+/// iterator has moved past the last element.
+// Not a NIF of its own: `iterator_next/1` of lib.rs decodes the resource and
+// passes the iterators of this module on to here.
+pub(crate) fn iterator_next<'a>(
+    env: Env<'a>,
+    iter_res: ResourceArc<IteratorResource>,
+) -> NifResult<Term<'a>> {
+    let _db = iter_res.db.read();
+    let iter = &mut *iter_res.iter.lock().unwrap();
+
+    // A tailing iterator only notices new writes after a fresh seek.
+    if iter_res.tailing && !iter.valid() && iter.status().is_ok() {
+        let last_key = iter_res.last_key.lock().unwrap();
+        match last_key.as_deref() {
+            Some(key) => {
+                iter.seek(key);
+                if iter.key() == Some(key) {
+                    iter.next();
+                }
+            }
+            None => iter.seek_to_first(),
+        }
+    }
+
+    // Case 1: The iterator is exhausted, or stopped because of an error.
+    let (key, value) = match iter.item() {
+        Some(item) => item,
+        None => {
+            return match iter.status() {
+                Ok(()) => Ok(atoms::finished().encode(env)),
+                Err(e) => Err(to_nif_err(e)),
+            };
+        }
+    };
+    if !iter_res.in_prefix(key) {
+        return Ok(atoms::finished().encode(env));
+    }
+
+    // Case 2: Copy out the current key-value pair before stepping. The value is
+    // only a view into the current block, so skipping it avoids the copy entirely.
+    let mut key_binary = rustler::OwnedBinary::new(key.len()).unwrap();
+    key_binary.as_mut_slice().copy_from_slice(key);
+
+    let value_binary = if iter_res.keys_only {
+        None
+    } else {
+        let mut value_binary = rustler::OwnedBinary::new(value.len()).unwrap();
+        value_binary.as_mut_slice().copy_from_slice(value);
+        Some(value_binary)
+    };
+
+    *iter_res.last_key.lock().unwrap() = Some(key.to_vec());
+
+    match iter_res.direction {
+        Direction::Forward => iter.next(),
+        Direction::Reverse => iter.prev(),
+    }
+
+    let ok_atom = atoms::ok().encode(env);
+    let key_term = key_binary.release(env).encode(env);
+    let Some(value_binary) = value_binary else {
+        return Ok(rustler::types::tuple::make_tuple(env, &[ok_atom, key_term]));
+    };
+    let value_term = value_binary.release(env).encode(env);
+
+    let data_tuple = rustler::types::tuple::make_tuple(env, &[key_term, value_term]);
+    Ok(rustler::types::tuple::make_tuple(
+        env,
+        &[ok_atom, data_tuple],
+    ))
+}
+
+/// Returns the iterator's position as an opaque binary token, or `nil` if
+/// `iterator_next` has not returned an entry yet.
+///
+/// The token can be persisted and passed back later as `{:iterator_mode, {:resume, token}}`
+/// to a new iterator, which continues strictly after the last entry returned, in
+/// the same direction. Tokens stay valid across process and node restarts.
+///
+/// Returns `{:ok, token}` or `nil`.
+#[rustler::nif(name = "iterator_position")]
+fn iterator_position<'a>(
+    env: Env<'a>,
+    iter_res: ResourceArc<IteratorResource>,
+) -> NifResult<Term<'a>> {
+    let last_key = iter_res.last_key.lock().unwrap();
+    match last_key.as_deref() {
+        Some(key) => {
+            let token = encode_position_token(iter_res.direction, key);
+            Ok((atoms::ok(), vec_to_binary(env, token)?).encode(env))
+        }
+        None => Ok(atom::nil().encode(env)),
+    }
+}
+
+/// Moves the iterator and returns the entry it lands on, for scans that change
+/// direction or jump around without creating a new iterator. `action` is one of:
+/// * `:first` / `:last` - the first or last entry.
+/// * `{:seek, key}` - the first entry at or after `key`.
+/// * `{:seek_for_prev, key}` - the last entry at or before `key`.
+/// * `:next` / `:prev` - one step forwards or backwards from the cursor.
+///
+/// The cursor stays on the returned entry, while `iterator_next/1` returns the
+/// entry under the cursor and then steps: on a forward iterator, `:prev` right
+/// after `iterator_next/1` returns the same entry again, and `iterator_next/1`
+/// right after a move starts with the entry the move returned, continuing in the
+/// iterator's original direction.
+///
+/// Returns `{:ok, {key, value}}` (`{:ok, key}` for iterators created with
+/// `{:keys_only, true}`), or `:finished` if the move left the iterator's range.
+#[rustler::nif(name = "iterator_move")]
+fn iterator_move<'a>(
+    env: Env<'a>,
+    iter_res: ResourceArc<IteratorResource>,
+    action: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let _db = iter_res.db.read();
+    let iter = &mut *iter_res.iter.lock().unwrap();
+
+    if let Ok(action) = action.decode::<atom::Atom>() {
+        if action == atoms::first() {
+            iter.seek_to_first();
+        } else if action == atoms::last() {
+            iter.seek_to_last();
+        } else if action == atoms::next() || action == atoms::prev() {
+            // Stepping needs a position; an exhausted iterator stays exhausted.
+            if !iter.valid() {
+                return Ok(atoms::finished().encode(env));
+            }
+            if action == atoms::next() {
+                iter.next();
+            } else {
+                iter.prev();
+            }
+        } else {
+            return Err(Error::BadArg);
+        }
+    } else {
+        let (action, key): (atom::Atom, Binary) = action.decode()?;
+        if action == atoms::seek() {
+            iter.seek(key.as_slice());
+        } else if action == atoms::seek_for_prev() {
+            iter.seek_for_prev(key.as_slice());
+        } else {
+            return Err(Error::BadArg);
+        }
+    }
+
+    let Some((key, value)) = iter.item() else {
+        return match iter.status() {
+            Ok(()) => Ok(atoms::finished().encode(env)),
+            Err(e) => Err(to_nif_err(e)),
+        };
+    };
+    if !iter_res.in_prefix(key) {
+        return Ok(atoms::finished().encode(env));
+    }
+    *iter_res.last_key.lock().unwrap() = Some(key.to_vec());
+
+    let key_term = bytes_to_binary(env, key).encode(env);
+    if iter_res.keys_only {
+        return Ok((atoms::ok(), key_term).encode(env));
+    }
+    let value_term = bytes_to_binary(env, value).encode(env);
+    Ok((atoms::ok(), (key_term, value_term)).encode(env))
+}
+
+fn flush_with(db: &DB, cf: Option<&ColumnFamily>, wait: bool) -> NifResult<bool> {
+    let mut flush_opts = FlushOptions::default();
+    flush_opts.set_wait(wait);
+
+    match cf {
+        Some(cf) => db.flush_cf_opt(cf, &flush_opts),
+        None => db.flush_opt(&flush_opts),
+    }
+    .map(|_| true)
+    .map_err(to_nif_err)
+}
+
+/// Flushes the memtables of the default column family, or of `{:cf, name}` in
+/// `opts`, to SST files.
+///
+/// With `wait` true it returns only once the data is on disk, e.g. before taking
+/// a backup; otherwise the flush is only scheduled. Returns `true`.
+#[rustler::nif(name = "flush_3", schedule = "DirtyIo")]
+fn flush_3(opts: Term, wait: bool) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let mut cf = None;
+    for (key_term, value) in option_pairs(opts)? {
+        let key: atom::Atom = key_term.decode()?;
+        if key == atoms::cf() {
+            let cf_name: String = value.decode()?;
+            cf = Some(cf_or_err(db, &cf_name)?);
+        } else {
+            return Err(Error::Term(Box::new(format!(
+                "Unknown flush option: {:?}",
+                key
+            ))));
+        }
+    }
+    flush_with(db, cf, wait)
+}
+
+/// Same as `flush_3(cf: cf_name, wait)`.
+#[rustler::nif(name = "flush_cf", schedule = "DirtyIo")]
+fn flush_cf(cf_name: String, wait: bool) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    flush_with(db, Some(cf), wait)
+}
+
+/// Compacts the keys of `cf` in `[start, end]` (`nil` for an open end) into level
+/// `output_level`, e.g. to reclaim the space of a range removed by `delete_range_cf/3`.
+/// An `output_level` of -1 leaves the files at the level RocksDB picks. `opts` takes
+/// no options yet; any given is an error.
+///
+/// Like `compact_all/0` it runs on a dirty scheduler, releases the database guard
+/// while compacting and keeps the database open until it returns. It is not a
+/// `compact_all/0` though: it does not show as `manual_compaction_running` and may
+/// run alongside one, or another range compaction. Returns `true`.
+#[rustler::nif(name = "compact_range_5", schedule = "DirtyIo")]
+fn compact_range_5(
+    start: Option<Binary>,
+    end: Option<Binary>,
+    opts: Term,
+    cf: String,
+    output_level: i32,
+) -> NifResult<bool> {
+    if let Some((key_term, _)) = option_pairs(opts)?.into_iter().next() {
+        let key: atom::Atom = key_term.decode()?;
+        return Err(Error::Term(Box::new(format!(
+            "Unknown compaction option: {:?}",
+            key
+        ))));
+    }
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf)?;
+
+    let mut compact_opts = CompactOptions::default();
+    if output_level >= 0 {
+        compact_opts.set_change_level(true);
+        compact_opts.set_target_level(output_level);
+    }
+    db.compact_range_cf_opt(
+        cf,
+        start.as_ref().map(|s| s.as_slice()),
+        end.as_ref().map(|e| e.as_slice()),
+        &compact_opts,
+    );
+
+    Ok(true)
+}
+
+// Walks a raw iterator from the first key, counting live entries and value bytes.
+// Tombstones are never surfaced by the iterator, so the count is exact.
+fn count_live_entries(mut iter: DBRawIterator) -> NifResult<(u64, u64)> {
+    let mut count: u64 = 0;
+    let mut value_bytes: u64 = 0;
+
+    iter.seek_to_first();
+    while iter.valid() {
+        count += 1;
+        value_bytes += iter.value().map_or(0, |v| v.len() as u64);
+        iter.next();
+    }
+    iter.status().map_err(to_nif_err)?;
+
+    Ok((count, value_bytes))
+}
+
+/// Counts the live keys in the default column family by full iteration.
+///
+/// Unlike `rocksdb.estimate-num-keys` this is exact, at the cost of an O(n) scan.
+///
+/// Returns `{:ok, count, total_value_bytes}`.
+#[rustler::nif(name = "count_exact", schedule = "DirtyIo")]
+fn count_exact<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+
+    let (count, value_bytes) = count_live_entries(db.raw_iterator())?;
+    Ok((atoms::ok(), count, value_bytes).encode(env))
+}
+
+/// Same as `count_exact/0`, scoped to a named column family.
+#[rustler::nif(name = "count_exact_cf", schedule = "DirtyIo")]
+fn count_exact_cf<'a>(env: Env<'a>, cf_name: String) -> NifResult<Term<'a>> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    let (count, value_bytes) = count_live_entries(db.raw_iterator_cf(cf))?;
+    Ok((atoms::ok(), count, value_bytes).encode(env))
+}
+
+/// Computes a deterministic digest of everything in a column family, so two nodes
+/// can confirm they hold the same state without shipping it.
+///
+/// Entries are fed to a BLAKE3 hasher in key order as `key_len (u64 big-endian),
+/// key, value_len (u64 big-endian), value`; the lengths keep different splits of
+/// the same bytes from colliding. The scan reads from an implicit snapshot, so
+/// concurrent writes do not tear the result. Nodes only agree if their CFs use the
+/// same comparator, since that fixes the order.
+///
+/// Returns `{:ok, digest}` with a 32-byte binary digest.
+#[rustler::nif(name = "cf_digest", schedule = "DirtyIo")]
+fn cf_digest<'a>(env: Env<'a>, cf_name: String) -> NifResult<Term<'a>> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    let mut read_opts = ReadOptions::default();
+    // A full scan should not evict the working set.
+    read_opts.fill_cache(false);
+    let mut iter = db.raw_iterator_cf_opt(cf, read_opts);
+
+    let mut hasher = blake3::Hasher::new();
+    iter.seek_to_first();
+    while let Some((key, value)) = iter.item() {
+        hasher.update(&(key.len() as u64).to_be_bytes());
+        hasher.update(key);
+        hasher.update(&(value.len() as u64).to_be_bytes());
+        hasher.update(value);
+        iter.next();
+    }
+    iter.status().map_err(to_nif_err)?;
+
+    let digest = hasher.finalize();
+    Ok((atoms::ok(), vec_to_binary(env, digest.as_bytes().to_vec())?).encode(env))
+}
+
+/// Returns whether a column family holds no live keys, by checking if an
+/// iterator from the start yields anything. Unlike `count_exact_cf/1` this stops at
+/// the first key, though a CF whose keys were all deleted recently may still have
+/// to skip over their tombstones.
+///
+/// Returns `true`/`false`, or `{:error, :cf_not_found}` for an unknown CF.
+#[rustler::nif(name = "cf_is_empty")]
+fn cf_is_empty(cf_name: String) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    let mut iter = db.raw_iterator_cf(cf);
+    iter.seek_to_first();
+    let empty = !iter.valid();
+    iter.status().map_err(to_nif_err)?;
+    Ok(empty)
+}
+
+/// Returns `{smallest_key, largest_key}` of `cf_name`, or `:empty` if it has no
+/// keys, with one seek to each end rather than a scan. Tombstones are skipped, so
+/// these are the bounds of the live keys. Returns `{:error, :cf_not_found}` for an
+/// unknown CF.
+#[rustler::nif(name = "cf_key_range")]
+fn cf_key_range<'a>(env: Env<'a>, cf_name: String) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    let mut iter = db.raw_iterator_cf(cf);
+    iter.seek_to_first();
+    let smallest = iter.key().map(|k| k.to_vec());
+    iter.seek_to_last();
+    let largest = iter.key().map(|k| k.to_vec());
+    iter.status().map_err(to_nif_err)?;
+
+    match (smallest, largest) {
+        (Some(smallest), Some(largest)) => {
+            Ok((vec_to_binary(env, smallest)?, vec_to_binary(env, largest)?).encode(env))
+        }
+        _ => Ok(atoms::empty().encode(env)),
+    }
+}
+
+// ------------------------ Streaming ------------------------
+
+// Chunks a stream may send before the first `stream_ack/1`.
+const STREAM_INITIAL_CREDITS: u32 = 2;
+
+pub struct StreamResource {
+    credits: Arc<StreamCredits>,
+}
+
+// Shared with the producer thread, which holds no `ResourceArc` of the stream.
+struct StreamCredits {
+    // Chunks the producer may still send; `stream_ack/1` adds one.
+    available: Mutex<u32>,
+    added: Condvar,
+}
+
+// What a stream's producer thread reads from.
+struct StreamCursor {
+    // Extended to `'static` like `IteratorResource::iter`; `db` keeps its
+    // database open.
+    iter: DBRawIterator<'static>,
+    // Read-locked while the producer reads a chunk, but not while it waits for
+    // credit. Declared after `iter` so it is dropped last.
+    db: Arc<SharedDb>,
+}
+
+impl StreamCredits {
+    // Waits for a credit and takes it. Gives up, returning `false`, once `pid` has
+    // exited, as it then never acks, or the database handle the stream reads from
+    // has been replaced; both are checked every second.
+    fn take(&self, env: &OwnedEnv, pid: LocalPid, generation: u64) -> bool {
+        let mut available = self.available.lock().unwrap();
+        while *available == 0 {
+            if HANDLE_GENERATION.load(atomic::Ordering::SeqCst) != generation
+                || !env.run(|env| env.is_process_alive(pid))
+            {
+                return false;
+            }
+            available = self
+                .added
+                .wait_timeout(available, Duration::from_secs(1))
+                .unwrap()
+                .0;
+        }
+        *available -= 1;
+        true
+    }
+}
+
+// Copies the stream term saved in `stream_env` into `env`, for a message.
+fn load_stream<'a>(stream_env: &OwnedEnv, stream: &SavedTerm, env: Env<'a>) -> Term<'a> {
+    stream_env.run(|saved_env| stream.load(saved_env).in_env(env))
+}
+
+fn bytes_to_binary<'a>(env: Env<'a>, bytes: &[u8]) -> Binary<'a> {
+    let mut binary = rustler::NewBinary::new(env, bytes.len());
+    binary.as_mut_slice().copy_from_slice(bytes);
+    binary.into()
+}
+
+/// Pushes every entry of `cf_name` to `pid` from a background thread, instead of
+/// the caller pulling them with `iterator_next/1`. Returns `{:ok, stream}`.
+///
+/// `pid` receives `{:chunk, stream, [{key, value}, ...]}` messages of up to
+/// `chunk_size` entries in key order, then `{:done, stream, count}`, or
+/// `{:error, stream, {kind, message}}` if reading fails. `stream` identifies the
+/// stream in the messages (match it with `^stream`).
+///
+/// Sending is credit based so a slow consumer's mailbox stays small: the first
+/// two chunks go out at once, and each further chunk only after a `stream_ack/1`
+/// for an earlier one. The entries are read from an implicit snapshot taken by
+/// this call, and the database is kept open until the stream ends. The stream
+/// stops early if `pid` exits or the database is replaced by another init, both
+/// noticed within a second; in neither case is `:done` sent.
+#[rustler::nif(name = "stream_cf")]
+fn stream_cf<'a>(
+    env: Env<'a>,
+    cf_name: String,
+    pid: LocalPid,
+    chunk_size: usize,
+) -> NifResult<Term<'a>> {
+    if chunk_size == 0 {
+        return Err(Error::BadArg);
+    }
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    let mut read_opts = ReadOptions::default();
+    // A full export should not evict the working set from the block cache.
+    read_opts.fill_cache(false);
+    let db_iter = db.raw_iterator_cf_opt(cf, read_opts);
+    // Same lifetime extension as `IteratorResource`.
+    let iter: DBRawIterator<'static> = unsafe { std::mem::transmute(db_iter) };
+    let cursor = StreamCursor {
+        iter,
+        db: shared.clone(),
+    };
+    let generation = HANDLE_GENERATION.load(atomic::Ordering::SeqCst);
+
+    let credits = Arc::new(StreamCredits {
+        available: Mutex::new(STREAM_INITIAL_CREDITS),
+        added: Condvar::new(),
     });
-    Ok(resource)
+    let stream = ResourceArc::new(StreamResource {
+        credits: credits.clone(),
+    });
+    // The messages carry the stream, encoded once here.
+    let stream_env = OwnedEnv::new();
+    let stream_term = stream_env.save(stream.encode(env));
+    std::thread::spawn(move || {
+        let mut owned_env = OwnedEnv::new();
+        // Moves the whole cursor in, so its fields drop in declaration order.
+        let mut cursor = cursor;
+        let iter = &mut cursor.iter;
+        let mut count: u64 = 0;
+        {
+            let _db = cursor.db.read();
+            iter.seek_to_first();
+        }
+        while HANDLE_GENERATION.load(atomic::Ordering::SeqCst) == generation {
+            let mut entries = Vec::with_capacity(chunk_size);
+            let status = {
+                let _db = cursor.db.read();
+                while entries.len() < chunk_size {
+                    let (Some(key), Some(value)) = (iter.key(), iter.value()) else {
+                        break;
+                    };
+                    entries.push((key.to_vec(), value.to_vec()));
+                    iter.next();
+                }
+                iter.status()
+            };
+
+            if let Err(e) = status {
+                let _ = owned_env.send_and_clear(&pid, |env| {
+                    let reason = (error_kind(&e), e.to_string());
+                    let stream = load_stream(&stream_env, &stream_term, env);
+                    (atoms::error(), stream, reason).encode(env)
+                });
+                return;
+            }
+            if entries.is_empty() {
+                let _ = owned_env.send_and_clear(&pid, |env| {
+                    let stream = load_stream(&stream_env, &stream_term, env);
+                    (atoms::done(), stream, count).encode(env)
+                });
+                return;
+            }
+            if !credits.take(&owned_env, pid, generation) {
+                return;
+            }
+
+            count += entries.len() as u64;
+            let sent = owned_env.send_and_clear(&pid, |env| {
+                let entries: Vec<(Binary, Binary)> = entries
+                    .iter()
+                    .map(|(k, v)| (bytes_to_binary(env, k), bytes_to_binary(env, v)))
+                    .collect();
+                let stream = load_stream(&stream_env, &stream_term, env);
+                (atoms::chunk(), stream, entries).encode(env)
+            });
+            if sent.is_err() {
+                return;
+            }
+        }
+    });
+
+    Ok((atoms::ok(), stream).encode(env))
+}
+
+/// Acknowledges one chunk of a `stream_cf/3` stream, letting it send another.
+/// Returns `:ok`.
+#[rustler::nif(name = "stream_ack")]
+fn stream_ack(stream: ResourceArc<StreamResource>) -> atom::Atom {
+    *stream.credits.available.lock().unwrap() += 1;
+    stream.credits.added.notify_one();
+    atoms::ok()
+}
+
+// ------------------------ Snapshots ------------------------
+
+pub struct SnapshotResource {
+    // Like `IteratorResource`, the snapshot's lifetime is extended to `'static`.
+    // `None` once released by `snapshot_release/1`.
+    snapshot: RwLock<Option<SnapshotWithThreadMode<'static, DB>>>,
+    // The database the snapshot was taken on, kept open until the snapshot has
+    // been released against it. Declared after `snapshot` so it is dropped last.
+    db: Arc<SharedDb>,
+}
+
+impl SnapshotResource {
+    // `db` is `shared`, read-locked by the caller.
+    fn new(shared: &Arc<SharedDb>, db: &DB) -> ResourceArc<SnapshotResource> {
+        let snapshot: SnapshotWithThreadMode<'static, DB> =
+            unsafe { std::mem::transmute(db.snapshot()) };
+        ResourceArc::new(SnapshotResource {
+            snapshot: RwLock::new(Some(snapshot)),
+            db: shared.clone(),
+        })
+    }
+
+    fn release(&self) {
+        // Also runs from `Drop`, so a poisoned lock must not panic.
+        let _db = self.db.db.read().unwrap_or_else(|e| e.into_inner());
+        let snapshot = self
+            .snapshot
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+        drop(snapshot);
+    }
+}
+
+impl Drop for SnapshotResource {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+// Reads through `snapshot` from `shared`, which the caller has read-locked. The
+// returned guard must be held for as long as `read_opts` is used to start a read,
+// so that `snapshot_release/1` cannot free the snapshot meanwhile.
+fn set_read_snapshot<'s>(
+    read_opts: &mut ReadOptions,
+    snapshot: &'s SnapshotResource,
+    shared: &Arc<SharedDb>,
+) -> NifResult<RwLockReadGuard<'s, Option<SnapshotWithThreadMode<'static, DB>>>> {
+    // Not `shared.read()`: the caller's read lock already pins the database.
+    if !Arc::ptr_eq(&snapshot.db, shared) {
+        return Err(Error::Term(Box::new(atoms::snapshot_db_mismatch())));
+    }
+    let guard = snapshot.snapshot.read().unwrap();
+    match guard.as_ref() {
+        Some(snap) => read_opts.set_snapshot(snap),
+        None => return Err(Error::Term(Box::new(atoms::snapshot_released()))),
+    }
+    Ok(guard)
+}
+
+/// Returns `{:ok, snapshot}`, a consistent view of the database as it is now: reads
+/// with `get_3(key, snapshot: snapshot)` or an iterator created with
+/// `{:snapshot, snapshot}` see none of the writes made after this call.
+///
+/// A snapshot keeps every version it sees from being compacted away, so release
+/// it with `snapshot_release/1` when done rather than waiting for garbage
+/// collection. Until then it also keeps the database it was taken on open, even
+/// once another init has replaced that database; reads through it of any other
+/// database return `{:error, :snapshot_db_mismatch}`.
+#[rustler::nif(name = "snapshot_new")]
+fn snapshot_new<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+
+    Ok((atoms::ok(), SnapshotResource::new(shared, db)).encode(env))
+}
+
+/// Releases `snapshot` now instead of when it is garbage collected. Later reads
+/// through it return `{:error, :snapshot_released}`. Returns `:ok`, also when it
+/// was already released.
+#[rustler::nif(name = "snapshot_release")]
+fn snapshot_release(snapshot: ResourceArc<SnapshotResource>) -> atom::Atom {
+    snapshot.release();
+    atoms::ok()
+}
+
+// ------------------------ Block cache ------------------------
+
+/// A resource holding an LRU block cache that can be shared between databases.
+/// RocksDB keeps its own reference to the cache, so dropping the resource after
+/// opening does not shrink a database's cache.
+pub struct CacheResource {
+    cache: Cache,
+}
+
+/// Creates an LRU block cache of `capacity_bytes` for use with `{:block_cache, cache}`.
+///
+/// Returns `{:ok, cache_resource}`.
+#[rustler::nif(name = "create_cache")]
+fn create_cache<'a>(env: Env<'a>, capacity_bytes: usize) -> NifResult<Term<'a>> {
+    let resource = ResourceArc::new(CacheResource {
+        cache: Cache::new_lru_cache(capacity_bytes),
+    });
+    Ok((atoms::ok(), resource).encode(env))
+}
+
+/// Returns the number of bytes currently held in `cache`, across every database using it.
+#[rustler::nif(name = "cache_usage")]
+fn cache_usage(cache_res: ResourceArc<CacheResource>) -> usize {
+    cache_res.cache.get_usage()
+}
+
+/// Reports approximate memory usage of the open database, including its block cache.
+///
+/// Returns `{:ok, %{mem_table_total: n, mem_table_unflushed: n,
+/// mem_table_readers_total: n, cache_total: n}}`. `cache_total` is the usage of the
+/// cache the database was opened with by `{:block_cache, cache}`, shared with every
+/// other database using it. Without that option it is 0: RocksDB then creates a
+/// private cache the rocksdb crate gives no access to.
+#[rustler::nif(name = "memory_usage")]
+fn memory_usage<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let shared = db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    let db = &*shared.read();
+
+    let caches: Vec<&Cache> = shared.block_cache.iter().collect();
+    let stats = rocksdb::perf::get_memory_usage_stats(Some(&[db]), Some(&caches[..]))
+        .map_err(to_nif_err)?;
+    let usage = Term::map_from_pairs(
+        env,
+        &[
+            (
+                atoms::mem_table_total().encode(env),
+                stats.mem_table_total.encode(env),
+            ),
+            (
+                atoms::mem_table_unflushed().encode(env),
+                stats.mem_table_unflushed.encode(env),
+            ),
+            (
+                atoms::mem_table_readers_total().encode(env),
+                stats.mem_table_readers_total.encode(env),
+            ),
+            (
+                atoms::cache_total().encode(env),
+                stats.cache_total.encode(env),
+            ),
+        ],
+    )?;
+    Ok((atoms::ok(), usage).encode(env))
+}
+
+// Walks `[start, end)` so every data block it touches lands in the block cache.
+// Values live in the same blocks as their keys, so nothing needs copying out.
+fn prefetch_range_in(
+    db: &DB,
+    cf: Option<&ColumnFamily>,
+    start: &[u8],
+    end: &[u8],
+) -> NifResult<u64> {
+    let mut read_opts = ReadOptions::default();
+    read_opts.fill_cache(true);
+    read_opts.set_iterate_upper_bound(end.to_vec());
+
+    let mut iter = match cf {
+        Some(cf) => db.raw_iterator_cf_opt(cf, read_opts),
+        None => db.raw_iterator_opt(read_opts),
+    };
+    let mut entries: u64 = 0;
+    iter.seek(start);
+    while iter.valid() {
+        entries += 1;
+        iter.next();
+    }
+    iter.status().map_err(to_nif_err)?;
+    Ok(entries)
+}
+
+/// Warms the block cache with the keys in `[start, end)` of the default column
+/// family, e.g. before a latency-sensitive batch job, without returning any data.
+///
+/// Runs on a dirty scheduler as it may read a lot from disk. Blocks compete with
+/// the rest of the working set, so the range should fit in the cache.
+///
+/// Returns `{:ok, entries_visited}`.
+#[rustler::nif(name = "prefetch_range", schedule = "DirtyIo")]
+fn prefetch_range<'a>(env: Env<'a>, start: Binary, end: Binary) -> NifResult<Term<'a>> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+
+    let entries = prefetch_range_in(db, None, start.as_slice(), end.as_slice())?;
+    Ok((atoms::ok(), entries).encode(env))
+}
+
+/// Same as `prefetch_range/2`, scoped to a named column family.
+#[rustler::nif(name = "prefetch_range_cf", schedule = "DirtyIo")]
+fn prefetch_range_cf<'a>(
+    env: Env<'a>,
+    cf_name: String,
+    start: Binary,
+    end: Binary,
+) -> NifResult<Term<'a>> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    let entries = prefetch_range_in(db, Some(cf), start.as_slice(), end.as_slice())?;
+    Ok((atoms::ok(), entries).encode(env))
+}
+
+// ------------------------ Perf context ------------------------
+//
+// RocksDB's perf context is per OS thread, not per Erlang process. The counters
+// only describe operations that ran on the scheduler thread the report is taken
+// from, so enable/measure/report from one process without yielding in between,
+// and keep in mind that dirty-scheduled NIFs run on different threads.
+
+fn perf_counters() -> [(atom::Atom, PerfMetric); 12] {
+    [
+        (
+            atoms::user_key_comparison_count(),
+            PerfMetric::UserKeyComparisonCount,
+        ),
+        (
+            atoms::block_cache_hit_count(),
+            PerfMetric::BlockCacheHitCount,
+        ),
+        (atoms::block_read_count(), PerfMetric::BlockReadCount),
+        (atoms::block_read_byte(), PerfMetric::BlockReadByte),
+        (atoms::block_read_time(), PerfMetric::BlockReadTime),
+        (atoms::get_snapshot_time(), PerfMetric::GetSnapshotTime),
+        (
+            atoms::get_from_memtable_time(),
+            PerfMetric::GetFromMemtableTime,
+        ),
+        (
+            atoms::get_from_memtable_count(),
+            PerfMetric::GetFromMemtableCount,
+        ),
+        (
+            atoms::get_from_output_files_time(),
+            PerfMetric::GetFromOutputFilesTime,
+        ),
+        (
+            atoms::seek_on_memtable_count(),
+            PerfMetric::SeekOnMemtableCount,
+        ),
+        (
+            atoms::internal_key_skipped_count(),
+            PerfMetric::InternalKeySkippedCount,
+        ),
+        (
+            atoms::internal_delete_skipped_count(),
+            PerfMetric::InternalDeleteSkippedCount,
+        ),
+    ]
+}
+
+/// Enables count and timing collection in the calling thread's perf context.
+#[rustler::nif(name = "perf_context_enable")]
+fn perf_context_enable() -> atom::Atom {
+    rocksdb::perf::set_perf_stats(PerfStatsLevel::EnableTime);
+    atoms::ok()
+}
+
+/// Zeroes the calling thread's perf context counters.
+#[rustler::nif(name = "perf_context_reset")]
+fn perf_context_reset() -> atom::Atom {
+    PerfContext::default().reset();
+    atoms::ok()
+}
+
+/// Returns the calling thread's perf context counters as a map of atom to integer.
+/// Times are in nanoseconds.
+#[rustler::nif(name = "perf_context_report")]
+fn perf_context_report<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let context = PerfContext::default();
+    let pairs: Vec<(Term, Term)> = perf_counters()
+        .iter()
+        .map(|(name, metric)| (name.encode(env), context.metric(*metric).encode(env)))
+        .collect();
+    Term::map_from_pairs(env, &pairs)
+}
+
+// ------------------------ Health ------------------------
+
+/// Reports whether RocksDB has hit a background error (flush/compaction I/O failure).
+///
+/// Returns `:ok`, or `{:error, {:background_errors, count}}` where `count` is the
+/// number of background errors since the database was opened.
+///
+/// RocksDB retries recoverable background errors on its own. `DB::Resume` is not
+/// exposed by the rocksdb crate, so once the underlying problem is fixed a database
+/// stuck in read-only mode has to be reopened with `init/1` or `init_with_opts/2`.
+#[rustler::nif(name = "background_error")]
+fn background_error<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let count = db
+        .property_int_value("rocksdb.background-errors")
+        .map_err(to_nif_err)?
+        .unwrap_or(0);
+    if count == 0 {
+        Ok(atoms::ok().encode(env))
+    } else {
+        Ok((atoms::error(), (atoms::background_errors(), count)).encode(env))
+    }
+}
+
+/// Reports whether RocksDB is currently throttling writes.
+///
+/// Returns `:normal`, `:delayed` (writes are being slowed down to the delayed write
+/// rate) or `:stopped` (writes block until compaction catches up). Poll this from
+/// producers to back off before a stall rather than after. The rocksdb crate has no
+/// event-listener support, so there is no push notification of changes.
+#[rustler::nif(name = "write_stall_status")]
+fn write_stall_status() -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let int_property = |name: &str| -> NifResult<u64> {
+        Ok(db
+            .property_int_value(name)
+            .map_err(to_nif_err)?
+            .unwrap_or(0))
+    };
+
+    if int_property("rocksdb.is-write-stopped")? != 0 {
+        Ok(atoms::stopped())
+    } else if int_property("rocksdb.actual-delayed-write-rate")? != 0 {
+        Ok(atoms::delayed())
+    } else {
+        Ok(atoms::normal())
+    }
+}
+
+// ------------------------ Effective options ------------------------
+
+// RocksDB rewrites `OPTIONS-<number>` whenever the options change (open,
+// `SetOptions`, CF creation); the highest number holds the options in effect.
+fn latest_options_file(dir: &Path) -> NifResult<std::path::PathBuf> {
+    let to_err = |e: std::io::Error| Error::Term(Box::new(e.to_string()));
+
+    let mut latest: Option<(u64, std::path::PathBuf)> = None;
+    for entry in std::fs::read_dir(dir).map_err(to_err)? {
+        let entry = entry.map_err(to_err)?;
+        let name = entry.file_name();
+        let Some(number) = name
+            .to_str()
+            .and_then(|n| n.strip_prefix("OPTIONS-"))
+            .and_then(|n| n.parse::<u64>().ok())
+        else {
+            continue;
+        };
+        if latest.as_ref().is_none_or(|(best, _)| number > *best) {
+            latest = Some((number, entry.path()));
+        }
+    }
+    latest
+        .map(|(_, path)| path)
+        .ok_or_else(|| Error::Term(Box::new("No OPTIONS file found")))
+}
+
+// Collects the `key=value` lines of the `[CFOptions "<cf>"]` section, and of the
+// `[TableOptions/<Factory> "<cf>"]` section with keys prefixed by `table_options.`.
+// Returns `None` if the file has no section for `cf_name`.
+fn parse_cf_options(ini: &str, cf_name: &str) -> Option<BTreeMap<String, String>> {
+    let cf_section = format!("CFOptions \"{}\"", cf_name);
+    let table_suffix = format!(" \"{}\"", cf_name);
+
+    let mut options = BTreeMap::new();
+    let mut found = false;
+    let mut prefix = None;
+    for line in ini.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            prefix = if section == cf_section {
+                found = true;
+                Some("")
+            } else if section.starts_with("TableOptions/") && section.ends_with(&table_suffix) {
+                Some("table_options.")
+            } else {
+                None
+            };
+            continue;
+        }
+        if let (Some(prefix), Some((key, value))) = (prefix, line.split_once('=')) {
+            options.insert(
+                format!("{}{}", prefix, key.trim()),
+                value.trim().to_string(),
+            );
+        }
+    }
+    found.then_some(options)
+}
+
+/// Returns the options a column family is actually running with, as RocksDB
+/// recorded them in the latest OPTIONS file of the database.
+///
+/// The rocksdb crate cannot read options back from an open handle, so this parses
+/// what RocksDB persisted after sanitizing them: values it rejected or could not
+/// honour in this build (e.g. an unavailable compression) show up as the value it
+/// fell back to, not the value requested.
+///
+/// Returns `{:ok, %{name => value}}` with RocksDB's option names and string values,
+/// e.g. `"compression" => "kSnappyCompression"`, `"write_buffer_size" => "67108864"`,
+/// `"comparator" => "leveldb.BytewiseComparator"`. Block-based table options are
+/// included with a `table_options.` prefix, e.g. `"table_options.block_size"`.
+#[rustler::nif(name = "cf_options")]
+fn cf_options<'a>(env: Env<'a>, cf_name: String) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    // Not checked with `cf_handle`: handles only exist for CFs named at open, and
+    // the file lists every CF of the database, `"default"` included.
+    let path = latest_options_file(db.path())?;
+    let ini = std::fs::read_to_string(path).map_err(|e| Error::Term(Box::new(e.to_string())))?;
+    let options = parse_cf_options(&ini, &cf_name)
+        .ok_or_else(|| Error::Term(Box::new(atoms::cf_not_found())))?;
+
+    let pairs: Vec<(Term, Term)> = options
+        .iter()
+        .map(|(key, value)| (key.encode(env), value.encode(env)))
+        .collect();
+    Ok((atoms::ok(), Term::map_from_pairs(env, &pairs)?).encode(env))
+}
+
+// Returns the value of `name` in the `[DBOptions]` section.
+fn parse_db_option(ini: &str, name: &str) -> Option<String> {
+    let mut in_db_options = false;
+    for line in ini.lines().map(str::trim) {
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            in_db_options = section == "DBOptions";
+        } else if let (true, Some((key, value))) = (in_db_options, line.split_once('=')) {
+            if key.trim() == name {
+                return Some(value.trim().to_string());
+            }
+        }
+    }
+    None
+}
+
+/// Returns `{:ok, bytes}`, the compaction readahead size the database runs with,
+/// as recorded in its latest OPTIONS file (see `cf_options/1`).
+#[rustler::nif(name = "compaction_readahead")]
+fn compaction_readahead<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let path = latest_options_file(db.path())?;
+    let ini = std::fs::read_to_string(path).map_err(|e| Error::Term(Box::new(e.to_string())))?;
+    let bytes: u64 = parse_db_option(&ini, "compaction_readahead_size")
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| Error::Term(Box::new("compaction_readahead_size not found")))?;
+    Ok((atoms::ok(), bytes).encode(env))
+}
+
+/// Would change the compaction readahead size of the open database.
+///
+/// RocksDB can change it at runtime, but only through `SetDBOptions`; the rocksdb
+/// crate binds `SetOptions` alone, which takes column family options and rejects
+/// DB-wide ones like this. Always returns `{:error, :not_supported}`: pass
+/// `{:compaction_readahead_size, bytes}` to `init_with_opts/2` instead.
+#[rustler::nif(name = "set_compaction_readahead")]
+fn set_compaction_readahead(_bytes: u64) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    db_guard.as_ref().ok_or(Error::Atom("db_not_initialized"))?;
+    Err(Error::Term(Box::new(atoms::not_supported())))
+}
+
+// RocksDB's name of a compression type in the OPTIONS file, as an atom.
+fn compression_atom(name: &str) -> atom::Atom {
+    match name {
+        "kNoCompression" => atoms::none(),
+        "kSnappyCompression" => atoms::snappy(),
+        "kZlibCompression" => atoms::zlib(),
+        "kBZip2Compression" => atoms::bz2(),
+        "kLZ4Compression" => atoms::lz4(),
+        "kLZ4HCCompression" => atoms::lz4hc(),
+        "kZSTD" => atoms::zstd(),
+        _ => atoms::unknown(),
+    }
 }
 
-/// Moves the iterator to the next position.
+/// Reports the compression `cf_name` runs with, from its latest OPTIONS file
+/// (see `cf_options/1`), to confirm which type each level ended up with.
 ///
-/// This is a specialized and slightly more efficient version of `iterator_move(iter, :next)`.
+/// Returns `{:ok, %{compression: type, bottommost_compression: type | nil,
+/// levels: [type]}}`. `bottommost_compression` is `nil` when unset, i.e. the same
+/// as `compression`. `levels` holds the type of each level from L0 down: the same
+/// type everywhere but the last level, which gets `bottommost_compression`.
+/// RocksDB actually applies it to the lowest level that holds data, which is the
+/// last one once the database has grown. Types are as for `init_with_opts/2`, or
+/// `:unknown` for one this module does not name.
+#[rustler::nif(name = "cf_compression")]
+fn cf_compression<'a>(env: Env<'a>, cf_name: String) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let path = latest_options_file(db.path())?;
+    let ini = std::fs::read_to_string(path).map_err(|e| Error::Term(Box::new(e.to_string())))?;
+    let options = parse_cf_options(&ini, &cf_name)
+        .ok_or_else(|| Error::Term(Box::new(atoms::cf_not_found())))?;
+    let option = |name: &str| options.get(name).map(String::as_str).unwrap_or("");
+
+    let compression = compression_atom(option("compression"));
+    let bottommost = match option("bottommost_compression") {
+        "" | "kDisableCompressionOption" => None,
+        name => Some(compression_atom(name)),
+    };
+    let num_levels: usize = option("num_levels").parse().unwrap_or(7);
+    // An explicit per-level list overrides both settings.
+    let levels: Vec<atom::Atom> = match option("compression_per_level") {
+        "" => (0..num_levels)
+            .map(|level| match bottommost {
+                Some(bottommost) if level + 1 == num_levels => bottommost,
+                _ => compression,
+            })
+            .collect(),
+        per_level => per_level.split(':').map(compression_atom).collect(),
+    };
+
+    let report = Term::map_from_pairs(
+        env,
+        &[
+            (atoms::compression().encode(env), compression.encode(env)),
+            (
+                atoms::bottommost_compression().encode(env),
+                bottommost.encode(env),
+            ),
+            (atoms::levels().encode(env), levels.encode(env)),
+        ],
+    )?;
+    Ok((atoms::ok(), report).encode(env))
+}
+
+// ------------------------ Memtables ------------------------
+
+// The rocksdb crate does not bind `SwitchMemtable`, so this uses the first half of
+// a flush: a non-waiting flush seals the active memtable synchronously, then hands
+// it to a background flush and returns.
+fn switch_memtable_for(db: &DB, cf: Option<&ColumnFamily>) -> NifResult<bool> {
+    flush_with(db, cf, false)
+}
+
+/// Seals the active memtable of the default column family, making it immutable,
+/// and starts a new one. Intended as a test hook for controlling memtable rotation.
 ///
-/// Returns `{:ok, {key, value}}` if the iterator is valid after moving,
-/// or `:finished` if the iterator has moved past the last element.
-#[rustler::nif(name = "iterator_next")]
-fn iterator_next<'a>(env: Env<'a>, iter_res: ResourceArc<IteratorResource>) -> NifResult<Term<'a>> {
-    let iter = &mut *iter_res.iter.lock().unwrap();
+/// This is emulated with a non-waiting flush, so the sealed memtable is also
+/// written to an SST shortly after, in the background; it is not kept in memory
+/// until the next natural flush. An empty memtable is left as is.
+#[rustler::nif(name = "switch_memtable")]
+fn switch_memtable() -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    switch_memtable_for(db, None)
+}
+
+/// Same as `switch_memtable/0`, scoped to a named column family.
+#[rustler::nif(name = "switch_memtable_cf")]
+fn switch_memtable_cf(cf_name: String) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    switch_memtable_for(db, Some(cf))
+}
+
+// ------------------------ Compaction ------------------------
+
+/// Compacts every column family over its whole key range, down to the bottommost
+/// level, and blocks until done. Returns `true`.
+///
+/// This can take minutes, so it runs on a dirty scheduler and releases the
+/// database guard while compacting: other NIFs, reads and writes keep working, and
+/// `compaction_progress/0` can be polled from another process meanwhile. Like an
+/// iterator, it keeps the database open until it returns even if an init replaces
+/// it, and creating or dropping a column family waits for it.
+#[rustler::nif(name = "compact_all", schedule = "DirtyIo")]
+fn compact_all() -> NifResult<bool> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf_names = DB::list_cf(&Options::default(), db.path()).map_err(to_nif_err)?;
 
-    // Call next() and handle its rich return type directly.
-    match iter.next() {
-        // Case 1: Successfully got the next key-value pair.
-        Some(Ok((key, value))) => {
-            // The key and value are Box<[u8]>, which can be encoded directly.
-            let mut key_binary = rustler::OwnedBinary::new(key.len()).unwrap();
-            key_binary.as_mut_slice().copy_from_slice(&key);
+    if MANUAL_COMPACTION_RUNNING.swap(true, atomic::Ordering::SeqCst) {
+        return Err(Error::Term(Box::new("Compaction already running")));
+    }
+    // `compact_range` reports no errors; background errors surface through
+    // `background_error/0`.
+    for cf in cf_names.iter().filter_map(|name| db.cf_handle(name)) {
+        db.compact_range_cf(cf, None::<&[u8]>, None::<&[u8]>);
+    }
+    MANUAL_COMPACTION_RUNNING.store(false, atomic::Ordering::SeqCst);
+
+    Ok(true)
+}
+
+/// Rewrites the whole of `cf_name`, the bottommost level included, and blocks
+/// until done. Returns `true`.
+///
+/// A plain compaction leaves files already at the bottommost level alone, so
+/// tombstones and the versions they shadow can pile up there and slow scans down.
+/// This forces those files through compaction too, dropping every tombstone no
+/// snapshot still needs. It rewrites all of the CF's data; like `compact_all/0` it
+/// runs on a dirty scheduler, releases the database guard while compacting, keeps
+/// the database open until it returns and shows in `compaction_progress/0`.
+#[rustler::nif(name = "scrub_cf", schedule = "DirtyIo")]
+fn scrub_cf(cf_name: String) -> NifResult<bool> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    if MANUAL_COMPACTION_RUNNING.swap(true, atomic::Ordering::SeqCst) {
+        return Err(Error::Term(Box::new("Compaction already running")));
+    }
+    let mut compact_opts = CompactOptions::default();
+    compact_opts.set_bottommost_level_compaction(BottommostLevelCompaction::Force);
+    db.compact_range_cf_opt(cf, None::<&[u8]>, None::<&[u8]>, &compact_opts);
+    MANUAL_COMPACTION_RUNNING.store(false, atomic::Ordering::SeqCst);
 
-            let mut value_binary = rustler::OwnedBinary::new(value.len()).unwrap();
-            value_binary.as_mut_slice().copy_from_slice(&value);
+    Ok(true)
+}
 
-            let ok_atom = atoms::ok().encode(env);
-            let key_term = key_binary.release(env).encode(env);
-            let value_term = value_binary.release(env).encode(env);
+/// Compacts part of `[start, end]` in `cf_name` (`nil` for an open end), at most
+/// about `max_bytes` of it, so a large compaction can be spread over several
+/// maintenance windows.
+///
+/// The keys from `start` are scanned until their keys and values add up to
+/// `max_bytes`, and only that prefix of the range is compacted. Returns
+/// `{:ok, :done}` when the rest of the range was covered, or `{:ok, :more, cursor}`
+/// where `cursor` is the first key left out: pass it as `start` in the next call
+/// to carry on without redoing work. The budget counts uncompressed bytes, so the
+/// I/O of a call is usually less (compression) or more (overlapping files in the
+/// level below) than `max_bytes`. At least one key is compacted per call.
+///
+/// Like `compact_all/0` it runs on a dirty scheduler, releases the database guard
+/// while compacting and keeps the database open until it returns. The range is in
+/// the database's key order, so with `{:comparator, :reverse_bytewise}` `start` is
+/// the larger key.
+#[rustler::nif(name = "compact_range_budgeted", schedule = "DirtyIo")]
+fn compact_range_budgeted<'a>(
+    env: Env<'a>,
+    cf_name: String,
+    start: Option<Binary>,
+    end: Option<Binary>,
+    max_bytes: u64,
+) -> NifResult<Term<'a>> {
+    let (shared, key_order) = {
+        let db_guard = DB_INSTANCE.lock().unwrap();
+        let shared = db_guard.clone().ok_or(Error::Atom("db_not_initialized"))?;
+        // Taken with the handle, as an init may replace both right after.
+        (shared, key_order())
+    };
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+    let start = start.map(|s| s.as_slice().to_vec());
+    let end = end.map(|e| e.as_slice().to_vec());
 
-            let data_tuple = rustler::types::tuple::make_tuple(env, &[key_term, value_term]);
-            Ok(rustler::types::tuple::make_tuple(
-                env,
-                &[ok_atom, data_tuple],
-            ))
+    // Find where the budget runs out: the last key to compact and the next one.
+    let mut chunk_end: Option<Vec<u8>> = None;
+    let mut cursor: Option<Vec<u8>> = None;
+    {
+        let mut read_opts = ReadOptions::default();
+        read_opts.fill_cache(false);
+        let mut iter = db.raw_iterator_cf_opt(cf, read_opts);
+        match &start {
+            Some(start) => iter.seek(start),
+            None => iter.seek_to_first(),
+        }
+        let mut bytes: u64 = 0;
+        while let (Some(key), Some(value)) = (iter.key(), iter.value()) {
+            if end
+                .as_deref()
+                .is_some_and(|end| key_order(key, end) == Ordering::Greater)
+            {
+                break;
+            }
+            if chunk_end.is_some() && bytes >= max_bytes {
+                cursor = Some(key.to_vec());
+                break;
+            }
+            bytes += (key.len() + value.len()) as u64;
+            chunk_end = Some(key.to_vec());
+            iter.next();
         }
-        // Case 2: An error occurred during iteration.
-        Some(Err(e)) => Err(Error::Term(Box::new(format!(
-            "RocksDB iteration error: {}",
-            e
-        )))),
-        // Case 3: The iterator reached the end.
-        None => Ok(atoms::finished().encode(env)),
+        iter.status().map_err(to_nif_err)?;
+    }
+
+    if MANUAL_COMPACTION_RUNNING.swap(true, atomic::Ordering::SeqCst) {
+        return Err(Error::Term(Box::new("Compaction already running")));
     }
+    // On the last chunk compact up to the requested end, covering keys written
+    // behind the scan in the meantime.
+    let compact_end = if cursor.is_some() { &chunk_end } else { &end };
+    db.compact_range_cf(cf, start.as_deref(), compact_end.as_deref());
+    MANUAL_COMPACTION_RUNNING.store(false, atomic::Ordering::SeqCst);
+
+    Ok(match cursor {
+        Some(cursor) => (atoms::ok(), atoms::more(), vec_to_binary(env, cursor)?).encode(env),
+        None => (atoms::ok(), atoms::done()).encode(env),
+    })
 }
 
-#[rustler::nif(name = "flush_3")]
-fn flush_3(_opts: Term, _wait: bool) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+/// Reports compaction activity, for showing progress of `compact_all/0` or of
+/// background compactions.
+///
+/// Returns `{:ok, %{running_compactions: n, compaction_pending: bool,
+/// pending_compaction_bytes: bytes, manual_compaction_running: bool}}`:
+/// * `running_compactions` - compactions executing right now, manual ones included.
+/// * `compaction_pending` - whether RocksDB has compactions queued.
+/// * `pending_compaction_bytes` - RocksDB's estimate of the bytes compaction still
+///   has to rewrite to bring every level under its target size; this shrinking
+///   towards 0 is the best available measure of progress.
+/// * `manual_compaction_running` - whether `compact_all/0` or `scrub_cf/1` has yet
+///   to return.
+///
+/// The properties are those of the default column family. The rocksdb crate does
+/// not support event listeners, so progress can only be polled.
+#[rustler::nif(name = "compaction_progress")]
+fn compaction_progress<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let int_property = |name: &str| -> NifResult<u64> {
+        Ok(db
+            .property_int_value(name)
+            .map_err(to_nif_err)?
+            .unwrap_or(0))
+    };
+
+    let progress = Term::map_from_pairs(
+        env,
+        &[
+            (
+                atoms::running_compactions().encode(env),
+                int_property("rocksdb.num-running-compactions")?.encode(env),
+            ),
+            (
+                atoms::compaction_pending().encode(env),
+                (int_property("rocksdb.compaction-pending")? != 0).encode(env),
+            ),
+            (
+                atoms::pending_compaction_bytes().encode(env),
+                int_property("rocksdb.estimate-pending-compaction-bytes")?.encode(env),
+            ),
+            (
+                atoms::manual_compaction_running().encode(env),
+                MANUAL_COMPACTION_RUNNING
+                    .load(atomic::Ordering::SeqCst)
+                    .encode(env),
+            ),
+        ],
+    )?;
+    Ok((atoms::ok(), progress).encode(env))
 }
 
-#[rustler::nif(name = "compact_range_5")]
-fn compact_range_5(
-    _start: Term,
-    _end: Term,
-    _opts: Term,
-    _cf: String,
-    _output_level: i32,
+/// Returns RocksDB's estimate of the bytes compaction still has to rewrite in
+/// `cf_name` (`"default"` for the default column family), as an integer.
+///
+/// This is `rocksdb.estimate-pending-compaction-bytes`: 0 once every level is
+/// within its target size, and the measure write stalls are triggered by
+/// (`soft_pending_compaction_bytes_limit`, 64 GiB by default).
+#[rustler::nif(name = "compaction_pending_bytes")]
+fn compaction_pending_bytes(cf_name: String) -> NifResult<u64> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    Ok(db
+        .property_int_value_cf(cf, "rocksdb.estimate-pending-compaction-bytes")
+        .map_err(to_nif_err)?
+        .unwrap_or(0))
+}
+
+/// Starts a flush of the memtables of `cf_name` (`"default"` for the default
+/// column family) and returns `true` without waiting for it to finish, so a write
+/// loop can flush on its own schedule without stalling.
+///
+/// The rocksdb crate has no event listeners to report completion; poll
+/// `flush_pending/1` until it returns `false` instead.
+#[rustler::nif(name = "flush_async")]
+fn flush_async(cf_name: String) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    switch_memtable_for(db, Some(cf))
+}
+
+/// Returns whether `cf_name` still has sealed memtables waiting for or being
+/// written by a flush, e.g. one started by `flush_async/1`.
+#[rustler::nif(name = "flush_pending")]
+fn flush_pending(cf_name: String) -> NifResult<bool> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    // Sealed memtables stay counted until their flush has been installed.
+    let immutable = db
+        .property_int_value_cf(cf, "rocksdb.num-immutable-mem-table")
+        .map_err(to_nif_err)?
+        .unwrap_or(0);
+    Ok(immutable != 0)
+}
+
+// ------------------------ SST files ------------------------
+
+/// Returns the properties of one live SST file of the open database.
+///
+/// `file` may be a bare file name (`"000123.sst"`) or a path ending in one.
+///
+/// Returns `{:ok, %{column_family: name, level: n, size: bytes, num_entries: n,
+/// num_deletions: n, smallest_key: key, largest_key: key}}`, or
+/// `{:error, :not_found}` if no live SST has that name.
+///
+/// The rocksdb crate does not bind `SstFileReader`, so a standalone SST cannot be
+/// opened or iterated on its own; this reads the metadata the open DB keeps for
+/// its files. Files no longer referenced by the DB cannot be inspected.
+#[rustler::nif(name = "sst_dump")]
+fn sst_dump<'a>(env: Env<'a>, file: String) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let wanted = Path::new(&file).file_name();
+    let live_files = db.live_files().map_err(to_nif_err)?;
+    let Some(sst) = live_files
+        .iter()
+        .find(|lf| Path::new(&lf.name).file_name() == wanted)
+    else {
+        return Err(Error::Term(Box::new(atoms::not_found())));
+    };
+
+    let optional_key = |key: &Option<Vec<u8>>| -> NifResult<Term<'a>> {
+        match key {
+            Some(key) => vec_to_binary(env, key.clone()),
+            None => Ok(atom::nil().encode(env)),
+        }
+    };
+    let properties = Term::map_from_pairs(
+        env,
+        &[
+            (
+                atoms::column_family().encode(env),
+                sst.column_family_name.encode(env),
+            ),
+            (atoms::level().encode(env), sst.level.encode(env)),
+            (atoms::size().encode(env), sst.size.encode(env)),
+            (
+                atoms::num_entries().encode(env),
+                sst.num_entries.encode(env),
+            ),
+            (
+                atoms::num_deletions().encode(env),
+                sst.num_deletions.encode(env),
+            ),
+            (
+                atoms::smallest_key().encode(env),
+                optional_key(&sst.start_key)?,
+            ),
+            (
+                atoms::largest_key().encode(env),
+                optional_key(&sst.end_key)?,
+            ),
+        ],
+    )?;
+    Ok((atoms::ok(), properties).encode(env))
+}
+
+// A file time as Unix seconds, or `:unknown` if the platform or filesystem does
+// not record it.
+fn file_time_term<'a>(env: Env<'a>, time: std::io::Result<std::time::SystemTime>) -> Term<'a> {
+    match time
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+    {
+        Some(since_epoch) => since_epoch.as_secs().encode(env),
+        None => atoms::unknown().encode(env),
+    }
+}
+
+/// Lists the live SST files of the open database with their age, to tell data
+/// that has sat in a level for long from data compaction rewrote recently.
+///
+/// Returns `{:ok, [%{name: file, column_family: name, level: n,
+/// creation_time: secs | :unknown, modification_time: secs | :unknown}]}` with times
+/// in Unix seconds.
+///
+/// The rocksdb crate does not expose table properties, so the `creation_time`
+/// RocksDB stores inside each SST cannot be read; both times come from the
+/// filesystem instead. `creation_time` is the file's birth time, which many Linux
+/// filesystems do not report. SSTs are never modified once written, so
+/// `modification_time` is when the flush or compaction that produced the file
+/// finished, and is the more reliably available of the two.
+#[rustler::nif(name = "sst_file_times")]
+fn sst_file_times<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let mut files = Vec::new();
+    for lf in db.live_files().map_err(to_nif_err)? {
+        // Live file names are relative to the DB directory, with a leading `/`.
+        let path = db.path().join(lf.name.trim_start_matches('/'));
+        let (created, modified) = match std::fs::metadata(&path) {
+            Ok(meta) => (
+                file_time_term(env, meta.created()),
+                file_time_term(env, meta.modified()),
+            ),
+            Err(_) => (atoms::unknown().encode(env), atoms::unknown().encode(env)),
+        };
+        files.push(Term::map_from_pairs(
+            env,
+            &[
+                (atoms::name().encode(env), lf.name.encode(env)),
+                (
+                    atoms::column_family().encode(env),
+                    lf.column_family_name.encode(env),
+                ),
+                (atoms::level().encode(env), lf.level.encode(env)),
+                (atoms::creation_time().encode(env), created),
+                (atoms::modification_time().encode(env), modified),
+            ],
+        )?);
+    }
+    Ok((atoms::ok(), files).encode(env))
+}
+
+/// Summarizes the current version of the database, to cheaply tell whether two
+/// replicas are at the same state without comparing their data.
+///
+/// Returns `{:ok, %{manifest: file, latest_sequence_number: n, num_live_files: n}}`:
+/// * `manifest` - the MANIFEST file named by `CURRENT`, e.g. `"MANIFEST-000005"`.
+///   Differs between nodes even when their data matches; mostly useful to know
+///   which file to inspect.
+/// * `latest_sequence_number` - the sequence number of the last write. A node with
+///   a lower one than its peer, after applying the same writes, is behind.
+/// * `num_live_files` - the number of SST files in the current version.
+#[rustler::nif(name = "manifest_summary")]
+fn manifest_summary<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let current = std::fs::read_to_string(db.path().join("CURRENT"))
+        .map_err(|e| Error::Term(Box::new(e.to_string())))?;
+    let num_live_files = db.live_files().map_err(to_nif_err)?.len();
+
+    let summary = Term::map_from_pairs(
+        env,
+        &[
+            (atoms::manifest().encode(env), current.trim().encode(env)),
+            (
+                atoms::latest_sequence_number().encode(env),
+                db.latest_sequence_number().encode(env),
+            ),
+            (
+                atoms::num_live_files().encode(env),
+                num_live_files.encode(env),
+            ),
+        ],
+    )?;
+    Ok((atoms::ok(), summary).encode(env))
+}
+
+// ------------------------ LSM shape ------------------------
+
+// Per-level compaction scores are not part of the column family metadata, so they
+// are taken from the `Score` column of the level table in `rocksdb.cfstats`:
+//
+//   Level    Files   Size     Score Read(GB) ...
+//     L0      2/0    1.49 KB   0.5      0.0 ...
+//
+// Rows other than `L<n>` (the header, `Sum`, `Int`, priority rows) are skipped.
+fn parse_level_scores(cfstats: &str) -> BTreeMap<i32, f64> {
+    let mut scores = BTreeMap::new();
+    for line in cfstats.lines() {
+        let mut tokens = line.split_whitespace();
+        let Some(level) = tokens
+            .next()
+            .and_then(|t| t.strip_prefix('L'))
+            .and_then(|t| t.parse::<i32>().ok())
+        else {
+            continue;
+        };
+        // Skip `Files` and the two-token `Size` (value and unit).
+        if let Some(score) = tokens.nth(3).and_then(|t| t.parse::<f64>().ok()) {
+            scores.entry(level).or_insert(score);
+        }
+    }
+    scores
+}
+
+fn levels_summary_for<'a>(
+    env: Env<'a>,
+    db: &DB,
+    cf: Option<&ColumnFamily>,
+    cf_name: &str,
+) -> NifResult<Term<'a>> {
+    let string_property = |name: &str| -> NifResult<Option<String>> {
+        match cf {
+            Some(cf) => db.property_value_cf(cf, name),
+            None => db.property_value(name),
+        }
+        .map_err(to_nif_err)
+    };
+
+    let mut level_sizes: BTreeMap<i32, u64> = BTreeMap::new();
+    for lf in db.live_files().map_err(to_nif_err)? {
+        if lf.column_family_name == cf_name {
+            *level_sizes.entry(lf.level).or_insert(0) += lf.size as u64;
+        }
+    }
+    let scores = string_property("rocksdb.cfstats")?
+        .map(|stats| parse_level_scores(&stats))
+        .unwrap_or_default();
+
+    // RocksDB answers `num-files-at-level<n>` for every configured level and
+    // fails past the last one, which gives the level count without the options.
+    let mut levels = Vec::new();
+    for level in 0.. {
+        let Some(num_files) = string_property(&format!("rocksdb.num-files-at-level{}", level))?
+        else {
+            break;
+        };
+        let num_files: u64 = num_files.trim().parse().unwrap_or(0);
+        let score = match scores.get(&level) {
+            Some(score) => score.encode(env),
+            None => atom::nil().encode(env),
+        };
+        levels.push(Term::map_from_pairs(
+            env,
+            &[
+                (atoms::level().encode(env), level.encode(env)),
+                (atoms::num_files().encode(env), num_files.encode(env)),
+                (
+                    atoms::size_bytes().encode(env),
+                    level_sizes.get(&level).copied().unwrap_or(0).encode(env),
+                ),
+                (atoms::score().encode(env), score),
+            ],
+        )?);
+    }
+    Ok((atoms::ok(), levels).encode(env))
+}
+
+/// Returns the LSM shape of the default column family, one map per level.
+///
+/// Returns `{:ok, [%{level: n, num_files: n, size_bytes: bytes, score: float}]}`
+/// for every configured level, empty ones included. File counts and sizes come from
+/// the column family metadata. `score` is the compaction score RocksDB last computed
+/// for the level (a level needs compacting once it reaches 1.0) and is `nil` for
+/// levels `rocksdb.cfstats` does not report on, which are typically empty ones.
+#[rustler::nif(name = "levels_summary")]
+fn levels_summary<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    levels_summary_for(env, db, None, "default")
+}
+
+/// Same as `levels_summary/0`, scoped to a named column family.
+#[rustler::nif(name = "levels_summary_cf")]
+fn levels_summary_cf<'a>(env: Env<'a>, cf_name: String) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+    let cf = cf_or_err(db, &cf_name)?;
+
+    levels_summary_for(env, db, Some(cf), &cf_name)
+}
+
+// Flushes the memtables and then compacts everything down to the last level,
+// bottommost files included, waiting for both.
+fn full_lsm_cycle(db: &DB, cf: Option<&ColumnFamily>) -> NifResult<()> {
+    let mut compact_opts = CompactOptions::default();
+    compact_opts.set_bottommost_level_compaction(BottommostLevelCompaction::Force);
+    match cf {
+        Some(cf) => {
+            db.flush_cf(cf).map_err(to_nif_err)?;
+            db.compact_range_cf_opt(cf, None::<&[u8]>, None::<&[u8]>, &compact_opts);
+        }
+        None => {
+            db.flush().map_err(to_nif_err)?;
+            db.compact_range_opt(None::<&[u8]>, None::<&[u8]>, &compact_opts);
+        }
+    }
+    Ok(())
+}
+
+/// Testing hook: pushes all data of the default column family through the LSM
+/// tree (memtable flush, then a compaction of every level into the last one) and
+/// returns `levels_summary/0` afterwards, so a test can assert where the data
+/// ended up.
+///
+/// Runs synchronously on a dirty scheduler and holds the database for the whole
+/// cycle; only meant for tests and small databases.
+#[rustler::nif(name = "force_full_lsm_cycle", schedule = "DirtyIo")]
+fn force_full_lsm_cycle<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+
+    full_lsm_cycle(db, None)?;
+    levels_summary_for(env, db, None, "default")
+}
+
+/// Same as `force_full_lsm_cycle/0` for a named column family.
+#[rustler::nif(name = "force_full_lsm_cycle_cf", schedule = "DirtyIo")]
+fn force_full_lsm_cycle_cf<'a>(env: Env<'a>, cf_name: String) -> NifResult<Term<'a>> {
+    let shared = current_db()?;
+    let db = &*shared.read();
+    let cf = cf_or_err(db, &cf_name)?;
+
+    full_lsm_cycle(db, Some(cf))?;
+    levels_summary_for(env, db, Some(cf), &cf_name)
+}
+
+// ------------------------ Obsolete files ------------------------
+
+// Sizes of the regular files directly in `dir`. RocksDB keeps its SSTs, WALs,
+// MANIFESTs and OPTIONS files flat in the DB directory.
+fn dir_file_sizes(dir: &Path) -> NifResult<BTreeMap<std::ffi::OsString, u64>> {
+    let to_err = |e: std::io::Error| Error::Term(Box::new(e.to_string()));
+
+    let mut sizes = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).map_err(to_err)? {
+        let entry = entry.map_err(to_err)?;
+        // Files can vanish under us while RocksDB is working; skip them.
+        if let Ok(meta) = entry.metadata() {
+            if meta.is_file() {
+                sizes.insert(entry.file_name(), meta.len());
+            }
+        }
+    }
+    Ok(sizes)
+}
+
+/// Forces RocksDB to delete the files it considers obsolete: SSTs replaced by
+/// compaction, WALs whose data has been flushed, old MANIFESTs and info logs.
+///
+/// RocksDB normally only scans for these every `delete_obsolete_files_period_micros`
+/// (6 hours by default). The rocksdb crate has no direct binding for the purge, so
+/// this toggles file deletions off and on again: re-enabling them runs a full scan
+/// and purge. Safe to call while the database is serving traffic; files still
+/// referenced by live iterators, snapshots or an ongoing backup are kept.
+///
+/// Returns `{:ok, %{files_deleted: n, bytes_reclaimed: bytes}}`, counting the files
+/// in the DB directory that disappeared during the call. Files removed concurrently
+/// by a background compaction are counted as well.
+#[rustler::nif(name = "delete_obsolete_files", schedule = "DirtyIo")]
+fn delete_obsolete_files<'a>(env: Env<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let before = dir_file_sizes(db.path())?;
+    db.disable_file_deletions().map_err(to_nif_err)?;
+    db.enable_file_deletions().map_err(to_nif_err)?;
+    let after = dir_file_sizes(db.path())?;
+
+    let (files_deleted, bytes_reclaimed) = before
+        .iter()
+        .filter(|(name, _)| !after.contains_key(*name))
+        .fold((0u64, 0u64), |(files, bytes), (_, size)| {
+            (files + 1, bytes + size)
+        });
+
+    let report = Term::map_from_pairs(
+        env,
+        &[
+            (
+                atoms::files_deleted().encode(env),
+                files_deleted.encode(env),
+            ),
+            (
+                atoms::bytes_reclaimed().encode(env),
+                bytes_reclaimed.encode(env),
+            ),
+        ],
+    )?;
+    Ok((atoms::ok(), report).encode(env))
+}
+
+// ------------------------ WAL archive ------------------------
+
+// The sequence number of the first write in a WAL file, read from the header of
+// its first record: checksum (4 bytes), length (2), type (1), plus a log number
+// (4) for the recyclable types, then the write batch starting with its sequence
+// number (fixed64, little-endian). `None` if the file does not start that way,
+// e.g. it is empty.
+fn wal_start_sequence(path: &Path) -> Option<u64> {
+    use std::io::Read;
+
+    let mut header = [0u8; 19];
+    std::fs::File::open(path)
+        .ok()?
+        .read_exact(&mut header)
+        .ok()?;
+    let batch = match header[6] {
+        // Full and first fragments; the other types never start a file.
+        1 | 2 => 7,
+        5 | 6 => 11,
+        _ => return None,
+    };
+    Some(u64::from_le_bytes(
+        header[batch..batch + 8].try_into().ok()?,
+    ))
+}
+
+// The `<number>.log` files of `dir` in log number order, with their size and
+// modification time.
+fn wal_files(dir: &Path) -> Vec<(u64, PathBuf, std::fs::Metadata)> {
+    let mut files: Vec<_> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| {
+            let number = entry
+                .file_name()
+                .to_str()?
+                .strip_suffix(".log")?
+                .parse::<u64>()
+                .ok()?;
+            Some((number, entry.path(), entry.metadata().ok()?))
+        })
+        .collect();
+    files.sort_by_key(|(number, _, _)| *number);
+    files
+}
+
+/// Records that `replica` still needs every write from sequence number `sequence`
+/// on, i.e. the next `get_updates_since` it will issue. `purge_wal_archive/1`
+/// never deletes a file holding such writes for any registered replica. Calling
+/// it again moves the replica's mark; registrations last until the next init.
+#[rustler::nif(name = "wal_replica_ack")]
+fn wal_replica_ack(replica: String, sequence: u64) -> atom::Atom {
+    WAL_REPLICA_ACKS.lock().unwrap().insert(replica, sequence);
+    atoms::ok()
+}
+
+/// Unregisters a replica from `wal_replica_ack/2`, e.g. one that was decommissioned
+/// and would otherwise pin the archive forever.
+#[rustler::nif(name = "wal_replica_forget")]
+fn wal_replica_forget(replica: String) -> atom::Atom {
+    WAL_REPLICA_ACKS.lock().unwrap().remove(&replica);
+    atoms::ok()
+}
+
+/// Deletes archived WAL files (those RocksDB moved to `archive/` after flushing
+/// them, kept for `get_updates_since` when the database was opened with a WAL TTL
+/// or size limit) according to `policy`:
+/// * `{:keep_seconds, n}` - delete files last written more than `n` seconds ago.
+/// * `{:keep_sequence, seq}` - delete files holding only writes below `seq`.
+///
+/// Either way a file is only deleted if all of its writes are below the lowest
+/// sequence registered with `wal_replica_ack/2`. A file's last sequence is only
+/// known from the start of the next one, so the newest archived file is kept
+/// unless a live WAL follows it, and files whose first record cannot be read are
+/// always kept.
+///
+/// Returns `{:ok, %{files_deleted: n, bytes_reclaimed: bytes, archive_bytes: bytes}}`,
+/// with `archive_bytes` the size of what is left in the archive.
+#[rustler::nif(name = "purge_wal_archive", schedule = "DirtyIo")]
+fn purge_wal_archive<'a>(env: Env<'a>, policy: Term<'a>) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let (kind, limit): (atom::Atom, u64) = policy.decode()?;
+    let (min_age, mut max_sequence) = if kind == atoms::keep_seconds() {
+        (Some(Duration::from_secs(limit)), u64::MAX)
+    } else if kind == atoms::keep_sequence() {
+        (None, limit)
+    } else {
+        return Err(Error::Term(Box::new("Unknown purge policy")));
+    };
+    if let Some(&floor) = WAL_REPLICA_ACKS.lock().unwrap().values().min() {
+        max_sequence = max_sequence.min(floor);
+    }
+
+    let archived = wal_files(&db.path().join("archive"));
+    // Archived logs all have lower numbers than the live ones.
+    let next_starts: Vec<Option<u64>> = archived
+        .iter()
+        .skip(1)
+        .chain(wal_files(db.path()).iter().take(1))
+        .map(|(_, path, _)| wal_start_sequence(path))
+        .collect();
+
+    let (mut files_deleted, mut bytes_reclaimed, mut archive_bytes) = (0u64, 0u64, 0u64);
+    for (i, (_, path, meta)) in archived.iter().enumerate() {
+        let below_floor = next_starts
+            .get(i)
+            .copied()
+            .flatten()
+            .is_some_and(|next_start| next_start <= max_sequence);
+        let old_enough = min_age.is_none_or(|min_age| {
+            meta.modified()
+                .ok()
+                .and_then(|modified| modified.elapsed().ok())
+                .is_some_and(|age| age > min_age)
+        });
+        if below_floor && old_enough && std::fs::remove_file(path).is_ok() {
+            files_deleted += 1;
+            bytes_reclaimed += meta.len();
+        } else {
+            archive_bytes += meta.len();
+        }
+    }
+
+    let report = Term::map_from_pairs(
+        env,
+        &[
+            (
+                atoms::files_deleted().encode(env),
+                files_deleted.encode(env),
+            ),
+            (
+                atoms::bytes_reclaimed().encode(env),
+                bytes_reclaimed.encode(env),
+            ),
+            (
+                atoms::archive_bytes().encode(env),
+                archive_bytes.encode(env),
+            ),
+        ],
+    )?;
+    Ok((atoms::ok(), report).encode(env))
+}
+
+// ------------------------ Checkpoints ------------------------
+
+// Files can be fully synced while the directory entry naming them is not, and a
+// power loss then loses the file. Only needed on Unix: Windows cannot open a
+// directory as a file, and NTFS journals directory updates itself.
+#[cfg(unix)]
+fn fsync_dir(dir: &Path) -> std::io::Result<()> {
+    std::fs::File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn fsync_dir(_dir: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+// Parses `[fsync_dir: bool]`, shared by the checkpoint and backup NIFs.
+fn parse_fsync_dir_option(opts: Term) -> NifResult<bool> {
+    let mut fsync = false;
+    for (key_term, value) in option_pairs(opts)? {
+        let key: atom::Atom = key_term.decode()?;
+        if key == atoms::fsync_dir() {
+            fsync = value.decode()?;
+        }
+    }
+    Ok(fsync)
+}
+
+fn create_checkpoint_at(path: &str, fsync: bool) -> NifResult<atom::Atom> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let checkpoint = Checkpoint::new(db).map_err(to_nif_err)?;
+    checkpoint.create_checkpoint(path).map_err(to_nif_err)?;
+
+    if fsync {
+        // RocksDB syncs the checkpoint directory before renaming it into place,
+        // but not the parent that now holds the renamed entry.
+        let parent = match Path::new(path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fsync_dir(parent).map_err(|e| Error::Term(Box::new(e.to_string())))?;
+    }
+    Ok(atoms::ok())
+}
+
+/// Creates a checkpoint of the open database at `path`, a directory that must
+/// not exist yet. SST files are hard-linked when `path` is on the same filesystem,
+/// so checkpoints are cheap; the result can be opened as a database on its own.
+///
+/// Returns `:ok`.
+#[rustler::nif(name = "create_checkpoint", schedule = "DirtyIo")]
+fn create_checkpoint(path: String) -> NifResult<atom::Atom> {
+    create_checkpoint_at(&path, false)
+}
+
+/// Same as `create_checkpoint/1`, with the options in `opts`:
+/// * `{:fsync_dir, true}` - fsync the parent directory of `path` afterwards, so the
+///   checkpoint's directory entry survives a power loss. A no-op on platforms whose
+///   filesystems do not need it (Windows).
+#[rustler::nif(name = "create_checkpoint_2", schedule = "DirtyIo")]
+fn create_checkpoint_2(path: String, opts: Term) -> NifResult<atom::Atom> {
+    let fsync = parse_fsync_dir_option(opts)?;
+    create_checkpoint_at(&path, fsync)
+}
+
+// ------------------------ Backups ------------------------
+
+// Every call opens the engine afresh; the engine only holds metadata, and
+// reusing the same directory is what makes subsequent backups incremental.
+fn open_backup_engine(backup_dir: &str) -> NifResult<BackupEngine> {
+    let backup_opts = BackupEngineOptions::new(backup_dir).map_err(to_nif_err)?;
+    let env = RocksEnv::new().map_err(to_nif_err)?;
+    BackupEngine::open(&backup_opts, &env).map_err(to_nif_err)
+}
+
+fn create_backup<'a>(env: Env<'a>, backup_dir: &str, fsync: bool) -> NifResult<Term<'a>> {
+    let db_guard = DB_INSTANCE.lock().unwrap();
+    let db = &*read_db(&db_guard)?;
+
+    let mut engine = open_backup_engine(backup_dir)?;
+    engine.create_new_backup(db).map_err(to_nif_err)?;
+    if fsync {
+        fsync_backup_dirs(Path::new(backup_dir))?;
+    }
+
+    let backup_id = engine
+        .get_backup_info()
+        .iter()
+        .map(|info| info.backup_id)
+        .max()
+        .ok_or_else(|| Error::Term(Box::new("Backup was not recorded")))?;
+    Ok((atoms::ok(), backup_id).encode(env))
+}
+
+// The engine syncs the files it writes but not the directories they are linked
+// into: the top level and its subdirectories (`meta`, `private`, `shared*`).
+fn fsync_backup_dirs(backup_dir: &Path) -> NifResult<()> {
+    let to_err = |e: std::io::Error| Error::Term(Box::new(e.to_string()));
+
+    for entry in std::fs::read_dir(backup_dir).map_err(to_err)? {
+        let entry = entry.map_err(to_err)?;
+        if entry.file_type().map_err(to_err)?.is_dir() {
+            fsync_dir(&entry.path()).map_err(to_err)?;
+        }
+    }
+    fsync_dir(backup_dir).map_err(to_err)
+}
+
+/// Creates a new backup of the open database in `backup_dir`.
+///
+/// Backups in the same directory share SST files, so only files created since
+/// the previous backup are copied.
+///
+/// Returns `{:ok, backup_id}`.
+#[rustler::nif(name = "backup_create", schedule = "DirtyIo")]
+fn backup_create<'a>(env: Env<'a>, backup_dir: String) -> NifResult<Term<'a>> {
+    create_backup(env, &backup_dir, false)
+}
+
+/// Same as `backup_create/1`, with the options in `opts`:
+/// * `{:fsync_dir, true}` - also fsync the backup directories once the backup is
+///   written, so the new entries survive a power loss (see `create_checkpoint_2/2`).
+#[rustler::nif(name = "backup_create_2", schedule = "DirtyIo")]
+fn backup_create_2<'a>(env: Env<'a>, backup_dir: String, opts: Term<'a>) -> NifResult<Term<'a>> {
+    let fsync = parse_fsync_dir_option(opts)?;
+    create_backup(env, &backup_dir, fsync)
+}
+
+/// Lists the backups in `backup_dir`.
+///
+/// Returns `{:ok, [%{backup_id: id, timestamp: unix_secs, size: bytes, num_files: n}]}`.
+#[rustler::nif(name = "backup_list")]
+fn backup_list<'a>(env: Env<'a>, backup_dir: String) -> NifResult<Term<'a>> {
+    let engine = open_backup_engine(&backup_dir)?;
+
+    let mut backups = Vec::new();
+    for info in engine.get_backup_info() {
+        let entry = Term::map_from_pairs(
+            env,
+            &[
+                (atoms::backup_id().encode(env), info.backup_id.encode(env)),
+                (atoms::timestamp().encode(env), info.timestamp.encode(env)),
+                (atoms::size().encode(env), info.size.encode(env)),
+                (atoms::num_files().encode(env), info.num_files.encode(env)),
+            ],
+        )?;
+        backups.push(entry);
+    }
+    Ok((atoms::ok(), backups).encode(env))
+}
+
+/// Restores backup `backup_id` from `backup_dir` into `db_dir`, writing WAL files to `wal_dir`.
+///
+/// The backup is verified first, so a corrupt backup yields `{:error, reason}`
+/// and the caller can fall back to an older one. The target database must not be open.
+#[rustler::nif(name = "backup_restore", schedule = "DirtyIo")]
+fn backup_restore(
+    backup_dir: String,
+    backup_id: u32,
+    db_dir: String,
+    wal_dir: String,
+) -> NifResult<atom::Atom> {
+    let mut engine = open_backup_engine(&backup_dir)?;
+    engine.verify_backup(backup_id).map_err(to_nif_err)?;
+
+    engine
+        .restore_from_backup(
+            Path::new(&db_dir),
+            Path::new(&wal_dir),
+            &RestoreOptions::default(),
+            backup_id,
+        )
+        .map(|_| atoms::ok())
+        .map_err(to_nif_err)
+}
+
+/// Deletes all but the `keep` most recent backups in `backup_dir`.
+#[rustler::nif(name = "backup_purge_old", schedule = "DirtyIo")]
+fn backup_purge_old(backup_dir: String, keep: usize) -> NifResult<atom::Atom> {
+    let mut engine = open_backup_engine(&backup_dir)?;
+    engine
+        .purge_old_backups(keep)
+        .map(|_| atoms::ok())
+        .map_err(to_nif_err)
+}
+
+// ------------------------ Database handles ------------------------
+//
+// The NIFs above all work on the `DB_INSTANCE` singleton and stay as they are for
+// existing single-database callers. A `DbResource` is a database of its own, so
+// one node can open several side by side (isolated test databases, a read-only
+// replica next to the primary); it is closed once it and all of its iterators
+// are garbage collected.
+
+pub struct DbResource {
+    db: Arc<SharedDb>,
+}
+
+/// Opens the database at `db_path` as a new handle, with every column family on
+/// disk, and returns `{:ok, db}`. It does not touch the database opened by
+/// `init/1`, and the path must not be open elsewhere.
+///
+/// `opts` takes the RocksDB options of `init_with_opts/2`. The ones the NIFs of
+/// the singleton act on outside of RocksDB (`:user_timestamps`,
+/// `:value_cache_entries`, `:disable_wal`, `:log_pid`) have no handle equivalent
+/// and are rejected.
+#[rustler::nif(name = "db_open", schedule = "DirtyIo")]
+fn db_open<'a>(env: Env<'a>, db_path: String, opts: Term<'a>) -> NifResult<Term<'a>> {
+    let path = Path::new(&db_path);
+    for (key_term, _) in option_pairs(opts)? {
+        let key: atom::Atom = key_term.decode()?;
+        if key == atoms::user_timestamps()
+            || key == atoms::value_cache_entries()
+            || key == atoms::disable_wal()
+            || key == atoms::log_pid()
+        {
+            return Err(Error::Term(Box::new(format!(
+                "Option not supported by db_open: {:?}",
+                key
+            ))));
+        }
+    }
+    let parsed = parse_db_options(opts)?;
+    let options = parsed.options;
+
+    // Listing fails when there is no database yet, which then starts with just "default".
+    let cf_names = DB::list_cf(&options, path).unwrap_or_else(|_| vec!["default".to_string()]);
+    let db = DB::open_cf(&options, path, cf_names).map_err(to_nif_err)?;
+    let handle = DbResource {
+        db: SharedDb::with_block_cache(db, options, parsed.block_cache),
+    };
+    Ok((atoms::ok(), ResourceArc::new(handle)).encode(env))
+}
+
+/// Same as `get_3/2` on handle `db`. Snapshots from `snapshot_new/0` belong to
+/// the `init/1` database, so passing one in `opts`, here or to `db_iterator/2`,
+/// returns `{:error, :snapshot_db_mismatch}`.
+#[rustler::nif(name = "db_get")]
+fn db_get<'a>(
+    env: Env<'a>,
+    db: ResourceArc<DbResource>,
+    key: Binary,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    get_with_opts(env, &db.db, &db.db.read(), None, key.as_slice(), opts)
+}
+
+/// Same as `get_4/3` on handle `db`.
+#[rustler::nif(name = "db_get_cf")]
+fn db_get_cf<'a>(
+    env: Env<'a>,
+    db: ResourceArc<DbResource>,
+    cf_name: String,
+    key: Binary,
+    opts: Term<'a>,
+) -> NifResult<Term<'a>> {
+    let handle = db.db.read();
+    let cf = cf_or_err(&handle, &cf_name)?;
+    get_with_opts(env, &db.db, &handle, Some(cf), key.as_slice(), opts)
+}
+
+/// Same as `put_4/3` on handle `db`; `:disable_wal` defaults to `false`.
+#[rustler::nif(name = "db_put")]
+fn db_put(db: ResourceArc<DbResource>, key: Binary, value: Binary, opts: Term) -> NifResult<bool> {
+    let write_opts = write_options_from(WriteOptions::default(), opts)?;
+    db.db
+        .read()
+        .put_opt(key.as_slice(), value.as_slice(), &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+/// Same as `put_5/4` on handle `db`; `:disable_wal` defaults to `false`.
+#[rustler::nif(name = "db_put_cf")]
+fn db_put_cf(
+    db: ResourceArc<DbResource>,
+    cf_name: String,
+    key: Binary,
+    value: Binary,
+    opts: Term,
 ) -> NifResult<bool> {
-    Err(Error::Atom("not_implemented"))
+    let handle = db.db.read();
+    let cf = cf_or_err(&handle, &cf_name)?;
+    let write_opts = write_options_from(WriteOptions::default(), opts)?;
+    handle
+        .put_cf_opt(cf, key.as_slice(), value.as_slice(), &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+/// Same as `delete_3/2` on handle `db`; `:disable_wal` defaults to `false`.
+#[rustler::nif(name = "db_delete")]
+fn db_delete(db: ResourceArc<DbResource>, key: Binary, opts: Term) -> NifResult<bool> {
+    let write_opts = write_options_from(WriteOptions::default(), opts)?;
+    db.db
+        .read()
+        .delete_opt(key.as_slice(), &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
 }
 
-fn load(env: Env, _: Term) -> bool {
+/// Same as `delete_4/3` on handle `db`; `:disable_wal` defaults to `false`.
+#[rustler::nif(name = "db_delete_cf")]
+fn db_delete_cf(
+    db: ResourceArc<DbResource>,
+    cf_name: String,
+    key: Binary,
+    opts: Term,
+) -> NifResult<bool> {
+    let handle = db.db.read();
+    let cf = cf_or_err(&handle, &cf_name)?;
+    let write_opts = write_options_from(WriteOptions::default(), opts)?;
+    handle
+        .delete_cf_opt(cf, key.as_slice(), &write_opts)
+        .map(|_| true)
+        .map_err(to_nif_err)
+}
+
+/// Same as `iterator/1` on handle `db`. The iterator keeps the database open, and
+/// works with `iterator_next/1` and the other iterator NIFs.
+#[rustler::nif(name = "db_iterator")]
+fn db_iterator(
+    env: Env,
+    db: ResourceArc<DbResource>,
+    opts: Term,
+) -> NifResult<ResourceArc<IteratorResource>> {
+    new_iterator(env, &db.db, &db.db.read(), None, opts)
+}
+
+/// Same as `iterator_2/2` on handle `db`.
+#[rustler::nif(name = "db_iterator_cf")]
+fn db_iterator_cf(
+    env: Env,
+    db: ResourceArc<DbResource>,
+    cf_name: String,
+    opts: Term,
+) -> NifResult<ResourceArc<IteratorResource>> {
+    let handle = db.db.read();
+    let cf = cf_or_err(&handle, &cf_name)?;
+    new_iterator(env, &db.db, &handle, Some(cf), opts)
+}
+
+// Called by the `load` of lib.rs, whose `init!` registers the NIFs of this module.
+pub(crate) fn load(env: Env, _: Term) -> bool {
+    let _ = rustler::resource!(DbResource, env);
     let _ = rustler::resource!(IteratorResource, env);
+    let _ = rustler::resource!(BatchResource, env);
+    let _ = rustler::resource!(CacheResource, env);
+    let _ = rustler::resource!(SnapshotResource, env);
+    let _ = rustler::resource!(StreamResource, env);
     true
 }
-
-// ---------- Rustler exports ----------
-rustler::init!("Elixir.RustlerRocksDB", load = load);
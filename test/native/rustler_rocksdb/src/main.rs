@@ -1,11 +1,132 @@
-use rocksdb::{IteratorMode, Options, DB};
+use rocksdb::{IteratorMode, Options, ReadOptions, SstFileWriter, DB};
 use std::env;
 use std::fs::{self, File};
-use std::io::{self, BufWriter, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+mod dump_format;
+
+use dump_format::{
+    dump_files, part_path, read_entry, read_header, DumpMode, DUMP_MAGIC, PART_MAGIC,
+};
 
 const DUMP_DIR: &str = "dump";
 
+/// Magic bytes (including a format version) opening an archive of dump files.
+const ARCHIVE_MAGIC: &[u8; 8] = b"AMAARCH\x01";
+
+/// Entries between two progress lines unless `--progress-interval` says otherwise.
+const DEFAULT_PROGRESS_INTERVAL: u64 = 1_000_000;
+
+/// Settings for a dump run, parsed from the command line.
+struct DumpConfig {
+    db_path: String,
+    /// Stop after this many entries per column family.
+    limit: Option<u64>,
+    mode: DumpMode,
+    /// Print a progress line to stderr every this many entries.
+    progress_interval: u64,
+    /// Suppress progress lines.
+    quiet: bool,
+    /// Verify block checksums and abort the whole dump on the first failure.
+    verify: bool,
+    /// Dump the write-ahead log with sequence numbers instead of the CFs.
+    internal: bool,
+    /// Remove the dump file of a CF that turned out to have no entries.
+    skip_empty: bool,
+    /// Do not dump the default CF at all.
+    skip_default: bool,
+    /// Start a new part file once the current one holds this many bytes.
+    shard_bytes: Option<u64>,
+}
+
+/// The outcome of dumping a single column family.
+struct DumpSummary {
+    entries: u64,
+    /// True when `--limit` cut the dump short and more entries were left.
+    truncated: bool,
+    /// The files written, in order: one, or every part with `--shard-bytes`.
+    files: Vec<PathBuf>,
+}
+
+/// Parses `[--limit N] [--keys-only | --values-only] [--progress-interval N] [--quiet]
+/// [--verify] [--internal] [--skip-empty] [--skip-default] [--shard-bytes N]
+/// <path-to-rocksdb>`.
+fn parse_args(args: &[String]) -> Result<DumpConfig, String> {
+    let mut db_path = None;
+    let mut limit = None;
+    let mut mode = DumpMode::Full;
+    let mut progress_interval = DEFAULT_PROGRESS_INTERVAL;
+    let mut quiet = false;
+    let mut verify = false;
+    let mut internal = false;
+    let mut skip_empty = false;
+    let mut skip_default = false;
+    let mut shard_bytes = None;
+
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--limit" => {
+                let value = iter.next().ok_or("--limit requires a value")?;
+                let n = value
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid --limit value: {}", value))?;
+                limit = Some(n);
+            }
+            "--keys-only" | "--values-only" => {
+                if mode != DumpMode::Full {
+                    return Err("--keys-only and --values-only are mutually exclusive".to_string());
+                }
+                mode = if arg == "--keys-only" {
+                    DumpMode::KeysOnly
+                } else {
+                    DumpMode::ValuesOnly
+                };
+            }
+            "--progress-interval" => {
+                let value = iter.next().ok_or("--progress-interval requires a value")?;
+                progress_interval = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid --progress-interval value: {}", value))?;
+            }
+            "--quiet" => quiet = true,
+            "--verify" => verify = true,
+            "--internal" => internal = true,
+            "--skip-empty" => skip_empty = true,
+            "--skip-default" => skip_default = true,
+            "--shard-bytes" => {
+                let value = iter.next().ok_or("--shard-bytes requires a value")?;
+                let n = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&n| n > 0)
+                    .ok_or_else(|| format!("Invalid --shard-bytes value: {}", value))?;
+                shard_bytes = Some(n);
+            }
+            flag if flag.starts_with("--") => return Err(format!("Unknown flag: {}", flag)),
+            path if db_path.is_none() => db_path = Some(path.to_string()),
+            extra => return Err(format!("Unexpected argument: {}", extra)),
+        }
+    }
+
+    Ok(DumpConfig {
+        db_path: db_path.ok_or("Missing <path-to-rocksdb>")?,
+        limit,
+        mode,
+        progress_interval,
+        quiet,
+        verify,
+        internal,
+        skip_empty,
+        skip_default,
+        shard_bytes,
+    })
+}
+
 /// A command-line tool to dump each column family of a RocksDB database
 /// into a separate binary file in a 'dump' subdirectory.
 ///
@@ -14,14 +135,119 @@ const DUMP_DIR: &str = "dump";
 /// - Key data ([u8])
 /// - Value length (4 bytes, u32 big-endian)
 /// - Value data ([u8])
+///
+/// With `--limit N` only the first N entries of each column family are written;
+/// the file format is unchanged, the file is just shorter.
+///
+/// With `--keys-only` or `--values-only` each entry carries just that one
+/// length-prefixed field. Such files start with a header so readers know which
+/// layout they hold:
+/// - Magic (8 bytes, `AMADUMP` followed by format version 1)
+/// - Mode (1 byte: 1 = keys only, 2 = values only)
+///
+/// Every `--progress-interval N` entries (default 1,000,000) a progress line with
+/// the entries and bytes written so far and the average rate goes to stderr.
+/// `--quiet` turns these lines off.
+///
+/// With `--verify` every block read is checked against its checksum and the first
+/// mismatch aborts the whole dump with exit code 2, naming the CF and the last key
+/// dumped before the corrupt block. Without it a CF that fails to dump is reported
+/// and the remaining CFs are still dumped.
+///
+/// With `--skip-empty` no dump file is left for a CF without entries, and with
+/// `--skip-default` the default CF is not dumped at all.
+///
+/// With `--shard-bytes N` a CF is written to `<cf>.part0.dump`, `<cf>.part1.dump`,
+/// ... instead, starting a new part before an entry that would take the current one
+/// past N bytes, so no entry spans two files. Every part starts with a header:
+/// - Magic (8 bytes, `AMAPART` followed by format version 1)
+/// - Mode (1 byte: 0 = keys and values, 1 = keys only, 2 = values only)
+/// - Part index (4 bytes, u32 big-endian)
+///
+/// With `--internal` the CFs are not dumped; instead every record still in the
+/// write-ahead log is written to `dump/internal.txt` with its sequence number,
+/// column family id and operation type, which shows tombstones and overwritten
+/// versions (see `dump_wal_records`).
+///
+/// Subcommands:
+/// - `checkintegrity <path-to-rocksdb>` - verify block checksums of every CF
+///   without writing anything (see `check_integrity`).
+/// - `dump-to-sst <dump-file> <out.sst>` - convert a dump into an SST file that can
+///   be loaded with `ingest_external_file` (see `dump_to_sst`).
+/// - `archive <dump-dir> <out-file>` - pack every `.dump` file of a directory into
+///   one archive with a table of contents (see `archive_dumps`).
+/// - `unarchive <archive> <out-dir>` - split an archive back into `.dump` files
+///   (see `unarchive_dumps`).
 fn main() {
     // --- 1. Parse Command-Line Arguments ---
     let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <path-to-rocksdb>", args[0]);
-        std::process::exit(1);
+    match args.get(1).map(String::as_str) {
+        Some("checkintegrity") => {
+            let Some(db_path) = args.get(2) else {
+                eprintln!("Usage: {} checkintegrity <path-to-rocksdb>", args[0]);
+                std::process::exit(1);
+            };
+            std::process::exit(check_integrity(db_path));
+        }
+        Some("dump-to-sst") => {
+            let (Some(dump_path), Some(sst_path)) = (args.get(2), args.get(3)) else {
+                eprintln!("Usage: {} dump-to-sst <dump-file> <out.sst>", args[0]);
+                std::process::exit(1);
+            };
+            match dump_to_sst(Path::new(dump_path), Path::new(sst_path)) {
+                Ok(count) => println!("Wrote {} entries to '{}'.", count, sst_path),
+                Err(e) => {
+                    eprintln!("Failed to convert '{}': {}", dump_path, e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some("archive") => {
+            let (Some(dump_dir), Some(archive_path)) = (args.get(2), args.get(3)) else {
+                eprintln!("Usage: {} archive <dump-dir> <out-file>", args[0]);
+                std::process::exit(1);
+            };
+            match archive_dumps(Path::new(dump_dir), Path::new(archive_path)) {
+                Ok(count) => println!("Archived {} dump files into '{}'.", count, archive_path),
+                Err(e) => {
+                    eprintln!("Failed to archive '{}': {}", dump_dir, e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        Some("unarchive") => {
+            let (Some(archive_path), Some(out_dir)) = (args.get(2), args.get(3)) else {
+                eprintln!("Usage: {} unarchive <archive> <out-dir>", args[0]);
+                std::process::exit(1);
+            };
+            match unarchive_dumps(Path::new(archive_path), Path::new(out_dir)) {
+                Ok(count) => println!("Extracted {} dump files into '{}'.", count, out_dir),
+                Err(e) => {
+                    eprintln!("Failed to unarchive '{}': {}", archive_path, e);
+                    std::process::exit(1);
+                }
+            }
+            return;
+        }
+        _ => {}
     }
-    let db_path = &args[1];
+
+    let config = match parse_args(&args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Usage: {} [--limit N] [--keys-only | --values-only] \
+                 [--progress-interval N] [--quiet] [--verify] [--internal] \
+                 [--skip-empty] [--skip-default] [--shard-bytes N] <path-to-rocksdb>",
+                args[0]
+            );
+            std::process::exit(1);
+        }
+    };
+    let db_path = &config.db_path;
     println!("Inspecting database at: {}", db_path);
 
     // --- 2. Create Output Directory ---
@@ -55,21 +281,67 @@ fn main() {
         Ok(db) => {
             println!("\nDatabase opened successfully. Starting dump process...");
 
+            if config.internal {
+                let output_path = Path::new(DUMP_DIR).join("internal.txt");
+                match dump_wal_records(&db, &output_path) {
+                    Ok(records) => println!(
+                        "  -> Wrote {} write-ahead log records to '{}'.",
+                        records,
+                        output_path.display()
+                    ),
+                    Err(e) => {
+                        eprintln!(
+                            "  -> An error occurred while reading the write-ahead log: {}",
+                            e
+                        );
+                        std::process::exit(1);
+                    }
+                }
+                return;
+            }
+
             // --- 5. Iterate Through and Dump Each Column Family ---
             for cf_name in &cf_names {
+                if config.skip_default && cf_name == "default" {
+                    println!("  -> Skipped 'default' (--skip-default).");
+                    continue;
+                }
+
                 // Construct the output path, e.g., "dump/contractstate.dump"
                 let mut output_path = PathBuf::from(DUMP_DIR);
                 output_path.push(cf_name);
                 output_path.set_extension("dump");
 
                 // Dump the current column family to its dedicated file.
-                match dump_cf_to_file(&db, cf_name, &output_path) {
-                    Ok(count) => {
+                match dump_cf_to_file(&db, cf_name, &output_path, &config) {
+                    Ok(summary) if config.skip_empty && summary.entries == 0 => {
+                        // Only known once the CF has been read, so the file is removed after the fact.
+                        match summary.files.iter().try_for_each(fs::remove_file) {
+                            Ok(()) => {
+                                println!("  -> Skipped '{}': no entries (--skip-empty).", cf_name)
+                            }
+                            Err(e) => eprintln!(
+                                "  -> Failed to remove empty dump '{}': {}",
+                                output_path.display(),
+                                e
+                            ),
+                        }
+                    }
+                    Ok(summary) => {
+                        let written = match summary.files.len() {
+                            1 => format!("'{}'", summary.files[0].display()),
+                            parts => format!("{} parts of '{}'", parts, output_path.display()),
+                        };
                         println!(
-                            "  -> Successfully dumped {} key-value pairs from '{}' to '{}'.",
-                            count,
+                            "  -> Successfully dumped {} key-value pairs from '{}' to {}{}.",
+                            summary.entries,
                             cf_name,
-                            output_path.display()
+                            written,
+                            if summary.truncated {
+                                " (truncated by --limit)"
+                            } else {
+                                ""
+                            }
                         );
                     }
                     Err(e) => {
@@ -77,6 +349,10 @@ fn main() {
                             "  -> An error occurred while dumping '{}': {}",
                             cf_name, e
                         );
+                        if config.verify {
+                            eprintln!("Aborting: --verify treats every read error as fatal.");
+                            std::process::exit(2);
+                        }
                     }
                 }
             }
@@ -88,6 +364,517 @@ fn main() {
     }
 }
 
+/// Renders a key as lowercase hex for diagnostics.
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Record tags of a write batch's serialized data, from RocksDB's `db/dbformat.h`.
+// The `CF_` tags carry a varint32 column family id after the tag; the others are
+// for the default CF, id 0.
+const TAG_DELETION: u8 = 0x00;
+const TAG_VALUE: u8 = 0x01;
+const TAG_MERGE: u8 = 0x02;
+const TAG_LOG_DATA: u8 = 0x03;
+const TAG_CF_DELETION: u8 = 0x04;
+const TAG_CF_VALUE: u8 = 0x05;
+const TAG_CF_MERGE: u8 = 0x06;
+const TAG_SINGLE_DELETION: u8 = 0x07;
+const TAG_CF_SINGLE_DELETION: u8 = 0x08;
+const TAG_BEGIN_PREPARE_XID: u8 = 0x09;
+const TAG_END_PREPARE_XID: u8 = 0x0A;
+const TAG_COMMIT_XID: u8 = 0x0B;
+const TAG_ROLLBACK_XID: u8 = 0x0C;
+const TAG_NOOP: u8 = 0x0D;
+const TAG_CF_RANGE_DELETION: u8 = 0x0E;
+const TAG_RANGE_DELETION: u8 = 0x0F;
+const TAG_BEGIN_PERSISTED_PREPARE_XID: u8 = 0x12;
+const TAG_BEGIN_UNPREPARE_XID: u8 = 0x13;
+
+// The sequence number (8 bytes) and record count (4 bytes) before the records.
+const BATCH_HEADER_LEN: usize = 12;
+
+/// Reads the varints and length-prefixed slices of a write batch's data.
+struct BatchReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> BatchReader<'a> {
+    fn byte(&mut self) -> io::Result<u8> {
+        let (&byte, rest) = self.data.split_first().ok_or_else(truncated_batch)?;
+        self.data = rest;
+        Ok(byte)
+    }
+
+    fn varint32(&mut self) -> io::Result<u32> {
+        let mut value = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            value |= u32::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Malformed varint in write batch",
+        ))
+    }
+
+    fn slice(&mut self) -> io::Result<&'a [u8]> {
+        let len = self.varint32()? as usize;
+        if self.data.len() < len {
+            return Err(truncated_batch());
+        }
+        let (slice, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(slice)
+    }
+}
+
+fn truncated_batch() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "Truncated write batch")
+}
+
+/// Writes one `<sequence> <cf id> <op> <key hex> <value hex>` line per record of
+/// a write batch, numbering the records from the batch's sequence number.
+struct InternalRecordWriter<W: Write> {
+    writer: W,
+    records: u64,
+}
+
+impl<W: Write> InternalRecordWriter<W> {
+    /// Decodes the serialized data of the batch starting at `sequence` itself,
+    /// since the rocksdb crate's `WriteBatchIterator` only reports puts and deletes
+    /// of the default CF.
+    fn write_batch(&mut self, mut sequence: u64, data: &[u8]) -> io::Result<()> {
+        let mut reader = BatchReader {
+            data: data.get(BATCH_HEADER_LEN..).ok_or_else(truncated_batch)?,
+        };
+        while !reader.data.is_empty() {
+            let tag = reader.byte()?;
+            let cf = match tag {
+                TAG_CF_DELETION
+                | TAG_CF_VALUE
+                | TAG_CF_MERGE
+                | TAG_CF_SINGLE_DELETION
+                | TAG_CF_RANGE_DELETION => reader.varint32()?,
+                _ => 0,
+            };
+            let (op, key, value) = match tag {
+                TAG_VALUE | TAG_CF_VALUE => ("Put", reader.slice()?, reader.slice()?),
+                TAG_DELETION | TAG_CF_DELETION => ("Delete", reader.slice()?, &[][..]),
+                TAG_MERGE | TAG_CF_MERGE => ("Merge", reader.slice()?, reader.slice()?),
+                TAG_SINGLE_DELETION | TAG_CF_SINGLE_DELETION => {
+                    ("SingleDelete", reader.slice()?, &[][..])
+                }
+                // The key field is the start of the range and the value its end.
+                TAG_RANGE_DELETION | TAG_CF_RANGE_DELETION => {
+                    ("DeleteRange", reader.slice()?, reader.slice()?)
+                }
+                // Markers that take no sequence number.
+                TAG_LOG_DATA | TAG_END_PREPARE_XID | TAG_COMMIT_XID | TAG_ROLLBACK_XID => {
+                    reader.slice()?;
+                    continue;
+                }
+                TAG_BEGIN_PREPARE_XID
+                | TAG_BEGIN_PERSISTED_PREPARE_XID
+                | TAG_BEGIN_UNPREPARE_XID
+                | TAG_NOOP => continue,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "Unsupported write batch record type 0x{:02x} at sequence {}",
+                            tag, sequence
+                        ),
+                    ))
+                }
+            };
+            writeln!(
+                self.writer,
+                "{} {} {} {} {}",
+                sequence,
+                cf,
+                op,
+                hex(key),
+                hex(value)
+            )?;
+            sequence += 1;
+            self.records += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Dumps the records of the write-ahead log with their internal sequence numbers.
+///
+/// The rocksdb crate has no `SstFileReader` or other way to iterate internal keys,
+/// so this only covers writes that are still in the WAL, i.e. not yet flushed and
+/// garbage collected. Puts, deletes, single deletes, range deletes and merges are
+/// listed for every CF, by the numeric id RocksDB gives the CF (0 for `default`).
+/// Deletes are written with an empty value field. Stops with an error at a record
+/// type it does not know, such as blob indexes or wide-column entities.
+///
+/// # Returns
+/// The number of records written.
+fn dump_wal_records(db: &DB, output_path: &Path) -> io::Result<u64> {
+    let to_io_err = |e: rocksdb::Error| io::Error::other(e.to_string());
+    let mut records = InternalRecordWriter {
+        writer: BufWriter::new(File::create(output_path)?),
+        records: 0,
+    };
+
+    for update in db.get_updates_since(0).map_err(to_io_err)? {
+        let (sequence, batch) = update.map_err(to_io_err)?;
+        records.write_batch(sequence, batch.data())?;
+    }
+
+    records.writer.flush()?;
+    Ok(records.records)
+}
+
+/// Reads every column family with block checksum verification enabled and
+/// reports a clean/dirty verdict per CF.
+///
+/// This is non-destructive and cheaper than a repair, but only catches corruption
+/// in blocks that are actually read, i.e. it does not validate deleted data.
+///
+/// # Returns
+/// The process exit code: 0 if every CF is clean, 2 if corruption was found,
+/// 1 if the database could not be opened.
+fn check_integrity(db_path: &str) -> i32 {
+    println!("Checking integrity of database at: {}", db_path);
+
+    let cf_names = match DB::list_cf(&Options::default(), db_path) {
+        Ok(names) => names,
+        Err(e) => {
+            eprintln!("Failed to list column families: {}.", e);
+            return 1;
+        }
+    };
+
+    let db = match DB::open_cf_for_read_only(&Options::default(), db_path, &cf_names, false) {
+        Ok(db) => db,
+        Err(e) => {
+            eprintln!("Failed to open database with column families: {}", e);
+            return 1;
+        }
+    };
+
+    let mut dirty_cfs = 0;
+    for cf_name in &cf_names {
+        let mut read_opts = ReadOptions::default();
+        read_opts.set_verify_checksums(true);
+        // A scan of everything should not evict the working set of a live node.
+        read_opts.fill_cache(false);
+
+        let mut iter = if cf_name == "default" {
+            db.raw_iterator_opt(read_opts)
+        } else {
+            match db.cf_handle(cf_name) {
+                Some(cf_handle) => db.raw_iterator_cf_opt(&cf_handle, read_opts),
+                None => {
+                    eprintln!("  {}: DIRTY (column family handle not found)", cf_name);
+                    dirty_cfs += 1;
+                    continue;
+                }
+            }
+        };
+
+        let mut entries: u64 = 0;
+        let mut last_good_key: Option<Vec<u8>> = None;
+        iter.seek_to_first();
+        while let Some(key) = iter.key() {
+            last_good_key = Some(key.to_vec());
+            entries += 1;
+            iter.next();
+        }
+
+        match iter.status() {
+            Ok(()) => println!("  {}: clean ({} entries verified)", cf_name, entries),
+            Err(e) => {
+                dirty_cfs += 1;
+                let after = match &last_good_key {
+                    Some(key) => format!("after key {}", hex(key)),
+                    None => "before the first key".to_string(),
+                };
+                println!(
+                    "  {}: DIRTY - corruption {} ({} entries read): {}",
+                    cf_name, after, entries, e
+                );
+            }
+        }
+    }
+
+    if dirty_cfs == 0 {
+        println!("\nAll {} column families are clean.", cf_names.len());
+        0
+    } else {
+        println!(
+            "\n{} of {} column families are dirty.",
+            dirty_cfs,
+            cf_names.len()
+        );
+        2
+    }
+}
+
+/// Writes the dump header for `mode`. Full dumps are written without one.
+fn write_header<W: Write>(writer: &mut W, mode: DumpMode) -> io::Result<()> {
+    if mode == DumpMode::Full {
+        return Ok(());
+    }
+    writer.write_all(DUMP_MAGIC)?;
+    writer.write_all(&[mode.to_byte()])
+}
+
+/// Writes the header of part `part` of a sharded dump.
+fn write_part_header<W: Write>(writer: &mut W, mode: DumpMode, part: u32) -> io::Result<()> {
+    writer.write_all(PART_MAGIC)?;
+    writer.write_all(&[mode.to_byte()])?;
+    writer.write_all(&part.to_be_bytes())
+}
+
+/// Converts a `.dump` file into an SST file via `SstFileWriter`.
+///
+/// Dumps come from an iterator and are therefore already sorted, but `SstFileWriter`
+/// requires strictly increasing keys, so the order is checked and the byte offset of
+/// the first out-of-order entry is reported. Only dumps of CFs that use the default
+/// bytewise comparator can be converted.
+///
+/// A sharded dump is read from its parts in order: pass the path without the part,
+/// e.g. `dump/contractstate.dump` for `dump/contractstate.part0.dump` and onwards.
+/// A part whose header carries another index than its name is rejected, as it was
+/// renamed or mixed up with another dump.
+///
+/// # Returns
+/// The number of entries written, or a description of the failure.
+fn dump_to_sst(dump_path: &Path, sst_path: &Path) -> Result<u64, String> {
+    let opts = Options::default();
+    let mut writer = SstFileWriter::create(&opts);
+    writer.open(sst_path).map_err(|e| e.to_string())?;
+
+    let mut count: u64 = 0;
+    let mut previous_key: Option<Vec<u8>> = None;
+    let files = dump_files(dump_path);
+    let sharded = files.len() > 1 || files[0] != dump_path;
+    for (index, path) in files.iter().enumerate() {
+        let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut reader = BufReader::new(file);
+        let (mode, part) = read_header(&mut reader).map_err(|e| e.to_string())?;
+        if mode != DumpMode::Full {
+            return Err(format!(
+                "dump was written in {:?} mode; an SST needs both keys and values",
+                mode
+            ));
+        }
+        if sharded && part != Some(index as u32) {
+            return Err(format!(
+                "'{}' is not part {} of the dump (its header says {:?})",
+                path.display(),
+                index,
+                part
+            ));
+        }
+
+        let mut offset = reader.stream_position().map_err(|e| e.to_string())?;
+        while let Some((key, value)) = read_entry(&mut reader).map_err(|e| {
+            format!(
+                "malformed entry in '{}' at offset {}: {}",
+                path.display(),
+                offset,
+                e
+            )
+        })? {
+            if let Some(previous) = &previous_key {
+                if key <= *previous {
+                    return Err(format!(
+                        "keys are not sorted: entry in '{}' at offset {} (key {}) does not sort after key {}",
+                        path.display(),
+                        offset,
+                        hex(&key),
+                        hex(previous)
+                    ));
+                }
+            }
+
+            writer.put(&key, &value).map_err(|e| e.to_string())?;
+            offset += 8 + key.len() as u64 + value.len() as u64;
+            count += 1;
+            previous_key = Some(key);
+        }
+    }
+
+    if count == 0 {
+        return Err("dump contains no entries; RocksDB cannot write an empty SST".to_string());
+    }
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(count)
+}
+
+/// Prints a progress line for a running column family dump to stderr.
+fn report_progress(cf_name: &str, entries: u64, bytes: u64, elapsed: Duration) {
+    let secs = elapsed.as_secs_f64().max(f64::EPSILON);
+    eprintln!(
+        "  .. '{}': {} entries, {:.1} MiB written ({:.0} entries/s, {:.1} MiB/s)",
+        cf_name,
+        entries,
+        bytes as f64 / (1024.0 * 1024.0),
+        entries as f64 / secs,
+        bytes as f64 / (1024.0 * 1024.0) / secs
+    );
+}
+
+/// One table-of-contents entry of an archive: where a CF's dump file lives in it.
+struct ArchiveEntry {
+    cf_name: String,
+    /// Byte offset of the dump data from the start of the archive.
+    offset: u64,
+    length: u64,
+}
+
+/// Packs every `<cf>.dump` file of `dump_dir` into a single archive.
+///
+/// The archive layout is:
+/// - Magic (8 bytes, `AMAARCH` followed by format version 1)
+/// - Entry count (4 bytes, u32 big-endian)
+/// - Per entry: CF name length (4 bytes, u32 big-endian), CF name (UTF-8),
+///   offset (8 bytes, u64 big-endian), length (8 bytes, u64 big-endian)
+/// - The dump files, byte for byte, in table-of-contents order
+///
+/// Offsets are absolute, so a reader can seek straight to one CF's dump after
+/// reading just the table of contents.
+///
+/// # Returns
+/// The number of dump files archived.
+fn archive_dumps(dump_dir: &Path, archive_path: &Path) -> io::Result<usize> {
+    let mut dumps = Vec::new();
+    for entry in fs::read_dir(dump_dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "dump") && path.is_file() {
+            let cf_name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("non UTF-8 dump file name: {}", path.display()),
+                    )
+                })?
+                .to_string();
+            dumps.push((cf_name, path));
+        }
+    }
+    dumps.sort();
+
+    // The table of contents is written before the data, so its size fixes the offsets.
+    let toc_len: u64 = dumps
+        .iter()
+        .map(|(cf_name, _)| 4 + cf_name.len() as u64 + 8 + 8)
+        .sum();
+    let mut offset = ARCHIVE_MAGIC.len() as u64 + 4 + toc_len;
+    let mut toc = Vec::with_capacity(dumps.len());
+    for (cf_name, path) in &dumps {
+        let length = fs::metadata(path)?.len();
+        toc.push(ArchiveEntry {
+            cf_name: cf_name.clone(),
+            offset,
+            length,
+        });
+        offset += length;
+    }
+
+    let mut writer = BufWriter::new(File::create(archive_path)?);
+    writer.write_all(ARCHIVE_MAGIC)?;
+    writer.write_all(&(toc.len() as u32).to_be_bytes())?;
+    for entry in &toc {
+        writer.write_all(&(entry.cf_name.len() as u32).to_be_bytes())?;
+        writer.write_all(entry.cf_name.as_bytes())?;
+        writer.write_all(&entry.offset.to_be_bytes())?;
+        writer.write_all(&entry.length.to_be_bytes())?;
+    }
+    for ((_, path), entry) in dumps.iter().zip(&toc) {
+        let copied = io::copy(&mut File::open(path)?, &mut writer)?;
+        if copied != entry.length {
+            return Err(io::Error::other(format!(
+                "'{}' changed size while being archived",
+                path.display()
+            )));
+        }
+    }
+    writer.flush()?;
+    Ok(toc.len())
+}
+
+/// Reads the table of contents of an archive written by `archive_dumps`.
+fn read_archive_toc<R: Read>(reader: &mut R) -> io::Result<Vec<ArchiveEntry>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != ARCHIVE_MAGIC {
+        return Err(invalid("not a dump archive"));
+    }
+
+    let mut u32_bytes = [0u8; 4];
+    let mut u64_bytes = [0u8; 8];
+    reader.read_exact(&mut u32_bytes)?;
+    let count = u32::from_be_bytes(u32_bytes);
+
+    let mut toc = Vec::new();
+    for _ in 0..count {
+        reader.read_exact(&mut u32_bytes)?;
+        let mut name = vec![0u8; u32::from_be_bytes(u32_bytes) as usize];
+        reader.read_exact(&mut name)?;
+        let cf_name = String::from_utf8(name).map_err(|_| invalid("non UTF-8 CF name"))?;
+        // The name becomes a file name on extraction; refuse anything that could
+        // escape the output directory.
+        if cf_name.is_empty() || cf_name.contains(['/', '\\']) || cf_name == ".." {
+            return Err(invalid("unsafe CF name in table of contents"));
+        }
+
+        reader.read_exact(&mut u64_bytes)?;
+        let offset = u64::from_be_bytes(u64_bytes);
+        reader.read_exact(&mut u64_bytes)?;
+        let length = u64::from_be_bytes(u64_bytes);
+        toc.push(ArchiveEntry {
+            cf_name,
+            offset,
+            length,
+        });
+    }
+    Ok(toc)
+}
+
+/// Splits an archive written by `archive_dumps` back into `<cf>.dump` files in
+/// `out_dir`, which is created if needed.
+///
+/// # Returns
+/// The number of dump files extracted.
+fn unarchive_dumps(archive_path: &Path, out_dir: &Path) -> io::Result<usize> {
+    let mut archive = File::open(archive_path)?;
+    let toc = read_archive_toc(&mut BufReader::new(&mut archive))?;
+    fs::create_dir_all(out_dir)?;
+
+    for entry in &toc {
+        archive.seek(io::SeekFrom::Start(entry.offset))?;
+        // Not `set_extension`: CF names may contain dots.
+        let output_path = out_dir.join(format!("{}.dump", entry.cf_name));
+        let mut writer = BufWriter::new(File::create(&output_path)?);
+
+        let copied = io::copy(&mut (&mut archive).take(entry.length), &mut writer)?;
+        if copied != entry.length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("archive is truncated inside '{}'", entry.cf_name),
+            ));
+        }
+        writer.flush()?;
+    }
+    Ok(toc.len())
+}
+
 /// Iterates through a specific column family and writes its contents to a file.
 ///
 /// This function correctly handles both the "default" CF and other named CFs.
@@ -95,20 +882,47 @@ fn main() {
 /// # Arguments
 /// * `db` - An open RocksDB instance.
 /// * `cf_name` - The name of the column family to dump.
-/// * `output_path` - The path to the file where the dump will be saved.
+/// * `output_path` - The path to the file where the dump will be saved. With
+///   `--shard-bytes` the parts are written next to it instead (see `part_path`).
+/// * `config` - The dump settings (`--limit`, output mode, sharding, progress reporting).
 ///
 /// # Returns
-/// A `Result` containing the number of key-value pairs dumped, or an `io::Error`.
-fn dump_cf_to_file(db: &DB, cf_name: &str, output_path: &Path) -> io::Result<u64> {
+/// A `Result` containing a `DumpSummary` of what was written, or an `io::Error`.
+fn dump_cf_to_file(
+    db: &DB,
+    cf_name: &str,
+    output_path: &Path,
+    config: &DumpConfig,
+) -> io::Result<DumpSummary> {
     // Create the output file and wrap it in a BufWriter for efficiency.
-    let file = File::create(output_path)?;
-    let mut writer = BufWriter::new(file);
+    let mut files = Vec::new();
+    let mut writer = if config.shard_bytes.is_some() {
+        files.push(part_path(output_path, 0));
+        let mut writer = BufWriter::new(File::create(&files[0])?);
+        write_part_header(&mut writer, config.mode, 0)?;
+        writer
+    } else {
+        files.push(output_path.to_path_buf());
+        let mut writer = BufWriter::new(File::create(output_path)?);
+        write_header(&mut writer, config.mode)?;
+        writer
+    };
+    let mut part_bytes: u64 = 0;
 
     // Get an iterator for the column family.
     // The method to get an iterator is different for the "default" CF
     // versus other named CFs.
+    let read_opts = || {
+        let mut read_opts = ReadOptions::default();
+        if config.verify {
+            read_opts.set_verify_checksums(true);
+            // Like `checkintegrity`, do not let a full scan evict the block cache.
+            read_opts.fill_cache(false);
+        }
+        read_opts
+    };
     let iter = if cf_name == "default" {
-        db.iterator(IteratorMode::Start) // For the default CF
+        db.iterator_opt(IteratorMode::Start, read_opts()) // For the default CF
     } else {
         // For a named CF, we must first get its handle.
         let cf_handle = db.cf_handle(cf_name).ok_or_else(|| {
@@ -117,33 +931,89 @@ fn dump_cf_to_file(db: &DB, cf_name: &str, output_path: &Path) -> io::Result<u64
                 format!("Column family '{}' handle not found", cf_name),
             )
         })?;
-        db.iterator_cf(&cf_handle, IteratorMode::Start)
+        db.iterator_cf_opt(&cf_handle, read_opts(), IteratorMode::Start)
     };
 
     let mut count: u64 = 0;
+    let mut bytes_written: u64 = 0;
+    let mut truncated = false;
+    let started = Instant::now();
+    // Only tracked with `--verify`, to say where a checksum failure was hit.
+    let mut last_key: Option<Box<[u8]>> = None;
 
     for item in iter {
+        if config.limit == Some(count) {
+            // We already pulled another item, so there really was more to dump.
+            truncated = true;
+            break;
+        }
+
         match item {
             Ok((key, value)) => {
+                // --- Start the Next Part if This Entry Does Not Fit ---
+                let entry_bytes = match config.mode {
+                    DumpMode::Full => 8 + key.len() + value.len(),
+                    DumpMode::KeysOnly => 4 + key.len(),
+                    DumpMode::ValuesOnly => 4 + value.len(),
+                } as u64;
+                if let Some(shard_bytes) = config.shard_bytes {
+                    if part_bytes > 0 && part_bytes + entry_bytes > shard_bytes {
+                        writer.flush()?;
+                        let part = files.len() as u32;
+                        files.push(part_path(output_path, part));
+                        writer = BufWriter::new(File::create(&files[files.len() - 1])?);
+                        write_part_header(&mut writer, config.mode, part)?;
+                        part_bytes = 0;
+                    }
+                }
+                part_bytes += entry_bytes;
+
                 // --- Serialize and Write Key ---
-                let key_len = key.len() as u32;
-                writer.write_all(&key_len.to_be_bytes())?;
-                writer.write_all(&key)?;
+                if config.mode != DumpMode::ValuesOnly {
+                    let key_len = key.len() as u32;
+                    writer.write_all(&key_len.to_be_bytes())?;
+                    writer.write_all(&key)?;
+                    bytes_written += 4 + key.len() as u64;
+                }
 
                 // --- Serialize and Write Value ---
-                let value_len = value.len() as u32;
-                writer.write_all(&value_len.to_be_bytes())?;
-                writer.write_all(&value)?;
+                if config.mode != DumpMode::KeysOnly {
+                    let value_len = value.len() as u32;
+                    writer.write_all(&value_len.to_be_bytes())?;
+                    writer.write_all(&value)?;
+                    bytes_written += 4 + value.len() as u64;
+                }
 
                 count += 1;
+                if config.verify {
+                    last_key = Some(key);
+                }
+                if !config.quiet && count.is_multiple_of(config.progress_interval) {
+                    report_progress(cf_name, count, bytes_written, started.elapsed());
+                }
+            }
+            Err(e) if config.verify => {
+                let after = match &last_key {
+                    Some(key) => format!("after key {}", hex(key)),
+                    None => "before the first key".to_string(),
+                };
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("read failed {} ({} entries dumped): {}", after, count, e),
+                ));
             }
             Err(e) => {
                 // If the database iterator returns an error, we convert it to an io::Error.
-                return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+                return Err(io::Error::other(e.to_string()));
             }
         }
     }
 
-    // The BufWriter is automatically flushed when it goes out of scope.
-    Ok(count)
+    // Flush explicitly: dropping the BufWriter would swallow a write error.
+    writer.flush()?;
+    Ok(DumpSummary {
+        entries: count,
+        truncated,
+        files,
+    })
 }
@@ -0,0 +1,31 @@
+defmodule BatchTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_cf(tmp_dir, ["items"]) == true
+    :ok
+  end
+
+  test "batch_write applies every operation at once" do
+    batch = RustlerRocksDB.batch_new()
+    assert RustlerRocksDB.batch_put(batch, "a", "1") == :ok
+    assert RustlerRocksDB.batch_put_cf(batch, "items", "b", "2") == :ok
+    assert RustlerRocksDB.get_3("a", []) == nil
+
+    assert RustlerRocksDB.batch_write(batch, []) == :ok
+    assert RustlerRocksDB.get_3("a", []) == {:ok, "1"}
+    assert RustlerRocksDB.get_4("b", [], "items") == {:ok, "2"}
+  end
+
+  test "a batch of column family operations is tied to its database", %{tmp_dir: tmp_dir} do
+    batch = RustlerRocksDB.batch_new()
+    assert RustlerRocksDB.batch_put_cf(batch, "items", "b", "2") == :ok
+    assert RustlerRocksDB.init_cf(Path.join(tmp_dir, "other"), ["items"]) == true
+
+    assert RustlerRocksDB.batch_delete_cf(batch, "items", "b") == {:error, :batch_db_mismatch}
+    assert RustlerRocksDB.batch_write(batch, []) == {:error, :batch_db_mismatch}
+    assert RustlerRocksDB.get_4("b", [], "items") == nil
+  end
+end
@@ -0,0 +1,39 @@
+defmodule BlockCacheTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  # Puts `count` keys into the open database and flushes them to an SST file, so
+  # reading them back goes through the block cache.
+  defp write_sst(prefix, count) do
+    for i <- 1..count do
+      key = "#{prefix}#{String.pad_leading(Integer.to_string(i), 4, "0")}"
+      assert RustlerRocksDB.put_4(key, :binary.bin_to_list(:crypto.strong_rand_bytes(256)), [])
+    end
+
+    assert RustlerRocksDB.flush_3([], true) == true
+  end
+
+  test "two databases opened with one cache share it", %{tmp_dir: tmp_dir} do
+    first = Path.join(tmp_dir, "first")
+    second = Path.join(tmp_dir, "second")
+
+    assert RustlerRocksDB.init(second) == true
+    write_sst("second", 200)
+
+    {:ok, cache} = RustlerRocksDB.create_cache(8 * 1024 * 1024)
+    assert RustlerRocksDB.init_with_opts(first, block_cache: cache) == true
+    write_sst("first", 200)
+
+    assert {:ok, _value} = RustlerRocksDB.get_3("first0001", [])
+    first_usage = RustlerRocksDB.cache_usage(cache)
+    assert first_usage > 0
+
+    {:ok, %{cache_total: cache_total}} = RustlerRocksDB.memory_usage()
+    assert cache_total == first_usage
+
+    {:ok, db} = RustlerRocksDB.db_open(second, block_cache: cache)
+    assert {:ok, _value} = RustlerRocksDB.db_get(db, "second0001", [])
+    assert RustlerRocksDB.cache_usage(cache) > first_usage
+  end
+end
@@ -0,0 +1,54 @@
+defmodule CompactRangeTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_with_opts(tmp_dir, compression: :none) == true
+    :ok
+  end
+
+  defp key(i), do: "key" <> String.pad_leading(Integer.to_string(i), 4, "0")
+
+  defp sst_size do
+    {:ok, levels} = RustlerRocksDB.levels_summary()
+    levels |> Enum.map(& &1.size_bytes) |> Enum.sum()
+  end
+
+  test "compacting a deleted range reclaims its space" do
+    for i <- 1..1000 do
+      value = :binary.bin_to_list(:crypto.strong_rand_bytes(1024))
+      assert RustlerRocksDB.put_4(key(i), value, []) == true
+    end
+
+    assert RustlerRocksDB.flush_3([], true) == true
+    assert RustlerRocksDB.delete_range(key(1), key(801)) == :ok
+    assert RustlerRocksDB.flush_3([], true) == true
+    before = sst_size()
+
+    assert RustlerRocksDB.compact_range_5(key(1), key(801), [], "default", 1) == true
+
+    assert sst_size() < before / 2
+    assert RustlerRocksDB.get_3(key(1), []) == nil
+    assert {:ok, _value} = RustlerRocksDB.get_3(key(1000), [])
+  end
+
+  test "nil bounds compact the whole column family" do
+    for i <- 1..100 do
+      assert RustlerRocksDB.put_4(key(i), [1, 2, 3], []) == true
+      if rem(i, 25) == 0, do: assert(RustlerRocksDB.flush_3([], true) == true)
+    end
+
+    assert RustlerRocksDB.compact_range_5(nil, nil, [], "default", -1) == true
+
+    {:ok, levels} = RustlerRocksDB.levels_summary()
+    assert [%{num_files: 1}] = Enum.filter(levels, &(&1.num_files > 0))
+  end
+
+  test "compact_range_5 rejects options and an unknown column family" do
+    assert {:error, _reason} =
+             RustlerRocksDB.compact_range_5(nil, nil, [exclusive: true], "default", 1)
+
+    assert RustlerRocksDB.compact_range_5(nil, nil, [], "nope", 1) == {:error, :cf_not_found}
+  end
+end
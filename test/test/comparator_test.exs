@@ -0,0 +1,30 @@
+defmodule ComparatorTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_with_opts(tmp_dir, comparator: :reverse_bytewise) == true
+    :ok
+  end
+
+  defp keys(iter, acc \\ []) do
+    case RustlerRocksDB.iterator_next(iter) do
+      {:ok, {key, _value}} -> keys(iter, [key | acc])
+      :finished -> Enum.reverse(acc)
+    end
+  end
+
+  test "iteration from the start returns the largest key first" do
+    assert RustlerRocksDB.multi_put([{"a", "1"}, {"c", "3"}, {"b", "2"}, {"ab", "4"}]) == true
+
+    assert keys(RustlerRocksDB.iterator([])) == ["c", "b", "ab", "a"]
+    assert keys(RustlerRocksDB.iterator(iterator_mode: :end)) == ["a", "ab", "b", "c"]
+  end
+
+  test "compare_keys follows the database order" do
+    assert RustlerRocksDB.compare_keys("a", "b") == :gt
+    assert RustlerRocksDB.compare_keys("b", "a") == :lt
+    assert RustlerRocksDB.compare_keys("a", "a") == :eq
+  end
+end
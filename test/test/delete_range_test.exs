@@ -0,0 +1,45 @@
+defmodule DeleteRangeTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_cf(tmp_dir, ["items"]) == true
+    :ok
+  end
+
+  defp keys(iter, acc \\ []) do
+    case RustlerRocksDB.iterator_next(iter) do
+      {:ok, {key, _value}} -> keys(iter, [key | acc])
+      :finished -> Enum.reverse(acc)
+    end
+  end
+
+  defp entries(keys), do: Enum.map(keys, &{&1, "value"})
+
+  test "delete_range removes the keys in [start, end)" do
+    assert RustlerRocksDB.multi_put(entries(["a", "b", "bb", "c", "d"])) == true
+    assert keys(RustlerRocksDB.iterator([])) == ["a", "b", "bb", "c", "d"]
+
+    assert RustlerRocksDB.delete_range("b", "c") == :ok
+    assert keys(RustlerRocksDB.iterator([])) == ["a", "c", "d"]
+  end
+
+  test "delete_range_cf removes the keys of the named column family only" do
+    assert RustlerRocksDB.multi_put(entries(["a", "b", "c"])) == true
+    assert RustlerRocksDB.multi_put_cf("items", entries(["a", "b", "c"])) == true
+
+    assert RustlerRocksDB.delete_range_cf("items", "a", "c") == :ok
+    assert keys(RustlerRocksDB.iterator_2([], "items")) == ["c"]
+    assert keys(RustlerRocksDB.iterator([])) == ["a", "b", "c"]
+  end
+
+  test "a snapshot taken before delete_range still sees the keys" do
+    assert RustlerRocksDB.multi_put(entries(["a", "b", "c"])) == true
+    {:ok, snapshot} = RustlerRocksDB.snapshot_new()
+
+    assert RustlerRocksDB.delete_range("a", "c") == :ok
+    assert keys(RustlerRocksDB.iterator(snapshot: snapshot)) == ["a", "b", "c"]
+    assert keys(RustlerRocksDB.iterator([])) == ["c"]
+  end
+end
@@ -0,0 +1,39 @@
+defmodule DeleteTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  @value :binary.bin_to_list("value")
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_cf(tmp_dir, ["items"]) == true
+    :ok
+  end
+
+  test "delete_3 removes a key from the default column family" do
+    assert RustlerRocksDB.put_4("key", @value, []) == true
+    assert RustlerRocksDB.delete_3("key", sync: true) == true
+    assert RustlerRocksDB.get_3("key", []) == nil
+  end
+
+  test "delete_3 of a key that does not exist returns true" do
+    assert RustlerRocksDB.delete_3("missing", []) == true
+  end
+
+  test "delete_4 removes a key from the named column family only" do
+    assert RustlerRocksDB.put_4("key", @value, []) == true
+    assert RustlerRocksDB.put_5("key", @value, [], "items") == true
+
+    assert RustlerRocksDB.delete_4("key", [], "items") == true
+    assert RustlerRocksDB.get_4("key", [], "items") == nil
+    assert RustlerRocksDB.get_3("key", []) == {:ok, "value"}
+  end
+
+  test "delete_4 of a key that does not exist returns true" do
+    assert RustlerRocksDB.delete_4("missing", [], "items") == true
+  end
+
+  test "delete_4 on an unknown column family returns cf_not_found" do
+    assert RustlerRocksDB.delete_4("key", [], "nope") == {:error, :cf_not_found}
+  end
+end
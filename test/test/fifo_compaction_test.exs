@@ -0,0 +1,54 @@
+defmodule FifoCompactionTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  @max_table_files_size 96 * 1024
+
+  setup %{tmp_dir: tmp_dir} do
+    opts = [
+      compaction_style: :fifo,
+      fifo_max_table_files_size: @max_table_files_size,
+      compression: :none
+    ]
+
+    assert RustlerRocksDB.init_with_opts(tmp_dir, opts) == true
+    :ok
+  end
+
+  defp sst_size do
+    {:ok, levels} = RustlerRocksDB.levels_summary()
+    levels |> Enum.map(& &1.size_bytes) |> Enum.sum()
+  end
+
+  # FIFO compactions run in the background after each flush.
+  defp wait_for_sst_size_at_most(bytes, attempts \\ 50) do
+    cond do
+      sst_size() <= bytes -> :ok
+      attempts == 0 -> flunk("SST files still hold #{sst_size()} bytes")
+      true ->
+        Process.sleep(100)
+        wait_for_sst_size_at_most(bytes, attempts - 1)
+    end
+  end
+
+  test "the oldest files are dropped once the size cap is exceeded" do
+    {:ok, options} = RustlerRocksDB.cf_options("default")
+    assert options["compaction_style"] == "kCompactionStyleFIFO"
+
+    # Six flushes of about 32 KiB each, twice the cap.
+    for file <- 1..6 do
+      for i <- 1..8 do
+        value = :binary.bin_to_list(:crypto.strong_rand_bytes(4096))
+        assert RustlerRocksDB.put_4("file#{file}-#{i}", value, []) == true
+      end
+
+      assert RustlerRocksDB.flush_3([], true) == true
+    end
+
+    wait_for_sst_size_at_most(@max_table_files_size)
+
+    assert RustlerRocksDB.get_3("file1-1", []) == nil
+    assert {:ok, _value} = RustlerRocksDB.get_3("file6-8", [])
+  end
+end
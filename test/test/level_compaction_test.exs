@@ -0,0 +1,20 @@
+defmodule LevelCompactionTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  test "level compaction shape options take effect", %{tmp_dir: tmp_dir} do
+    opts = [
+      target_file_size_base: 8 * 1024 * 1024,
+      max_bytes_for_level_base: 64 * 1024 * 1024,
+      level0_file_num_compaction_trigger: 8
+    ]
+
+    assert RustlerRocksDB.init_with_opts(tmp_dir, opts) == true
+
+    {:ok, options} = RustlerRocksDB.cf_options("default")
+    assert options["target_file_size_base"] == "8388608"
+    assert options["max_bytes_for_level_base"] == "67108864"
+    assert options["level0_file_num_compaction_trigger"] == "8"
+  end
+end
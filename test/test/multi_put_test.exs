@@ -0,0 +1,43 @@
+defmodule MultiPutTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_cf(tmp_dir, ["items"]) == true
+    :ok
+  end
+
+  test "multi_put writes every entry" do
+    assert RustlerRocksDB.multi_put([{"a", "1"}, {"b", "2"}, {"c", "3"}]) == true
+
+    assert RustlerRocksDB.get_3("a", []) == {:ok, "1"}
+    assert RustlerRocksDB.get_3("b", []) == {:ok, "2"}
+    assert RustlerRocksDB.get_3("c", []) == {:ok, "3"}
+  end
+
+  test "multi_put writes nothing if an entry is malformed" do
+    assert_raise ArgumentError, fn ->
+      RustlerRocksDB.multi_put([{"a", "1"}, {"b", 2}, {"c", "3"}])
+    end
+
+    assert RustlerRocksDB.get_3("a", []) == nil
+    assert RustlerRocksDB.get_3("c", []) == nil
+  end
+
+  test "multi_put_cf writes into the named column family only" do
+    assert RustlerRocksDB.multi_put_cf("items", [{"a", "1"}, {"b", "2"}]) == true
+
+    assert RustlerRocksDB.get_4("a", [], "items") == {:ok, "1"}
+    assert RustlerRocksDB.get_4("b", [], "items") == {:ok, "2"}
+    assert RustlerRocksDB.get_3("a", []) == nil
+  end
+
+  test "multi_put_cf writes nothing if an entry is malformed" do
+    assert_raise ArgumentError, fn ->
+      RustlerRocksDB.multi_put_cf("items", [{"a", "1"}, :b])
+    end
+
+    assert RustlerRocksDB.get_4("a", [], "items") == nil
+  end
+end
@@ -0,0 +1,43 @@
+defmodule PrefixIteratorTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  @keys ["aaa1", "aaa2", "bbb1", "bbb2", "bbb3", "ccc1"]
+
+  defp keys(iter, acc \\ []) do
+    case RustlerRocksDB.iterator_next(iter) do
+      {:ok, {key, _value}} -> keys(iter, [key | acc])
+      :finished -> Enum.reverse(acc)
+    end
+  end
+
+  defp put_keys do
+    assert RustlerRocksDB.multi_put(Enum.map(@keys, &{&1, "value"})) == true
+  end
+
+  test "a prefix scan returns only the keys of its prefix", %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_with_opts(tmp_dir, prefix_extractor: 3) == true
+    put_keys()
+
+    assert keys(RustlerRocksDB.iterator(prefix: "aaa")) == ["aaa1", "aaa2"]
+    assert keys(RustlerRocksDB.iterator(prefix: "bbb")) == ["bbb1", "bbb2", "bbb3"]
+    assert keys(RustlerRocksDB.iterator(prefix: "ccc")) == ["ccc1"]
+    assert keys(RustlerRocksDB.iterator(prefix: "ddd")) == []
+  end
+
+  test "a prefix scan also works from SST files", %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_with_opts(tmp_dir, prefix_extractor: 3) == true
+    put_keys()
+    assert RustlerRocksDB.flush_3([], true) == true
+
+    assert keys(RustlerRocksDB.iterator(prefix: "bbb")) == ["bbb1", "bbb2", "bbb3"]
+  end
+
+  test "a prefix scan works without a prefix extractor", %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init(tmp_dir) == true
+    put_keys()
+
+    assert keys(RustlerRocksDB.iterator(prefix: "bbb")) == ["bbb1", "bbb2", "bbb3"]
+  end
+end
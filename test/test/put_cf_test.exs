@@ -0,0 +1,23 @@
+defmodule PutCfTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init_cf(tmp_dir, ["items"]) == true
+    :ok
+  end
+
+  test "put_5 writes to the named column family" do
+    assert RustlerRocksDB.put_5("key", :binary.bin_to_list("value"), [sync: true], "items") ==
+             true
+
+    assert RustlerRocksDB.get_4("key", [], "items") == {:ok, "value"}
+    assert RustlerRocksDB.get_3("key", []) == nil
+  end
+
+  test "put_5 on an unknown column family returns cf_not_found" do
+    assert RustlerRocksDB.put_5("key", :binary.bin_to_list("value"), [], "nope") ==
+             {:error, :cf_not_found}
+  end
+end
@@ -0,0 +1,48 @@
+defmodule SnapshotTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init(tmp_dir) == true
+    :ok
+  end
+
+  defp entries(iter, acc \\ []) do
+    case RustlerRocksDB.iterator_next(iter) do
+      {:ok, entry} -> entries(iter, [entry | acc])
+      :finished -> Enum.reverse(acc)
+    end
+  end
+
+  test "writes after the snapshot are invisible to reads through it" do
+    assert RustlerRocksDB.multi_put([{"a", "1"}, {"b", "1"}]) == true
+    {:ok, snapshot} = RustlerRocksDB.snapshot_new()
+
+    assert RustlerRocksDB.multi_put([{"a", "2"}, {"c", "2"}]) == true
+    assert RustlerRocksDB.delete_3("b", []) == true
+
+    assert RustlerRocksDB.get_3("a", snapshot: snapshot) == {:ok, "1"}
+    assert RustlerRocksDB.get_3("b", snapshot: snapshot) == {:ok, "1"}
+    assert RustlerRocksDB.get_3("c", snapshot: snapshot) == nil
+    assert entries(RustlerRocksDB.iterator(snapshot: snapshot)) == [{"a", "1"}, {"b", "1"}]
+
+    assert RustlerRocksDB.get_3("a", []) == {:ok, "2"}
+    assert entries(RustlerRocksDB.iterator([])) == [{"a", "2"}, {"c", "2"}]
+  end
+
+  test "a released snapshot can no longer be read through" do
+    {:ok, snapshot} = RustlerRocksDB.snapshot_new()
+    assert RustlerRocksDB.snapshot_release(snapshot) == :ok
+    assert RustlerRocksDB.snapshot_release(snapshot) == :ok
+
+    assert RustlerRocksDB.get_3("a", snapshot: snapshot) == {:error, :snapshot_released}
+  end
+
+  test "a snapshot of a replaced database is rejected", %{tmp_dir: tmp_dir} do
+    {:ok, snapshot} = RustlerRocksDB.snapshot_new()
+    assert RustlerRocksDB.init(Path.join(tmp_dir, "other")) == true
+
+    assert RustlerRocksDB.get_3("a", snapshot: snapshot) == {:error, :snapshot_db_mismatch}
+  end
+end
@@ -0,0 +1,56 @@
+defmodule TransactionLockTimeoutTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  setup %{tmp_dir: tmp_dir} do
+    {:ok, db, ["default"]} =
+      RustlerRocksDB.open_transaction_db(tmp_dir, [create_if_missing: true], [{"default", []}])
+
+    {:ok, holder} = RustlerRocksDB.begin_transaction(db)
+    assert RustlerRocksDB.transaction_get_for_update_cf(holder, "default", "key", []) ==
+             {:ok, nil}
+
+    {:ok, holder: holder, db: db}
+  end
+
+  test "a zero timeout fails at once on a locked key", %{holder: holder, db: db} do
+    {:ok, txn} = RustlerRocksDB.begin_transaction(db)
+
+    assert RustlerRocksDB.transaction_put_4(txn, "key", [1], lock_timeout_ms: 0) ==
+             {:error, :timeout}
+
+    assert RustlerRocksDB.transaction_get_for_update_cf(txn, "default", "key",
+             lock_timeout_ms: 0
+           ) == {:error, :timeout}
+
+    assert RustlerRocksDB.rollback_transaction(holder) == :ok
+    assert RustlerRocksDB.transaction_put_4(txn, "key", [1], lock_timeout_ms: 0) == true
+    assert RustlerRocksDB.commit_transaction(txn) == :ok
+  end
+
+  test "a put waits for the lock up to its timeout", %{holder: holder, db: db} do
+    {:ok, txn} = RustlerRocksDB.begin_transaction(db)
+
+    {elapsed, result} =
+      :timer.tc(fn -> RustlerRocksDB.transaction_put_4(txn, "key", [1], lock_timeout_ms: 100) end)
+
+    assert result == {:error, :timeout}
+    assert elapsed >= 100_000
+
+    Task.start(fn ->
+      Process.sleep(50)
+      RustlerRocksDB.commit_transaction(holder)
+    end)
+
+    assert RustlerRocksDB.transaction_put_4(txn, "key", [2], lock_timeout_ms: 5000) == true
+    assert RustlerRocksDB.commit_transaction(txn) == :ok
+  end
+
+  test "unknown options are an error", %{db: db} do
+    {:ok, txn} = RustlerRocksDB.begin_transaction(db)
+
+    assert {:error, _reason} = RustlerRocksDB.transaction_put_4(txn, "other", [1], wait: 1)
+    assert_raise ArgumentError, fn -> RustlerRocksDB.transaction_put_4(txn, "other", [1], 5) end
+  end
+end
@@ -0,0 +1,35 @@
+defmodule UniversalCompactionTest do
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  test "universal compaction options take effect", %{tmp_dir: tmp_dir} do
+    opts = [
+      compaction_style: :universal,
+      universal_size_ratio: 10,
+      universal_min_merge_width: 4,
+      universal_max_size_amplification_percent: 300
+    ]
+
+    assert RustlerRocksDB.init_with_opts(tmp_dir, opts) == true
+
+    {:ok, options} = RustlerRocksDB.cf_options("default")
+    assert options["compaction_style"] == "kCompactionStyleUniversal"
+
+    universal = options["compaction_options_universal"]
+    assert universal =~ "size_ratio=10;"
+    assert universal =~ "min_merge_width=4;"
+    assert universal =~ "max_size_amplification_percent=300;"
+  end
+
+  test "unset universal options keep their defaults", %{tmp_dir: tmp_dir} do
+    opts = [compaction_style: :universal, universal_size_ratio: 10]
+    assert RustlerRocksDB.init_with_opts(tmp_dir, opts) == true
+
+    {:ok, options} = RustlerRocksDB.cf_options("default")
+    universal = options["compaction_options_universal"]
+    assert universal =~ "size_ratio=10;"
+    assert universal =~ "min_merge_width=2;"
+    assert universal =~ "max_size_amplification_percent=200;"
+  end
+end
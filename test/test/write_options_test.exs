@@ -0,0 +1,29 @@
+defmodule WriteOptionsTest do
+  # The NIFs share one database per VM, so these tests cannot run concurrently.
+  use ExUnit.Case, async: false
+
+  @moduletag :tmp_dir
+
+  # `put_4/3` takes the value as a list of bytes.
+  @value :binary.bin_to_list("value")
+
+  setup %{tmp_dir: tmp_dir} do
+    assert RustlerRocksDB.init(tmp_dir) == true
+    :ok
+  end
+
+  test "put_4 with sync: true writes a value that reads back" do
+    assert RustlerRocksDB.put_4("key", @value, sync: true) == true
+    assert RustlerRocksDB.get_3("key", []) == {:ok, "value"}
+  end
+
+  test "put_4 with disable_wal: true still writes to the memtable" do
+    assert RustlerRocksDB.put_4("key", @value, disable_wal: true) == true
+    assert RustlerRocksDB.get_3("key", []) == {:ok, "value"}
+  end
+
+  test "put_4 rejects an unknown write option" do
+    assert {:error, _reason} = RustlerRocksDB.put_4("key", @value, fsync: true)
+    assert RustlerRocksDB.get_3("key", []) == nil
+  end
+end